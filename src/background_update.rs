@@ -1,11 +1,13 @@
 use std::borrow::BorrowMut;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use crate::oauth2;
 
 use chrono::Utc;
 
-use crate::db::{PhotoDb, PhotoDbRo, SqliteDb};
+use crate::db::{CategoryDb, MetadataDb, PhotoDb, PhotoDbRo, SharedAlbumDb, SqliteDb};
+use crate::domain::{ContentCategory, GoogleId};
+use crate::notify::Notifier;
 use crate::photolib::{HttpRemotePhotoLib, RemotePhotoLibMetaData};
 
 pub trait BackgroundUpdate: Sync + Send {
@@ -18,46 +20,80 @@ pub trait BackgroundUpdate: Sync + Send {
     fn name(&self) -> &'static str;
 }
 
-pub struct BackgroundAlbumUpdate<C, A>
-where
-    C: BorrowMut<hyper::Client>,
-    A: oauth2::GetToken,
-{
-    pub remote_photo_lib: Arc<Mutex<HttpRemotePhotoLib<C, A>>>,
-    pub db: Arc<SqliteDb>,
+// When an update first runs after mounting, and how often it runs after that
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateSchedule {
+    pub delay_seconds: u64,
+    pub interval_seconds: u64,
 }
 
-unsafe impl<C, A> Sync for BackgroundAlbumUpdate<C, A>
-where
-    C: BorrowMut<hyper::Client>,
-    A: oauth2::GetToken,
-{
+impl UpdateSchedule {
+    pub fn delay(self) -> time::Duration {
+        time::Duration::seconds(self.delay_seconds as i64)
+    }
+
+    pub fn interval(self) -> time::Duration {
+        time::Duration::seconds(self.interval_seconds as i64)
+    }
 }
-unsafe impl<C, A> Send for BackgroundAlbumUpdate<C, A>
+
+pub const ALBUMS_SCHEDULE: UpdateSchedule = UpdateSchedule {
+    delay_seconds: 5,
+    interval_seconds: 12 * 60 * 60,
+};
+pub const SHARED_ALBUMS_SCHEDULE: UpdateSchedule = UpdateSchedule {
+    delay_seconds: 10,
+    interval_seconds: 12 * 60 * 60,
+};
+pub const MEDIA_ITEMS_SCHEDULE: UpdateSchedule = UpdateSchedule {
+    delay_seconds: 15,
+    interval_seconds: 2 * 24 * 60 * 60,
+};
+pub const CATEGORIES_SCHEDULE: UpdateSchedule = UpdateSchedule {
+    delay_seconds: 25,
+    interval_seconds: 24 * 60 * 60,
+};
+
+// A DB error skips the item instead of ending the refresh, the next one picks it up
+fn exists_or_skip(db: &SqliteDb, google_id: &GoogleId) -> bool {
+    match db.exists(google_id) {
+        Ok(exists) => exists,
+        Err(error) => {
+            warn!("Skipping media_item='{}' due to {:?}", google_id, error);
+            false
+        }
+    }
+}
+
+// Media items come by the thousand, reporting each would flood the journal
+const MEDIA_ITEMS_PER_STATUS: usize = 100;
+
+pub struct BackgroundAlbumUpdate<C, A>
 where
     C: BorrowMut<hyper::Client>,
     A: oauth2::GetToken,
 {
+    pub remote_photo_lib: Arc<HttpRemotePhotoLib<C, A>>,
+    pub db: Arc<SqliteDb>,
+    pub schedule: UpdateSchedule,
+    pub notifier: Notifier,
 }
 
 impl<C, A> BackgroundUpdate for BackgroundAlbumUpdate<C, A>
 where
-    C: BorrowMut<hyper::Client>,
-    A: oauth2::GetToken,
+    C: BorrowMut<hyper::Client> + Send,
+    A: oauth2::GetToken + Send,
 {
     fn update(&self) -> Result<(), String> {
         warn!("Start background albums refresh");
-        let albums;
-        {
-            let remote_photo_lib_unlocked = self
-                .remote_photo_lib
-                .lock()
-                .map_err(|err| format!("{:?}", err))?;
-            albums = remote_photo_lib_unlocked
-                .albums()
-                .map_err(|err| format!("{:?}", err))?;
-        }
-        for album in albums {
+        let albums = self
+            .remote_photo_lib
+            .albums()
+            .map_err(|err| format!("{:?}", err))?;
+        let total = albums.len();
+        for (done, album) in albums.into_iter().enumerate() {
+            self.notifier.progress("albums", done, total);
             match self
                 .db
                 .upsert_album(&album.google_id(), &album.name, &Utc::now())
@@ -65,19 +101,13 @@ where
                 Ok(inode) => debug!("upserted album='{:?}' into inode={:?}", album, inode),
                 Err(error) => error!("Failed to upsert album='{:?}' due to {:?}", album, error),
             }
-            let media_items_in_album;
-            {
-                let remote_photo_lib_unlocked = self
-                    .remote_photo_lib
-                    .lock()
-                    .map_err(|err| format!("{:?}", err))?;
-                media_items_in_album = remote_photo_lib_unlocked
-                    .album(&album.google_id())
-                    .map_err(|err| format!("{:?}", err))?;
-            }
+            let media_items_in_album = self
+                .remote_photo_lib
+                .album(&album.google_id())
+                .map_err(|err| format!("{:?}", err))?;
             media_items_in_album
                 .iter()
-                .filter(|item| self.db.exists(item.google_id()).unwrap())
+                .filter(|item| exists_or_skip(&self.db, item.google_id()))
                 .for_each(|media_item_in_album| {
                     warn!("Found {} in album {}", media_item_in_album.name, album.name);
                     match self.db.upsert_media_item_in_album(
@@ -95,17 +125,18 @@ where
                     }
                 });
         }
+        self.notifier.status("albums up to date");
         warn!("End background albums refresh");
 
         Result::Ok(())
     }
 
     fn delay(&self) -> time::Duration {
-        time::Duration::seconds(5)
+        self.schedule.delay()
     }
 
     fn interval(&self) -> time::Duration {
-        time::Duration::hours(12)
+        self.schedule.interval()
     }
 
     fn name(&self) -> &'static str {
@@ -113,47 +144,139 @@ where
     }
 }
 
-pub struct BackgroundMediaUpdate<C, A>
+pub struct BackgroundSharedAlbumUpdate<C, A>
 where
     C: BorrowMut<hyper::Client>,
     A: oauth2::GetToken,
 {
-    pub remote_photo_lib: Arc<Mutex<HttpRemotePhotoLib<C, A>>>,
+    pub remote_photo_lib: Arc<HttpRemotePhotoLib<C, A>>,
     pub db: Arc<SqliteDb>,
+    pub schedule: UpdateSchedule,
+    pub notifier: Notifier,
 }
 
-unsafe impl<C, A> Sync for BackgroundMediaUpdate<C, A>
+impl<C, A> BackgroundUpdate for BackgroundSharedAlbumUpdate<C, A>
 where
-    C: BorrowMut<hyper::Client>,
-    A: oauth2::GetToken,
+    C: BorrowMut<hyper::Client> + Send,
+    A: oauth2::GetToken + Send,
 {
+    fn update(&self) -> Result<(), String> {
+        warn!("Start background shared albums refresh");
+        let shared_albums = self
+            .remote_photo_lib
+            .shared_albums()
+            .map_err(|err| format!("{:?}", err))?;
+        let total = shared_albums.len();
+        for (done, (album, share_info)) in shared_albums.into_iter().enumerate() {
+            self.notifier.progress("shared albums", done, total);
+            match self.db.upsert_shared_album(
+                &album.google_id(),
+                &album.name,
+                &share_info,
+                &Utc::now(),
+            ) {
+                Ok(inode) => debug!("upserted shared album='{:?}' into inode={:?}", album, inode),
+                Err(error) => error!(
+                    "Failed to upsert shared album='{:?}' due to {:?}",
+                    album, error
+                ),
+            }
+            let media_items_in_album = self
+                .remote_photo_lib
+                .album(&album.google_id())
+                .map_err(|err| format!("{:?}", err))?;
+            for media_item_in_album in media_items_in_album {
+                // Media items shared by others are not part of the users library, so they
+                // are not found by the media items refresh.
+                match self.db.exists(media_item_in_album.google_id()) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        if let Err(error) = self.db.upsert_media_item(
+                            media_item_in_album.google_id(),
+                            &media_item_in_album.name,
+                            &Utc::now(),
+                        ) {
+                            error!(
+                                "Failed to upsert media_item='{:?}' due to {:?}",
+                                media_item_in_album, error
+                            );
+                            continue;
+                        }
+                    }
+                    Err(error) => {
+                        warn!(
+                            "Skipping media_item='{:?}' due to {:?}",
+                            media_item_in_album, error
+                        );
+                        continue;
+                    }
+                }
+                match self
+                    .db
+                    .upsert_media_item_in_album(album.google_id(), media_item_in_album.google_id())
+                {
+                    Ok(()) => debug!(
+                        "upsert media_item='{:?}' into shared album='{:?}'",
+                        media_item_in_album, album
+                    ),
+                    Err(error) => error!(
+                        "Failed to upsert media_item='{:?}' into shared album='{:?}' due to {:?}",
+                        media_item_in_album, album, error
+                    ),
+                }
+            }
+        }
+        self.notifier.status("shared albums up to date");
+        warn!("End background shared albums refresh");
+
+        Result::Ok(())
+    }
+
+    fn delay(&self) -> time::Duration {
+        self.schedule.delay()
+    }
+
+    fn interval(&self) -> time::Duration {
+        self.schedule.interval()
+    }
+
+    fn name(&self) -> &'static str {
+        "Shared Albums"
+    }
 }
-unsafe impl<C, A> Send for BackgroundMediaUpdate<C, A>
+
+pub struct BackgroundMediaUpdate<C, A>
 where
     C: BorrowMut<hyper::Client>,
     A: oauth2::GetToken,
 {
+    pub remote_photo_lib: Arc<HttpRemotePhotoLib<C, A>>,
+    pub db: Arc<SqliteDb>,
+    pub schedule: UpdateSchedule,
+    pub notifier: Notifier,
 }
 
 impl<C, A> BackgroundUpdate for BackgroundMediaUpdate<C, A>
 where
-    C: BorrowMut<hyper::Client>,
-    A: oauth2::GetToken,
+    C: BorrowMut<hyper::Client> + Send,
+    A: oauth2::GetToken + Send,
 {
     fn update(&self) -> Result<(), String> {
         {
             warn!("Start background media_items refresh");
-            let media_items;
-            {
-                let remote_photo_lib_unlocked = self
-                    .remote_photo_lib
-                    .lock()
-                    .map_err(|err| format!("{:?}", err))?;
-                media_items = remote_photo_lib_unlocked
-                    .media_items()
-                    .map_err(|err| format!("{:?}", err))?;
-            }
-            for media_item in media_items {
+            let media_items = self
+                .remote_photo_lib
+                .media_items()
+                .map_err(|err| format!("{:?}", err))?;
+            let total = media_items.len();
+            for (done, media_item) in media_items.into_iter().enumerate() {
+                if done % MEDIA_ITEMS_PER_STATUS == 0 {
+                    // Listings end on their own, this loop only touches the DB
+                    if self.remote_photo_lib.is_stopped() {
+                        return Result::Err(String::from("Stopped"));
+                    }
+                    self.notifier.progress("media items", done, total);
+                }
                 match self.db.upsert_media_item(
                     &media_item.google_id(),
                     &media_item.name,
@@ -163,27 +286,146 @@ where
                         "upserted media_item='{:?}' into inode={:?}",
                         media_item, inode
                     ),
-                    Err(error) => error!(
-                        "Failed to upsert media_item='{:?}' due to {:?}",
-                        media_item, error
-                    ),
+                    Err(error) => {
+                        error!(
+                            "Failed to upsert media_item='{:?}' due to {:?}",
+                            media_item, error
+                        );
+                        continue;
+                    }
+                }
+                if let Some(metadata) = &media_item.metadata {
+                    if let Err(error) = self
+                        .db
+                        .upsert_media_item_metadata(&media_item.google_id(), metadata)
+                    {
+                        error!(
+                            "Failed to upsert metadata of media_item='{:?}' due to {:?}",
+                            media_item, error
+                        );
+                    }
                 }
             }
             warn!("End background media_items refresh");
         }
 
+        {
+            warn!("Start background favourites refresh");
+            self.notifier.status("syncing favourites");
+            let favourites = self
+                .remote_photo_lib
+                .favourites()
+                .map_err(|err| format!("{:?}", err))?;
+            let media_item_ids: Vec<&GoogleId> = favourites
+                .iter()
+                .filter(|item| exists_or_skip(&self.db, item.google_id()))
+                .map(|item| item.google_id())
+                .collect();
+            match self.db.set_favourite_media_items(&media_item_ids) {
+                Ok(()) => debug!("set {} media_items as favourites", media_item_ids.len()),
+                Err(error) => error!("Failed to set favourite media_items due to {:?}", error),
+            }
+            warn!("End background favourites refresh");
+        }
+        self.notifier.status("media items up to date");
+
         Result::Ok(())
     }
 
     fn delay(&self) -> time::Duration {
-        time::Duration::seconds(15)
+        self.schedule.delay()
     }
 
     fn interval(&self) -> time::Duration {
-        time::Duration::days(2)
+        self.schedule.interval()
     }
 
     fn name(&self) -> &'static str {
         "Media Items"
     }
 }
+
+pub struct BackgroundCategoryUpdate<C, A>
+where
+    C: BorrowMut<hyper::Client>,
+    A: oauth2::GetToken,
+{
+    pub remote_photo_lib: Arc<HttpRemotePhotoLib<C, A>>,
+    pub db: Arc<SqliteDb>,
+    pub schedule: UpdateSchedule,
+    pub notifier: Notifier,
+    pub categories: Vec<ContentCategory>,
+}
+
+impl<C, A> BackgroundUpdate for BackgroundCategoryUpdate<C, A>
+where
+    C: BorrowMut<hyper::Client> + Send,
+    A: oauth2::GetToken + Send,
+{
+    fn update(&self) -> Result<(), String> {
+        warn!("Start background categories refresh");
+        for (done, category) in self.categories.iter().enumerate() {
+            self.notifier
+                .progress("categories", done, self.categories.len());
+            let media_items_in_category = self
+                .remote_photo_lib
+                .category(*category)
+                .map_err(|err| format!("{:?}", err))?;
+            let media_item_ids: Vec<&GoogleId> = media_items_in_category
+                .iter()
+                .filter(|item| exists_or_skip(&self.db, item.google_id()))
+                .map(|item| item.google_id())
+                .collect();
+            match self
+                .db
+                .set_media_items_in_category(*category, &media_item_ids)
+            {
+                Ok(()) => debug!(
+                    "set {} media_items into category='{}'",
+                    media_item_ids.len(),
+                    category
+                ),
+                Err(error) => error!(
+                    "Failed to set media_items into category='{}' due to {:?}",
+                    category, error
+                ),
+            }
+        }
+        self.notifier.status("categories up to date");
+        warn!("End background categories refresh");
+
+        Result::Ok(())
+    }
+
+    fn delay(&self) -> time::Duration {
+        self.schedule.delay()
+    }
+
+    fn interval(&self) -> time::Duration {
+        self.schedule.interval()
+    }
+
+    fn name(&self) -> &'static str {
+        "Categories"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::db::PhotoDb;
+
+    #[test]
+    fn exists_or_skip_closed_db() {
+        let db = SqliteDb::in_memory().unwrap();
+        db.upsert_media_item("GoogleIdMediaItem1", "Photo1.jpg", &Utc::now())
+            .unwrap();
+        assert!(exists_or_skip(&db, "GoogleIdMediaItem1"));
+        assert!(!exists_or_skip(&db, "GoogleIdMediaItem2"));
+
+        // Once closed at shutdown every item is skipped
+        db.close().unwrap();
+        assert!(!exists_or_skip(&db, "GoogleIdMediaItem1"));
+    }
+}
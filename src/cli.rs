@@ -0,0 +1,329 @@
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use crate::config::{Config, MountMode};
+
+#[derive(StructOpt, Debug, PartialEq)]
+#[structopt(name = "photooxide", about = "A Google Photos FUSE filesystem")]
+pub struct Options {
+    #[structopt(
+        short = "c",
+        long = "config",
+        parse(from_os_str),
+        help = "Config file [default: $XDG_CONFIG_HOME/photooxide/config.toml]"
+    )]
+    pub config: Option<PathBuf>,
+    #[structopt(long = "db", parse(from_os_str), help = "Local DB file")]
+    pub db: Option<PathBuf>,
+    #[structopt(
+        long = "client-secret",
+        parse(from_os_str),
+        help = "OAuth client secret JSON [default: $XDG_CONFIG_HOME/photooxide/client_secret.json]"
+    )]
+    pub client_secret: Option<PathBuf>,
+    #[structopt(subcommand)]
+    pub command: Command,
+}
+
+#[derive(StructOpt, Debug, PartialEq)]
+pub enum Command {
+    #[structopt(
+        name = "mount",
+        about = "Mount the library, refreshing it in the background"
+    )]
+    Mount {
+        #[structopt(parse(from_os_str), help = "Mountpoint, if not configured")]
+        mountpoint: Option<PathBuf>,
+        #[structopt(
+            short = "o",
+            long = "option",
+            number_of_values = 1,
+            help = "Further FUSE mount option"
+        )]
+        options: Vec<String>,
+        #[structopt(long = "allow-other", help = "Allow other users to access the mount")]
+        allow_other: bool,
+        #[structopt(
+            long = "read-write",
+            help = "Use the token with upload and sharing access, see auth --read-write"
+        )]
+        read_write: bool,
+        #[structopt(
+            long = "no-refresh",
+            help = "Do not refresh the local DB while mounted"
+        )]
+        no_refresh: bool,
+        #[structopt(
+            long = "daemon",
+            conflicts_with = "foreground",
+            help = "Detach from the terminal once authorized"
+        )]
+        daemon: bool,
+        #[structopt(
+            long = "foreground",
+            help = "Stay in the foreground, as systemd expects"
+        )]
+        foreground: bool,
+        #[structopt(
+            long = "pid-file",
+            parse(from_os_str),
+            help = "Write the pid of the mount to this file"
+        )]
+        pid_file: Option<PathBuf>,
+    },
+    #[structopt(name = "sync", about = "Refresh the local DB from Google Photos once")]
+    Sync,
+    #[structopt(
+        name = "auth",
+        about = "Authorize access to Google Photos and store the token"
+    )]
+    Auth {
+        #[structopt(
+            long = "device",
+            help = "Enter a code shown here on another device, instead of pasting one back"
+        )]
+        device: bool,
+        #[structopt(
+            long = "read-write",
+            help = "Also allow uploading and sharing, needed for read-write mounts"
+        )]
+        read_write: bool,
+    },
+    #[structopt(
+        name = "logout",
+        about = "Revoke the stored token with Google and remove it"
+    )]
+    Logout {
+        #[structopt(
+            long = "purge",
+            help = "Also remove the albums and media items cached in the local DB"
+        )]
+        purge: bool,
+    },
+    #[structopt(name = "status", about = "Show what is in the local DB")]
+    Status,
+    #[structopt(name = "ls", about = "List albums, or the media items of one album")]
+    Ls {
+        #[structopt(short = "m", long = "media", help = "List all media items")]
+        media: bool,
+        album: Option<String>,
+    },
+    #[structopt(name = "get", about = "Download a media item by name or id")]
+    Get {
+        item: String,
+        #[structopt(
+            short = "o",
+            long = "output",
+            parse(from_os_str),
+            help = "Write to a file instead of stdout"
+        )]
+        output: Option<PathBuf>,
+    },
+}
+
+impl Options {
+    // Command line options take precedence over the config file and environment
+    pub fn apply(&self, config: &mut Config) {
+        if let Option::Some(ref db) = self.db {
+            config.db_path = db.clone();
+        }
+        if let Option::Some(ref client_secret) = self.client_secret {
+            config.client_secret = Option::Some(client_secret.clone());
+        }
+        match self.command {
+            Command::Mount { read_write, .. } | Command::Auth { read_write, .. } if read_write => {
+                config.mount.mode = MountMode::ReadWrite;
+            }
+            _ => {}
+        }
+        if let Command::Mount {
+            ref mountpoint,
+            ref options,
+            allow_other,
+            no_refresh,
+            daemon,
+            foreground,
+            ref pid_file,
+            ..
+        } = self.command
+        {
+            if let Option::Some(ref mountpoint) = mountpoint {
+                config.mount.mountpoint = Option::Some(mountpoint.clone());
+            }
+            config.mount.options.extend(options.iter().cloned());
+            if allow_other {
+                config.mount.allow_other = true;
+            }
+            if no_refresh {
+                config.refresh.enabled = false;
+            }
+            if daemon {
+                config.mount.daemon = true;
+            }
+            if foreground {
+                config.mount.daemon = false;
+            }
+            if let Option::Some(ref pid_file) = pid_file {
+                config.mount.pid_file = Option::Some(pid_file.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, structopt::clap::Error> {
+        Options::from_iter_safe(args).map(|options| options.command)
+    }
+
+    fn mount(mountpoint: Option<&str>) -> Command {
+        Command::Mount {
+            mountpoint: mountpoint.map(PathBuf::from),
+            options: Vec::new(),
+            allow_other: false,
+            read_write: false,
+            no_refresh: false,
+            daemon: false,
+            foreground: false,
+            pid_file: Option::None,
+        }
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(
+            parse(&["photooxide", "mount", "photo_mount"]).unwrap(),
+            mount(Option::Some("photo_mount"))
+        );
+        assert_eq!(
+            parse(&["photooxide", "mount"]).unwrap(),
+            mount(Option::None)
+        );
+        assert_eq!(parse(&["photooxide", "sync"]).unwrap(), Command::Sync);
+        assert_eq!(
+            parse(&["photooxide", "auth"]).unwrap(),
+            Command::Auth {
+                device: false,
+                read_write: false
+            }
+        );
+        assert_eq!(
+            parse(&["photooxide", "auth", "--device", "--read-write"]).unwrap(),
+            Command::Auth {
+                device: true,
+                read_write: true
+            }
+        );
+        assert_eq!(
+            parse(&["photooxide", "logout"]).unwrap(),
+            Command::Logout { purge: false }
+        );
+        assert_eq!(
+            parse(&["photooxide", "logout", "--purge"]).unwrap(),
+            Command::Logout { purge: true }
+        );
+        assert_eq!(parse(&["photooxide", "status"]).unwrap(), Command::Status);
+        assert_eq!(
+            parse(&["photooxide", "ls"]).unwrap(),
+            Command::Ls {
+                media: false,
+                album: Option::None
+            }
+        );
+        assert_eq!(
+            parse(&["photooxide", "ls", "Holidays"]).unwrap(),
+            Command::Ls {
+                media: false,
+                album: Option::Some(String::from("Holidays"))
+            }
+        );
+        assert_eq!(
+            parse(&["photooxide", "ls", "--media"]).unwrap(),
+            Command::Ls {
+                media: true,
+                album: Option::None
+            }
+        );
+        assert_eq!(
+            parse(&["photooxide", "get", "IMG_0001.jpg", "-o", "out.jpg"]).unwrap(),
+            Command::Get {
+                item: String::from("IMG_0001.jpg"),
+                output: Option::Some(PathBuf::from("out.jpg"))
+            }
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse(&["photooxide"]).is_err());
+        assert!(parse(&["photooxide", "mount", "-o"]).is_err());
+        assert!(parse(&["photooxide", "mount", "--daemon", "--foreground"]).is_err());
+        assert!(parse(&["photooxide", "get"]).is_err());
+        assert!(parse(&["photooxide", "unknown"]).is_err());
+    }
+
+    #[test]
+    fn apply_options() {
+        let options = Options::from_iter(&[
+            "photooxide",
+            "--db",
+            "other.sqlite",
+            "--client-secret",
+            "secret.json",
+            "mount",
+            "-o",
+            "noatime",
+            "--option",
+            "uid=1000",
+            "--allow-other",
+            "--read-write",
+            "--no-refresh",
+            "--daemon",
+            "--pid-file",
+            "photooxide.pid",
+            "photo_mount",
+        ]);
+        let mut config = Config::default();
+        config.mount.options = vec![String::from("nosuid")];
+        options.apply(&mut config);
+
+        assert_eq!(config.db_path, PathBuf::from("other.sqlite"));
+        assert_eq!(
+            config.client_secret_path(),
+            Option::Some(PathBuf::from("secret.json"))
+        );
+        assert_eq!(
+            config.mount.mountpoint,
+            Option::Some(PathBuf::from("photo_mount"))
+        );
+        assert_eq!(config.mount.options, vec!["nosuid", "noatime", "uid=1000"]);
+        assert!(config.mount.allow_other);
+        assert_eq!(config.mount.mode, MountMode::ReadWrite);
+        assert!(!config.refresh.enabled);
+        assert!(config.mount.daemon);
+        assert_eq!(
+            config.mount.pid_file,
+            Option::Some(PathBuf::from("photooxide.pid"))
+        );
+
+        // The config file can be overridden either way
+        let mut config = Config::default();
+        config.mount.daemon = true;
+        Options::from_iter(&["photooxide", "mount", "--foreground"]).apply(&mut config);
+        assert!(!config.mount.daemon);
+
+        let mut config = Config::default();
+        Options::from_iter(&["photooxide", "auth", "--read-write"]).apply(&mut config);
+        assert_eq!(config.mount.mode, MountMode::ReadWrite);
+
+        // Other commands and unset options leave the config as it is
+        let mut config = Config::default();
+        Options::from_iter(&["photooxide", "status"]).apply(&mut config);
+        assert_eq!(config, Config::default());
+        Options::from_iter(&["photooxide", "mount"]).apply(&mut config);
+        assert_eq!(config, Config::default());
+    }
+}
@@ -0,0 +1,91 @@
+use std::fs;
+use std::option::Option;
+use std::path::Path;
+
+use crate::oauth2::{ApplicationSecret, ConsoleApplicationSecret};
+use serde_json;
+
+use crate::config::Config;
+use crate::error::PhotoOxideError;
+
+// Built in from client_secret.json next to Cargo.toml, only used when no file is found
+#[cfg(feature = "embedded-client-secret")]
+const EMBEDDED_CLIENT_SECRET: Option<&str> = Option::Some(include_str!("../client_secret.json"));
+#[cfg(not(feature = "embedded-client-secret"))]
+const EMBEDDED_CLIENT_SECRET: Option<&str> = Option::None;
+
+pub fn client_secret(config: &Config) -> Result<ApplicationSecret, PhotoOxideError> {
+    match config.client_secret_path() {
+        Option::Some(path) => from_file(&path),
+        Option::None => match EMBEDDED_CLIENT_SECRET {
+            Option::Some(json) => from_json(json),
+            Option::None => Result::Err(PhotoOxideError::ConfigError(format!(
+                "No OAuth client secret. Create an OAuth client ID for a desktop app in the \
+                 Google API console and save its JSON as {}, or give its path with \
+                 --client-secret, PHOTOOXIDE_CLIENT_SECRET or client_secret in the config file",
+                Config::default_client_secret_path()
+                    .map(|path| format!("{}", path.display()))
+                    .unwrap_or_else(|| String::from("client_secret.json"))
+            ))),
+        },
+    }
+}
+
+fn from_file(path: &Path) -> Result<ApplicationSecret, PhotoOxideError> {
+    let json = fs::read_to_string(path).map_err(|error| {
+        PhotoOxideError::ConfigError(format!(
+            "Failed to read the OAuth client secret {}: {}",
+            path.display(),
+            error
+        ))
+    })?;
+    from_json(&json)
+}
+
+// Only client IDs for installed (desktop) apps work with the console flow
+fn from_json(json: &str) -> Result<ApplicationSecret, PhotoOxideError> {
+    let secret = serde_json::from_str::<ConsoleApplicationSecret>(json).map_err(|error| {
+        PhotoOxideError::ConfigError(format!("Invalid OAuth client secret: {}", error))
+    })?;
+    secret.installed.ok_or_else(|| {
+        PhotoOxideError::ConfigError(String::from("OAuth client secret is not for a desktop app"))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    fn travis_client_secret() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("client_secret.travis.json")
+    }
+
+    #[test]
+    fn client_secret_from_file() {
+        let mut config = Config {
+            client_secret: Option::Some(travis_client_secret()),
+            ..Config::default()
+        };
+        assert_eq!(
+            client_secret(&config).unwrap().client_id,
+            "NOT_A_REAL_CLIENT_ID"
+        );
+
+        // A configured file has to exist
+        config.client_secret = Option::Some(PathBuf::from("/nonexistent/client_secret.json"));
+        assert!(client_secret(&config).is_err());
+    }
+
+    #[test]
+    fn client_secret_from_json() {
+        assert!(from_json("{}").is_err());
+        assert!(from_json("not json").is_err());
+        // Web app client IDs can not be used
+        let web = fs::read_to_string(travis_client_secret())
+            .unwrap()
+            .replace("installed", "web");
+        assert!(from_json(&web).is_err());
+    }
+}
@@ -0,0 +1,585 @@
+use std::env;
+use std::fs;
+use std::option::Option;
+use std::path::{Path, PathBuf};
+
+use crate::background_update::{
+    UpdateSchedule, ALBUMS_SCHEDULE, CATEGORIES_SCHEDULE, MEDIA_ITEMS_SCHEDULE,
+    SHARED_ALBUMS_SCHEDULE,
+};
+use crate::domain::ContentCategory;
+use crate::error::PhotoOxideError;
+use crate::photofs::PhotoFsConfig;
+use crate::photolib::{PHOTOS_APPENDONLY_SCOPE, PHOTOS_READONLY_SCOPE, PHOTOS_SHARING_SCOPE};
+
+// Directory under the XDG config and data directories
+const APP_DIR: &str = "photooxide";
+const CONFIG_FILE: &str = "config.toml";
+const CLIENT_SECRET_FILE: &str = "client_secret.json";
+const DB_FILE: &str = "cache.sqlite";
+const TOKEN_KEY_FILE: &str = "token.key";
+
+const DEFAULT_LOG_FILTER: &str =
+    "photooxide=info,photooxide::db=debug,photooxide::photofs=error,photooxide::photolib=debug";
+const DEFAULT_FSNAME: &str = "photooxide";
+const DEFAULT_CATEGORIES: [&str; 6] = [
+    "LANDSCAPES",
+    "SCREENSHOTS",
+    "RECEIPTS",
+    "DOCUMENTS",
+    "PETS",
+    "SELFIES",
+];
+
+// Everything configurable, from defaults, the config file, PHOTOOXIDE_* environment
+// variables and the command line, each overriding the one before
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Local DB with the library listing and the OAuth token
+    pub db_path: PathBuf,
+    // OAuth client secret JSON, as downloaded from the Google API console
+    pub client_secret: Option<PathBuf>,
+    // env_logger filter, RUST_LOG takes precedence
+    pub log_filter: String,
+    pub mount: MountConfig,
+    pub refresh: RefreshConfig,
+    pub filesystem: PhotoFsConfig,
+    pub token_key: TokenKeyConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MountConfig {
+    // Used when the mount command is not given one
+    pub mountpoint: Option<PathBuf>,
+    // Decides the OAuth scopes, and so the stored token, used by every command
+    pub mode: MountMode,
+    // Name of the filesystem in mount and df
+    pub fsname: String,
+    // Needs user_allow_other in /etc/fuse.conf unless mounted by root
+    pub allow_other: bool,
+    // Further FUSE options, as given to mount with -o
+    pub options: Vec<String>,
+    // Detach from the terminal once authorized, systemd services run in the foreground
+    pub daemon: bool,
+    // Holds the pid of the mount while it runs
+    pub pid_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RefreshConfig {
+    // Refresh the local DB in the background while mounted
+    pub enabled: bool,
+    // Content categories listed under /categories
+    pub content_categories: Vec<String>,
+    pub albums: UpdateSchedule,
+    pub shared_albums: UpdateSchedule,
+    pub media_items: UpdateSchedule,
+    pub categories: UpdateSchedule,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MountMode {
+    #[serde(alias = "ro")]
+    ReadOnly,
+    // Asks for upload and sharing access too, the filesystem has no write support yet
+    #[serde(alias = "rw")]
+    ReadWrite,
+}
+
+impl MountMode {
+    pub fn scopes(self) -> Vec<String> {
+        let scopes: &[&str] = match self {
+            MountMode::ReadOnly => &[PHOTOS_READONLY_SCOPE],
+            MountMode::ReadWrite => &[
+                PHOTOS_READONLY_SCOPE,
+                PHOTOS_APPENDONLY_SCOPE,
+                PHOTOS_SHARING_SCOPE,
+            ],
+        };
+        scopes.iter().map(|scope| String::from(*scope)).collect()
+    }
+
+    pub fn is_writable(self) -> bool {
+        self == MountMode::ReadWrite
+    }
+}
+
+impl std::str::FromStr for MountMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_lowercase().as_str() {
+            "read-only" | "ro" => Result::Ok(MountMode::ReadOnly),
+            "read-write" | "rw" => Result::Ok(MountMode::ReadWrite),
+            _ => Result::Err(format!("Unknown mount mode {:?}", mode)),
+        }
+    }
+}
+
+// Where the key encrypting the OAuth tokens in the local DB comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKeySource {
+    // Generated on first use, readable by the owner only
+    File,
+    // Generated on first use and kept in the OS keyring, needs the keyring feature
+    Keyring,
+    // Derived from PHOTOOXIDE_TOKEN_PASSPHRASE, else asked for on the terminal
+    Passphrase,
+}
+
+impl std::str::FromStr for TokenKeySource {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source.to_lowercase().as_str() {
+            "file" => Result::Ok(TokenKeySource::File),
+            "keyring" => Result::Ok(TokenKeySource::Keyring),
+            "passphrase" => Result::Ok(TokenKeySource::Passphrase),
+            _ => Result::Err(format!("Unknown token key source {:?}", source)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenKeyConfig {
+    pub source: TokenKeySource,
+    // Key file for the file source, keep it out of backups of the DB
+    pub file: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            db_path: dirs::data_dir()
+                .map(|dir| dir.join(APP_DIR).join(DB_FILE))
+                .unwrap_or_else(|| PathBuf::from(DB_FILE)),
+            client_secret: Option::None,
+            log_filter: String::from(DEFAULT_LOG_FILTER),
+            mount: MountConfig::default(),
+            refresh: RefreshConfig::default(),
+            filesystem: PhotoFsConfig::default(),
+            token_key: TokenKeyConfig::default(),
+        }
+    }
+}
+
+impl Default for TokenKeyConfig {
+    fn default() -> TokenKeyConfig {
+        TokenKeyConfig {
+            source: TokenKeySource::File,
+            file: dirs::config_dir()
+                .map(|dir| dir.join(APP_DIR).join(TOKEN_KEY_FILE))
+                .unwrap_or_else(|| PathBuf::from(TOKEN_KEY_FILE)),
+        }
+    }
+}
+
+impl Default for MountConfig {
+    fn default() -> MountConfig {
+        MountConfig {
+            mountpoint: Option::None,
+            mode: MountMode::ReadOnly,
+            fsname: String::from(DEFAULT_FSNAME),
+            allow_other: false,
+            options: Vec::new(),
+            daemon: false,
+            pid_file: Option::None,
+        }
+    }
+}
+
+impl Default for RefreshConfig {
+    fn default() -> RefreshConfig {
+        RefreshConfig {
+            enabled: true,
+            content_categories: DEFAULT_CATEGORIES
+                .iter()
+                .map(|c| String::from(*c))
+                .collect(),
+            albums: ALBUMS_SCHEDULE,
+            shared_albums: SHARED_ALBUMS_SCHEDULE,
+            media_items: MEDIA_ITEMS_SCHEDULE,
+            categories: CATEGORIES_SCHEDULE,
+        }
+    }
+}
+
+impl RefreshConfig {
+    pub fn content_categories(&self) -> Vec<ContentCategory> {
+        self.content_categories
+            .iter()
+            .filter(|category| !category.trim().is_empty())
+            .filter_map(|category| match category.parse::<ContentCategory>() {
+                Ok(category) => Option::Some(category),
+                Err(error) => {
+                    warn!("Ignoring configured category: {}", error);
+                    Option::None
+                }
+            })
+            .collect()
+    }
+
+    // An interval of 0 would run an update back to back against the API
+    fn check_intervals(&self) -> Result<(), PhotoOxideError> {
+        let schedules = [
+            ("albums", self.albums),
+            ("shared_albums", self.shared_albums),
+            ("media_items", self.media_items),
+            ("categories", self.categories),
+        ];
+        for (name, schedule) in &schedules {
+            if schedule.interval_seconds == 0 {
+                return Result::Err(PhotoOxideError::ConfigError(format!(
+                    "refresh.{}.interval_seconds has to be above 0",
+                    name
+                )));
+            }
+        }
+        Result::Ok(())
+    }
+}
+
+impl Config {
+    // $XDG_CONFIG_HOME/photooxide/config.toml
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(APP_DIR).join(CONFIG_FILE))
+    }
+
+    // $XDG_CONFIG_HOME/photooxide/client_secret.json
+    pub fn default_client_secret_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(APP_DIR).join(CLIENT_SECRET_FILE))
+    }
+
+    // The configured client secret, else the one in the config directory if there is one
+    pub fn client_secret_path(&self) -> Option<PathBuf> {
+        match self.client_secret {
+            Option::Some(ref path) => Option::Some(path.clone()),
+            Option::None => Config::default_client_secret_path().filter(|path| path.exists()),
+        }
+    }
+
+    // Without a config file at the default path the defaults are used, a given path
+    // has to exist
+    pub fn load(path: Option<&Path>) -> Result<Config, PhotoOxideError> {
+        match path {
+            Option::Some(path) => Config::from_file(path),
+            Option::None => match Config::default_path() {
+                Option::Some(ref path) if path.exists() => Config::from_file(path),
+                _ => Result::Ok(Config::default()),
+            },
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, PhotoOxideError> {
+        Config::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Config, PhotoOxideError> {
+        let config: Config = toml::from_str(toml)?;
+        config.refresh.check_intervals()?;
+        Result::Ok(config)
+    }
+
+    pub fn apply_env(&mut self) {
+        let fs_config = &mut self.filesystem;
+        if let Ok(sizes) = env::var("PHOTOOXIDE_THUMBNAIL_SIZES") {
+            fs_config.thumbnail_sizes = sizes
+                .split(',')
+                .filter(|size| !size.trim().is_empty())
+                .filter_map(|size| match size.trim().parse::<u32>() {
+                    Ok(size) => Option::Some(size),
+                    Err(error) => {
+                        warn!("Ignoring configured thumbnail size {:?}: {}", size, error);
+                        Option::None
+                    }
+                })
+                .collect();
+        }
+        flag_from_env("PHOTOOXIDE_XMP_SIDECARS", &mut fs_config.xmp_sidecars);
+        flag_from_env("PHOTOOXIDE_JPEG_METADATA", &mut fs_config.jpeg_metadata);
+        flag_from_env("PHOTOOXIDE_ALBUM_SYMLINKS", &mut fs_config.album_symlinks);
+        if let Ok(size) = env::var("PHOTOOXIDE_CONTENT_CACHE_MB") {
+            match size.trim().parse::<usize>() {
+                Ok(size) => fs_config.content_cache_bytes = size * 1024 * 1024,
+                Err(error) => warn!(
+                    "Ignoring configured content cache size {:?}: {}",
+                    size, error
+                ),
+            }
+        }
+        if let Ok(depth) = env::var("PHOTOOXIDE_PREFETCH_DEPTH") {
+            match depth.trim().parse::<usize>() {
+                Ok(depth) => fs_config.prefetch_depth = depth,
+                Err(error) => warn!("Ignoring configured prefetch depth {:?}: {}", depth, error),
+            }
+        }
+        if let Ok(rate) = env::var("PHOTOOXIDE_PREFETCH_KB_PER_SECOND") {
+            match rate.trim().parse::<usize>() {
+                Ok(rate) => fs_config.prefetch_bytes_per_second = rate * 1024,
+                Err(error) => warn!("Ignoring configured prefetch rate {:?}: {}", rate, error),
+            }
+        }
+
+        if let Ok(uid) = env::var("PHOTOOXIDE_UID") {
+            match uid.trim().parse::<u32>() {
+                Ok(uid) => fs_config.uid = Option::Some(uid),
+                Err(error) => warn!("Ignoring configured uid {:?}: {}", uid, error),
+            }
+        }
+        if let Ok(gid) = env::var("PHOTOOXIDE_GID") {
+            match gid.trim().parse::<u32>() {
+                Ok(gid) => fs_config.gid = Option::Some(gid),
+                Err(error) => warn!("Ignoring configured gid {:?}: {}", gid, error),
+            }
+        }
+        ids_from_env("PHOTOOXIDE_ALLOWED_UIDS", &mut fs_config.allowed_uids);
+        ids_from_env("PHOTOOXIDE_ALLOWED_GIDS", &mut fs_config.allowed_gids);
+        mode_from_env("PHOTOOXIDE_FILE_MODE", &mut fs_config.file_mode);
+        mode_from_env("PHOTOOXIDE_DIR_MODE", &mut fs_config.dir_mode);
+        ttl_from_env("PHOTOOXIDE_FIXED_TTL", &mut fs_config.fixed_ttl_seconds);
+        ttl_from_env("PHOTOOXIDE_ALBUM_TTL", &mut fs_config.album_ttl_seconds);
+        ttl_from_env("PHOTOOXIDE_MEDIA_TTL", &mut fs_config.media_ttl_seconds);
+        ttl_from_env(
+            "PHOTOOXIDE_NEGATIVE_TTL",
+            &mut fs_config.negative_ttl_seconds,
+        );
+
+        if let Ok(path) = env::var("PHOTOOXIDE_CLIENT_SECRET") {
+            self.client_secret = Option::Some(PathBuf::from(path));
+        }
+        if env::var("PHOTOOXIDE_DISABLE_REFRESH").is_ok() {
+            self.refresh.enabled = false;
+        }
+        if let Ok(categories) = env::var("PHOTOOXIDE_CATEGORIES") {
+            self.refresh.content_categories = categories
+                .split(',')
+                .map(|c| String::from(c.trim()))
+                .collect();
+        }
+        flag_from_env("PHOTOOXIDE_ALLOW_OTHER", &mut self.mount.allow_other);
+        flag_from_env("PHOTOOXIDE_DAEMON", &mut self.mount.daemon);
+        if let Ok(path) = env::var("PHOTOOXIDE_PID_FILE") {
+            self.mount.pid_file = Option::Some(PathBuf::from(path));
+        }
+        if let Ok(mode) = env::var("PHOTOOXIDE_MOUNT_MODE") {
+            match mode.trim().parse::<MountMode>() {
+                Ok(mode) => self.mount.mode = mode,
+                Err(error) => warn!("Ignoring configured mount mode: {}", error),
+            }
+        }
+
+        if let Ok(source) = env::var("PHOTOOXIDE_TOKEN_KEY") {
+            match source.trim().parse::<TokenKeySource>() {
+                Ok(source) => self.token_key.source = source,
+                Err(error) => warn!("Ignoring configured token key: {}", error),
+            }
+        }
+        if let Ok(path) = env::var("PHOTOOXIDE_TOKEN_KEY_FILE") {
+            self.token_key.file = PathBuf::from(path);
+        }
+    }
+}
+
+// Set flags turn an option on, unset ones leave it as configured
+fn flag_from_env(name: &str, flag: &mut bool) {
+    if env::var(name).is_ok() {
+        *flag = true;
+    }
+}
+
+// TTLs are configured in seconds
+fn ttl_from_env(name: &str, ttl_seconds: &mut u64) {
+    if let Ok(ttl) = env::var(name) {
+        match ttl.trim().parse::<u64>() {
+            Ok(ttl) => *ttl_seconds = ttl,
+            Err(error) => warn!("Ignoring configured {} {:?}: {}", name, ttl, error),
+        }
+    }
+}
+
+// Comma separated uids or gids
+fn ids_from_env(name: &str, ids: &mut Vec<u32>) {
+    if let Ok(value) = env::var(name) {
+        *ids = value
+            .split(',')
+            .filter(|id| !id.trim().is_empty())
+            .filter_map(|id| match id.trim().parse::<u32>() {
+                Ok(id) => Option::Some(id),
+                Err(error) => {
+                    warn!("Ignoring {:?} in {}: {}", id, name, error);
+                    Option::None
+                }
+            })
+            .collect();
+    }
+}
+
+// Modes are configured in octal, like for chmod
+fn mode_from_env(name: &str, mode: &mut u16) {
+    if let Ok(value) = env::var(name) {
+        match u16::from_str_radix(value.trim(), 8) {
+            Ok(value) if value <= 0o7777 => *mode = value,
+            Ok(_) => warn!("Ignoring configured {} {:?}: not a mode", name, value),
+            Err(error) => warn!("Ignoring configured {} {:?}: {}", name, value, error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn config_defaults() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config, Config::default());
+        assert!(config.db_path.ends_with("photooxide/cache.sqlite"));
+        assert_eq!(config.mount.mountpoint, Option::None);
+        assert_eq!(config.mount.fsname, "photooxide");
+        assert_eq!(config.mount.mode, MountMode::ReadOnly);
+        assert!(!config.mount.allow_other);
+        assert!(!config.mount.daemon);
+        assert_eq!(config.mount.pid_file, Option::None);
+        assert!(config.refresh.enabled);
+        assert_eq!(config.refresh.albums, ALBUMS_SCHEDULE);
+        assert_eq!(config.refresh.content_categories().len(), 6);
+        assert_eq!(config.filesystem, PhotoFsConfig::default());
+        assert_eq!(config.token_key.source, TokenKeySource::File);
+        assert!(config.token_key.file.ends_with("photooxide/token.key"));
+    }
+
+    #[test]
+    fn config_from_toml() {
+        let config = Config::from_toml(
+            r#"
+            db_path = "/var/cache/photooxide.sqlite"
+            client_secret = "/etc/photooxide/client_secret.json"
+            log_filter = "photooxide=debug"
+
+            [mount]
+            mountpoint = "/mnt/photos"
+            mode = "read-write"
+            fsname = "photos"
+            allow_other = true
+            options = ["noatime"]
+            daemon = true
+            pid_file = "/run/photooxide.pid"
+
+            [refresh]
+            content_categories = ["receipts", "UNKNOWN"]
+            media_items = { delay_seconds = 0, interval_seconds = 3600 }
+
+            [filesystem]
+            thumbnail_sizes = [512]
+            dir_mode = 0o750
+            negative_ttl_seconds = 0
+
+            [token_key]
+            source = "passphrase"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.db_path,
+            PathBuf::from("/var/cache/photooxide.sqlite")
+        );
+        assert_eq!(
+            config.client_secret_path(),
+            Option::Some(PathBuf::from("/etc/photooxide/client_secret.json"))
+        );
+        assert_eq!(config.log_filter, "photooxide=debug");
+        assert_eq!(
+            config.mount,
+            MountConfig {
+                mountpoint: Option::Some(PathBuf::from("/mnt/photos")),
+                mode: MountMode::ReadWrite,
+                fsname: String::from("photos"),
+                allow_other: true,
+                options: vec![String::from("noatime")],
+                daemon: true,
+                pid_file: Option::Some(PathBuf::from("/run/photooxide.pid")),
+            }
+        );
+        assert_eq!(
+            config.refresh.content_categories(),
+            vec![ContentCategory::Receipts]
+        );
+        assert_eq!(config.refresh.media_items.delay(), time::Duration::zero());
+        assert_eq!(
+            config.refresh.media_items.interval(),
+            time::Duration::hours(1)
+        );
+        assert_eq!(config.refresh.albums, ALBUMS_SCHEDULE);
+        assert_eq!(config.filesystem.thumbnail_sizes, vec![512]);
+        assert_eq!(config.filesystem.dir_mode, 0o750);
+        assert_eq!(config.filesystem.file_mode, 0o644);
+        assert_eq!(config.filesystem.negative_ttl_seconds, 0);
+        assert_eq!(config.token_key.source, TokenKeySource::Passphrase);
+        assert_eq!(config.token_key.file, TokenKeyConfig::default().file);
+    }
+
+    #[test]
+    fn mount_mode_scopes() {
+        assert_eq!(MountMode::ReadOnly.scopes(), vec![PHOTOS_READONLY_SCOPE]);
+        assert!(!MountMode::ReadOnly.is_writable());
+        assert_eq!(
+            MountMode::ReadWrite.scopes(),
+            vec![
+                PHOTOS_READONLY_SCOPE,
+                PHOTOS_APPENDONLY_SCOPE,
+                PHOTOS_SHARING_SCOPE
+            ]
+        );
+        assert!(MountMode::ReadWrite.is_writable());
+
+        assert_eq!("read-only".parse(), Result::Ok(MountMode::ReadOnly));
+        assert_eq!("RW".parse(), Result::Ok(MountMode::ReadWrite));
+        assert!("append".parse::<MountMode>().is_err());
+
+        // The config file takes the same short forms
+        assert_eq!(
+            Config::from_toml("[mount]\nmode = \"rw\"")
+                .unwrap()
+                .mount
+                .mode,
+            MountMode::ReadWrite
+        );
+        assert_eq!(
+            Config::from_toml("[mount]\nmode = \"ro\"")
+                .unwrap()
+                .mount
+                .mode,
+            MountMode::ReadOnly
+        );
+    }
+
+    #[test]
+    fn token_key_source_from_str() {
+        assert_eq!("file".parse(), Result::Ok(TokenKeySource::File));
+        assert_eq!("Keyring".parse(), Result::Ok(TokenKeySource::Keyring));
+        assert_eq!("passphrase".parse(), Result::Ok(TokenKeySource::Passphrase));
+        assert!("plain".parse::<TokenKeySource>().is_err());
+    }
+
+    #[test]
+    fn config_errors() {
+        assert!(Config::from_toml("db_path = 1").is_err());
+        assert!(Config::from_toml("unknown = true").is_err());
+        assert!(Config::from_toml("[filesystem]\nthumbnails = [1]").is_err());
+        assert!(Config::from_toml("[refresh]\nalbums = { delay_seconds = 1 }").is_err());
+        assert!(Config::from_toml(
+            "[refresh]\ncategories = { delay_seconds = 1, interval_seconds = 0 }"
+        )
+        .is_err());
+        assert!(Config::from_toml("[token_key]\nsource = \"plain\"").is_err());
+        assert!(Config::from_toml("[mount]\nmode = \"append\"").is_err());
+        assert!(Config::load(Option::Some(Path::new("/nonexistent/config.toml"))).is_err());
+    }
+}
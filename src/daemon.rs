@@ -0,0 +1,107 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use daemonize::{Daemonize, Stdio};
+use libc;
+
+use crate::error::PhotoOxideError;
+
+// Returns in the detached child only, the parent exits. Threads do not survive the fork,
+// so this has to happen before any is started. The working directory is kept for relative
+// paths in the config, and so is stderr for the log.
+pub fn daemonize() -> Result<(), PhotoOxideError> {
+    Daemonize::new()
+        .working_directory(env::current_dir()?)
+        .stderr(Stdio::keep())
+        .start()
+        .map_err(|error| {
+            PhotoOxideError::IoError(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Failed to daemonize: {}", error),
+            ))
+        })
+}
+
+// Removed again when the mount ends. A pid file naming a running process is refused, so
+// two mounts can not share one.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create(path: &Path) -> Result<PidFile, PhotoOxideError> {
+        if let Option::Some(pid) = fs::read_to_string(path)
+            .ok()
+            .and_then(|pid| pid.trim().parse::<libc::pid_t>().ok())
+        {
+            if is_running(pid) {
+                return Result::Err(PhotoOxideError::ConfigError(format!(
+                    "Pid file {} belongs to the running process {}",
+                    path.display(),
+                    pid
+                )));
+            }
+        }
+        if let Option::Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, format!("{}\n", process::id()))?;
+        Result::Ok(PidFile {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(error) = fs::remove_file(&self.path) {
+            warn!(
+                "Failed to remove pid file {}: {}",
+                self.path.display(),
+                error
+            );
+        }
+    }
+}
+
+// Signal 0 only checks the process exists, EPERM means it does but belongs to another user
+fn is_running(pid: libc::pid_t) -> bool {
+    if pid <= 0 {
+        return false;
+    }
+    let exists = unsafe { libc::kill(pid, 0) } == 0;
+    exists || io::Error::last_os_error().raw_os_error() == Option::Some(libc::EPERM)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pid_file() {
+        let path = env::temp_dir()
+            .join(format!("photooxide-test-{}", process::id()))
+            .join("photooxide.pid");
+        let _ = fs::remove_file(&path);
+
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}\n", process::id())
+        );
+
+        // Taken while this process runs, removed when dropped
+        assert!(PidFile::create(&path).is_err());
+        drop(pid_file);
+        assert!(!path.exists());
+
+        // Stale pid files are replaced
+        fs::write(&path, format!("{}\n", libc::pid_t::MAX)).unwrap();
+        drop(PidFile::create(&path).unwrap());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,212 @@
+use std::iter;
+use std::result::Result;
+use std::sync::Mutex;
+
+use rusqlite;
+use rusqlite::types::ToSql;
+
+use crate::domain::{ContentCategory, GoogleId, MediaTypes, PhotoDbMediaItem};
+
+use crate::db::{row_to_media_item, DbError, SqliteDb, TableName};
+
+pub trait CategoryDb: Sized {
+    fn categories(&self) -> Result<Vec<ContentCategory>, DbError>;
+    fn media_items_in_category(
+        &self,
+        category: ContentCategory,
+    ) -> Result<Vec<PhotoDbMediaItem>, DbError>;
+    fn media_items_in_category_length(&self, category: ContentCategory) -> Result<usize, DbError>;
+
+    // Replaces the full membership of a category with the given media items
+    fn set_media_items_in_category(
+        &self,
+        category: ContentCategory,
+        media_item_ids: &[&GoogleId],
+    ) -> Result<(), DbError>;
+}
+
+pub fn ensure_schema_category(db: &Mutex<rusqlite::Connection>) -> Result<(), DbError> {
+    let db = db.lock()?;
+
+    db.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS '{}' (
+                category             TEXT NOT NULL,
+                media_item_google_id TEXT NOT NULL,
+                PRIMARY KEY(category, media_item_google_id),
+                FOREIGN KEY (media_item_google_id) REFERENCES '{}' (google_id) ON DELETE CASCADE
+            );",
+            TableName::MediaItemsInCategory,
+            TableName::AlbumsAndMediaItems
+        ),
+        iter::empty::<&dyn ToSql>(),
+    )?;
+
+    Result::Ok(())
+}
+
+impl CategoryDb for SqliteDb {
+    fn categories(&self) -> Result<Vec<ContentCategory>, DbError> {
+        let db = self.connection()?;
+        let mut statment = db.prepare(&format!(
+            "SELECT DISTINCT category FROM '{}' ORDER BY category;",
+            TableName::MediaItemsInCategory
+        ))?;
+        let category_results =
+            statment.query_map(iter::empty::<&dyn ToSql>(), |row| row.get::<_, String>(0))?;
+
+        let mut categories: Vec<ContentCategory> = Vec::new();
+        for category_result in category_results {
+            match category_result?.parse::<ContentCategory>() {
+                Ok(category) => categories.push(category),
+                Err(error) => warn!("Ignoring category in DB: {}", error),
+            }
+        }
+        Result::Ok(categories)
+    }
+
+    fn media_items_in_category(
+        &self,
+        category: ContentCategory,
+    ) -> Result<Vec<PhotoDbMediaItem>, DbError> {
+        let db = self.connection()?;
+        let mut statment = db.prepare(&format!(
+            "SELECT google_id, type, name, last_remote_check, inode
+            FROM '{}' INNER JOIN '{}' ON '{}'.google_id = '{}'.media_item_google_id
+            WHERE type = '{}' AND category = ? ORDER BY google_id;",
+            TableName::AlbumsAndMediaItems,
+            TableName::MediaItemsInCategory,
+            TableName::AlbumsAndMediaItems,
+            TableName::MediaItemsInCategory,
+            MediaTypes::MediaItem,
+        ))?;
+        let media_items_results =
+            statment.query_map(&[&format!("{}", category)], row_to_media_item)?;
+
+        let mut media_items: Vec<PhotoDbMediaItem> = Vec::new();
+        for media_item_result in media_items_results {
+            let media_item = media_item_result?;
+            media_items.push(media_item);
+        }
+        Result::Ok(media_items)
+    }
+
+    fn media_items_in_category_length(&self, category: ContentCategory) -> Result<usize, DbError> {
+        let count: i64 = self.connection()?.query_row(
+            &format!(
+                "SELECT COUNT(*)
+                FROM '{}' INNER JOIN '{}' ON '{}'.google_id = '{}'.media_item_google_id
+                WHERE type = '{}' AND category = ?;",
+                TableName::AlbumsAndMediaItems,
+                TableName::MediaItemsInCategory,
+                TableName::AlbumsAndMediaItems,
+                TableName::MediaItemsInCategory,
+                MediaTypes::MediaItem,
+            ),
+            &[&format!("{}", category)],
+            |row| row.get(0),
+        )?;
+        Result::Ok(count as usize)
+    }
+
+    fn set_media_items_in_category(
+        &self,
+        category: ContentCategory,
+        media_item_ids: &[&GoogleId],
+    ) -> Result<(), DbError> {
+        let category = format!("{}", category);
+        let mut db = self.connection()?;
+        let transaction = db.transaction()?;
+        transaction.execute(
+            &format!(
+                "DELETE FROM '{}' WHERE category = ?;",
+                TableName::MediaItemsInCategory
+            ),
+            &[&category],
+        )?;
+        for media_item_id in media_item_ids {
+            transaction.execute(
+                &format!(
+                    "INSERT OR REPLACE INTO '{}' (category, media_item_google_id) VALUES (?, ?);",
+                    TableName::MediaItemsInCategory
+                ),
+                &[&category as &dyn ToSql, media_item_id],
+            )?;
+        }
+        transaction.commit()?;
+        Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::db::PhotoDb;
+
+    #[test]
+    fn sqlitedb_media_items_in_category() -> Result<(), DbError> {
+        let db = SqliteDb::in_memory()?;
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+
+        // Test Empty DB
+        assert_eq!(db.categories()?.len(), 0);
+        assert_eq!(
+            db.media_items_in_category(ContentCategory::Receipts)?.len(),
+            0
+        );
+        assert_eq!(
+            db.media_items_in_category_length(ContentCategory::Receipts)?,
+            0
+        );
+
+        db.upsert_media_item("GoogleIdMediaItem1", "Receipt 1", &now)?;
+        db.upsert_media_item("GoogleIdMediaItem2", "Receipt 2", &now)?;
+        db.upsert_media_item("GoogleIdMediaItem3", "Landscape 1", &now)?;
+
+        // Test insert
+        db.set_media_items_in_category(
+            ContentCategory::Receipts,
+            &["GoogleIdMediaItem1", "GoogleIdMediaItem2"],
+        )?;
+        db.set_media_items_in_category(ContentCategory::Landscapes, &["GoogleIdMediaItem3"])?;
+
+        assert_eq!(
+            db.categories()?,
+            vec![ContentCategory::Landscapes, ContentCategory::Receipts]
+        );
+        let receipts = db.media_items_in_category(ContentCategory::Receipts)?;
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].google_id(), "GoogleIdMediaItem1");
+        assert_eq!(receipts[1].google_id(), "GoogleIdMediaItem2");
+        assert_eq!(
+            db.media_items_in_category_length(ContentCategory::Receipts)?,
+            2
+        );
+
+        // Test set replaces the previous membership
+        db.set_media_items_in_category(ContentCategory::Receipts, &["GoogleIdMediaItem2"])?;
+        let receipts = db.media_items_in_category(ContentCategory::Receipts)?;
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].google_id(), "GoogleIdMediaItem2");
+        assert_eq!(
+            db.media_items_in_category(ContentCategory::Landscapes)?
+                .len(),
+            1
+        );
+
+        // Test unknown media items are rejected
+        assert!(db
+            .set_media_items_in_category(ContentCategory::Receipts, &["GoogleIdMediaItem4"])
+            .is_err());
+        assert_eq!(
+            db.media_items_in_category(ContentCategory::Receipts)?.len(),
+            1
+        );
+
+        Result::Ok(())
+    }
+}
@@ -8,6 +8,7 @@ use rusqlite;
 pub enum DbError {
     SqlError(rusqlite::Error),
     LockingError,
+    Closed,
 }
 
 impl From<rusqlite::Error> for DbError {
@@ -27,6 +28,7 @@ impl std::error::Error for DbError {
         match self {
             DbError::SqlError(err) => Option::Some(err),
             DbError::LockingError => Option::None,
+            DbError::Closed => Option::None,
         }
     }
 }
@@ -36,6 +38,7 @@ impl fmt::Display for DbError {
         match self {
             DbError::SqlError(err) => write!(f, "DbError: SqlError({:?})", err),
             DbError::LockingError => write!(f, "DbError: LockingError"),
+            DbError::Closed => write!(f, "DbError: Closed"),
         }
     }
 }
@@ -64,6 +67,7 @@ mod test {
     #[test]
     fn db_error_source() {
         assert!(DbError::LockingError.source().is_none());
+        assert!(DbError::Closed.source().is_none());
         {
             let source = rusqlite::Error::SqliteSingleThreadedMode;
             assert!(DbError::SqlError(source).source().is_some());
@@ -83,5 +87,6 @@ mod test {
             format!("{}", DbError::LockingError),
             "DbError: LockingError"
         );
+        assert_eq!(format!("{}", DbError::Closed), "DbError: Closed");
     }
 }
@@ -38,22 +38,26 @@ pub fn ensure_schema_next_inode(db: &Mutex<rusqlite::Connection>) -> Result<(),
 }
 
 impl NextInodeDb for SqliteDb {
-    // TODO: Fix locking
     fn get_and_update_inode(&self) -> Result<Inode, DbError> {
-        let db = self.db.lock()?;
-        db.execute(
-            &format!("UPDATE '{}' SET inode = inode + 1;", TableName::NextInode),
-            iter::empty::<&dyn ToSql>(),
-        )?;
-        let result: Result<i64, rusqlite::Error> = db.query_row(
-            &format!("SELECT inode FROM '{}';", TableName::NextInode),
-            iter::empty::<&dyn ToSql>(),
-            |row| row.get(0),
-        );
-        match result {
-            Err(error) => Result::Err(DbError::from(error)),
-            Ok(inode) => Result::Ok(inode as Inode),
-        }
+        let db = self.connection()?;
+        next_inode(&db)
+    }
+}
+
+// For callers already holding the DB lock
+pub fn next_inode(db: &rusqlite::Connection) -> Result<Inode, DbError> {
+    db.execute(
+        &format!("UPDATE '{}' SET inode = inode + 1;", TableName::NextInode),
+        iter::empty::<&dyn ToSql>(),
+    )?;
+    let result: Result<i64, rusqlite::Error> = db.query_row(
+        &format!("SELECT inode FROM '{}';", TableName::NextInode),
+        iter::empty::<&dyn ToSql>(),
+        |row| row.get(0),
+    );
+    match result {
+        Err(error) => Result::Err(DbError::from(error)),
+        Ok(inode) => Result::Ok(inode as Inode),
     }
 }
 
@@ -0,0 +1,285 @@
+use std::iter;
+use std::result::Result;
+use std::sync::Mutex;
+
+use rusqlite;
+use rusqlite::types::ToSql;
+
+use chrono::{TimeZone, Utc};
+
+use crate::domain::{GoogleId, MediaItemMetadata, MediaTypes, PhotoDbAlbum};
+
+use crate::db::{row_to_album, DbError, SqliteDb, TableName};
+
+pub trait MetadataDb: Sized {
+    fn media_item_metadata(&self, id: &GoogleId) -> Result<Option<MediaItemMetadata>, DbError>;
+    fn upsert_media_item_metadata(
+        &self,
+        id: &GoogleId,
+        metadata: &MediaItemMetadata,
+    ) -> Result<(), DbError>;
+
+    // Albums and shared albums the media item is a member of
+    fn albums_for_media_item(&self, id: &GoogleId) -> Result<Vec<PhotoDbAlbum>, DbError>;
+
+    fn is_favourite(&self, id: &GoogleId) -> Result<bool, DbError>;
+    // Replaces the full set of favourites with the given media items
+    fn set_favourite_media_items(&self, media_item_ids: &[&GoogleId]) -> Result<(), DbError>;
+}
+
+pub fn ensure_schema_metadata(db: &Mutex<rusqlite::Connection>) -> Result<(), DbError> {
+    let db = db.lock()?;
+
+    db.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS '{}' (
+                media_item_google_id TEXT NOT NULL,
+                description          TEXT,
+                mime_type            TEXT,
+                product_url          TEXT,
+                creation_time        INTEGER,
+                width                INTEGER,
+                height               INTEGER,
+                camera_make          TEXT,
+                camera_model         TEXT,
+                PRIMARY KEY (media_item_google_id),
+                FOREIGN KEY (media_item_google_id) REFERENCES '{}' (google_id) ON DELETE CASCADE
+            );",
+            TableName::MediaItemMetadata,
+            TableName::AlbumsAndMediaItems
+        ),
+        iter::empty::<&dyn ToSql>(),
+    )?;
+
+    db.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS '{}' (
+                media_item_google_id TEXT NOT NULL,
+                PRIMARY KEY (media_item_google_id),
+                FOREIGN KEY (media_item_google_id) REFERENCES '{}' (google_id) ON DELETE CASCADE
+            );",
+            TableName::FavouriteMediaItems,
+            TableName::AlbumsAndMediaItems
+        ),
+        iter::empty::<&dyn ToSql>(),
+    )?;
+
+    Result::Ok(())
+}
+
+fn row_to_metadata(row: &rusqlite::Row<'_>) -> rusqlite::Result<MediaItemMetadata> {
+    let creation_time: Option<i64> = row.get(3)?;
+    let width: Option<i64> = row.get(4)?;
+    let height: Option<i64> = row.get(5)?;
+    Result::Ok(MediaItemMetadata {
+        description: row.get(0)?,
+        mime_type: row.get(1)?,
+        product_url: row.get(2)?,
+        creation_time: creation_time.map(|timestamp| Utc::timestamp(&Utc, timestamp, 0)),
+        width: width.map(|width| width as u64),
+        height: height.map(|height| height as u64),
+        camera_make: row.get(6)?,
+        camera_model: row.get(7)?,
+    })
+}
+
+impl MetadataDb for SqliteDb {
+    fn media_item_metadata(&self, id: &GoogleId) -> Result<Option<MediaItemMetadata>, DbError> {
+        let result: Result<MediaItemMetadata, rusqlite::Error> = self.connection()?.query_row(
+            &format!(
+                "SELECT description, mime_type, product_url, creation_time, width, height, camera_make, camera_model
+                FROM '{}' WHERE media_item_google_id = ?;",
+                TableName::MediaItemMetadata
+            ),
+            &[&id],
+            row_to_metadata,
+        );
+        match result {
+            Err(rusqlite::Error::QueryReturnedNoRows) => Result::Ok(Option::None),
+            Err(error) => Result::Err(DbError::from(error)),
+            Ok(metadata) => Result::Ok(Option::Some(metadata)),
+        }
+    }
+
+    fn upsert_media_item_metadata(
+        &self,
+        id: &GoogleId,
+        metadata: &MediaItemMetadata,
+    ) -> Result<(), DbError> {
+        let creation_time = metadata
+            .creation_time
+            .map(|creation_time| creation_time.timestamp());
+        let width = metadata.width.map(|width| width as i64);
+        let height = metadata.height.map(|height| height as i64);
+        self.connection()?.execute(
+            &format!(
+                "INSERT OR REPLACE INTO '{}' (media_item_google_id, description, mime_type, product_url, creation_time, width, height, camera_make, camera_model)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?);",
+                TableName::MediaItemMetadata
+            ),
+            &[
+                &id as &dyn ToSql,
+                &metadata.description,
+                &metadata.mime_type,
+                &metadata.product_url,
+                &creation_time,
+                &width,
+                &height,
+                &metadata.camera_make,
+                &metadata.camera_model,
+            ],
+        )?;
+        Result::Ok(())
+    }
+
+    fn albums_for_media_item(&self, id: &GoogleId) -> Result<Vec<PhotoDbAlbum>, DbError> {
+        let db = self.connection()?;
+        let mut statment = db.prepare(&format!(
+            "SELECT google_id, type, name, last_remote_check, inode
+            FROM '{}' INNER JOIN '{}' ON '{}'.google_id = '{}'.album_google_id
+            WHERE type IN ('{}', '{}') AND media_item_google_id = ? ORDER BY name;",
+            TableName::AlbumsAndMediaItems,
+            TableName::MediaItemsInAlbum,
+            TableName::AlbumsAndMediaItems,
+            TableName::MediaItemsInAlbum,
+            MediaTypes::Album,
+            MediaTypes::SharedAlbum,
+        ))?;
+        let albums_results = statment.query_map(&[&id], row_to_album)?;
+
+        let mut albums: Vec<PhotoDbAlbum> = Vec::new();
+        for album_result in albums_results {
+            let album = album_result?;
+            albums.push(album);
+        }
+        Result::Ok(albums)
+    }
+
+    fn is_favourite(&self, id: &GoogleId) -> Result<bool, DbError> {
+        let count: i64 = self.connection()?.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM '{}' WHERE media_item_google_id = ?;",
+                TableName::FavouriteMediaItems
+            ),
+            &[&id],
+            |row| row.get(0),
+        )?;
+        Result::Ok(count > 0)
+    }
+
+    fn set_favourite_media_items(&self, media_item_ids: &[&GoogleId]) -> Result<(), DbError> {
+        let mut db = self.connection()?;
+        let transaction = db.transaction()?;
+        transaction.execute(
+            &format!("DELETE FROM '{}';", TableName::FavouriteMediaItems),
+            iter::empty::<&dyn ToSql>(),
+        )?;
+        for media_item_id in media_item_ids {
+            transaction.execute(
+                &format!(
+                    "INSERT OR REPLACE INTO '{}' (media_item_google_id) VALUES (?);",
+                    TableName::FavouriteMediaItems
+                ),
+                &[media_item_id],
+            )?;
+        }
+        transaction.commit()?;
+        Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::db::{PhotoDb, SharedAlbumDb};
+    use crate::domain::SharedAlbumInfo;
+
+    #[test]
+    fn sqlitedb_upsert_media_item_metadata() -> Result<(), DbError> {
+        let db = SqliteDb::in_memory()?;
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+
+        assert!(db.media_item_metadata("GoogleId1")?.is_none());
+
+        db.upsert_media_item("GoogleId1", "Photo1.jpg", &now)?;
+
+        // Test insert with nothing known
+        db.upsert_media_item_metadata("GoogleId1", &MediaItemMetadata::default())?;
+        assert_eq!(
+            db.media_item_metadata("GoogleId1")?.unwrap(),
+            MediaItemMetadata::default()
+        );
+
+        // Test upsert
+        let metadata = MediaItemMetadata {
+            description: Option::Some(String::from("A description")),
+            mime_type: Option::Some(String::from("image/jpeg")),
+            product_url: Option::Some(String::from("https://photos.google.com/lr/photo/1")),
+            creation_time: Option::Some(Utc::timestamp(&Utc, 1_546_300_800, 0)),
+            width: Option::Some(4032),
+            height: Option::Some(3024),
+            camera_make: Option::Some(String::from("Google")),
+            camera_model: Option::Some(String::from("Pixel 3")),
+        };
+        db.upsert_media_item_metadata("GoogleId1", &metadata)?;
+        assert_eq!(db.media_item_metadata("GoogleId1")?.unwrap(), metadata);
+
+        // Test unknown media items are rejected
+        assert!(db
+            .upsert_media_item_metadata("GoogleId2", &metadata)
+            .is_err());
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn sqlitedb_albums_for_media_item() -> Result<(), DbError> {
+        let db = SqliteDb::in_memory()?;
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let share_info = SharedAlbumInfo::new(false, true, false);
+
+        db.upsert_media_item("GoogleIdMediaItem1", "Photo1.jpg", &now)?;
+        db.upsert_media_item("GoogleIdMediaItem2", "Photo2.jpg", &now)?;
+        db.upsert_album("GoogleIdAlbum1", "Album B", &now)?;
+        db.upsert_album("GoogleIdAlbum2", "Album C", &now)?;
+        db.upsert_shared_album("GoogleIdAlbum3", "Album A", &share_info, &now)?;
+
+        assert_eq!(db.albums_for_media_item("GoogleIdMediaItem1")?.len(), 0);
+
+        db.upsert_media_item_in_album("GoogleIdAlbum1", "GoogleIdMediaItem1")?;
+        db.upsert_media_item_in_album("GoogleIdAlbum3", "GoogleIdMediaItem1")?;
+        db.upsert_media_item_in_album("GoogleIdAlbum2", "GoogleIdMediaItem2")?;
+
+        let albums = db.albums_for_media_item("GoogleIdMediaItem1")?;
+        assert_eq!(albums.len(), 2);
+        assert_eq!(albums[0].name, "Album A");
+        assert_eq!(albums[1].name, "Album B");
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn sqlitedb_favourite_media_items() -> Result<(), DbError> {
+        let db = SqliteDb::in_memory()?;
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+
+        db.upsert_media_item("GoogleId1", "Photo1.jpg", &now)?;
+        db.upsert_media_item("GoogleId2", "Photo2.jpg", &now)?;
+        assert!(!db.is_favourite("GoogleId1")?);
+
+        db.set_favourite_media_items(&["GoogleId1", "GoogleId2"])?;
+        assert!(db.is_favourite("GoogleId1")?);
+        assert!(db.is_favourite("GoogleId2")?);
+
+        // Test set replaces the previous favourites
+        db.set_favourite_media_items(&["GoogleId2"])?;
+        assert!(!db.is_favourite("GoogleId1")?);
+        assert!(db.is_favourite("GoogleId2")?);
+
+        Result::Ok(())
+    }
+}
@@ -2,7 +2,8 @@ use std::convert::From;
 use std::iter;
 use std::option::Option;
 use std::result::Result;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
 
 use rusqlite;
 use rusqlite::types::ToSql;
@@ -10,20 +11,33 @@ use rusqlite::types::ToSql;
 use chrono::{TimeZone, Utc};
 
 use crate::domain::{
-    GoogleId, Inode, MediaTypes, PhotoDbAlbum, PhotoDbMediaItem, PhotoDbMediaItemAlbum, UtcDateTime,
+    ContentCategory, GoogleId, Inode, MediaTypes, PhotoDbAlbum, PhotoDbMediaItem,
+    PhotoDbMediaItemAlbum, UtcDateTime,
 };
 
 mod error;
 pub use self::error::DbError;
 
 mod inode_db;
-use self::inode_db::ensure_schema_next_inode;
 pub use self::inode_db::NextInodeDb;
+use self::inode_db::{ensure_schema_next_inode, next_inode};
 
 mod token_storage_db;
 use self::token_storage_db::ensure_schema_token_storage;
 pub use self::token_storage_db::TokenStorageDb;
 
+mod category_db;
+use self::category_db::ensure_schema_category;
+pub use self::category_db::CategoryDb;
+
+mod shared_album_db;
+use self::shared_album_db::ensure_schema_shared_album;
+pub use self::shared_album_db::SharedAlbumDb;
+
+mod metadata_db;
+use self::metadata_db::ensure_schema_metadata;
+pub use self::metadata_db::MetadataDb;
+
 mod table_name;
 use self::table_name::TableName;
 
@@ -31,6 +45,7 @@ use self::table_name::TableName;
 pub enum Filter<'a> {
     NoFilter,
     ByAlbum(&'a GoogleId),
+    ByCategory(ContentCategory),
 }
 
 pub trait PhotoDbRo: Sized {
@@ -39,6 +54,14 @@ pub trait PhotoDbRo: Sized {
     fn albums(&self) -> Result<Vec<PhotoDbAlbum>, DbError>;
     fn media_items_in_album(&self, inode: Inode) -> Result<Vec<PhotoDbMediaItem>, DbError>;
     fn media_items_in_album_length(&self, inode: Inode) -> Result<usize, DbError>;
+    // Up to limit media items with an inode above after_inode, in inode order. Items
+    // sharing a name with an item of a lower inode are left out, like in lookups.
+    fn media_items_page(
+        &self,
+        filter: Filter,
+        after_inode: Inode,
+        limit: usize,
+    ) -> Result<Vec<PhotoDbMediaItem>, DbError>;
 
     // Single items
     fn media_item_by_name(
@@ -47,6 +70,7 @@ pub trait PhotoDbRo: Sized {
         filter: Filter,
     ) -> Result<Option<PhotoDbMediaItem>, DbError>;
     fn media_item_by_inode(&self, inode: Inode) -> Result<Option<PhotoDbMediaItem>, DbError>;
+    fn media_item_by_id(&self, id: &GoogleId) -> Result<Option<PhotoDbMediaItem>, DbError>;
     fn album_by_name(&self, name: &str) -> Result<Option<PhotoDbAlbum>, DbError>;
     fn album_by_inode(&self, inode: Inode) -> Result<Option<PhotoDbAlbum>, DbError>;
     fn item_by_inode(&self, inode: Inode) -> Result<Option<PhotoDbMediaItemAlbum>, DbError>;
@@ -145,6 +169,7 @@ fn ensure_schema(db: &Mutex<rusqlite::Connection>) -> Result<(), DbError> {
 
 pub struct SqliteDb {
     db: Mutex<rusqlite::Connection>,
+    closed: AtomicBool,
 }
 
 unsafe impl Send for SqliteDb {}
@@ -185,7 +210,7 @@ fn row_to_option_datetime(row: &rusqlite::Row<'_>) -> rusqlite::Result<Option<Ut
 
 impl PhotoDbRo for SqliteDb {
     fn media_items(&self) -> Result<Vec<PhotoDbMediaItem>, DbError> {
-        let db = self.db.lock()?;
+        let db = self.connection()?;
         let mut statment = db.prepare(&format!(
             "SELECT google_id, type, name, last_remote_check, inode FROM '{}' WHERE type = '{}' ORDER BY google_id;",
             TableName::AlbumsAndMediaItems,
@@ -203,7 +228,7 @@ impl PhotoDbRo for SqliteDb {
     }
 
     fn albums(&self) -> Result<Vec<PhotoDbAlbum>, DbError> {
-        let db = self.db.lock()?;
+        let db = self.connection()?;
         let mut statment = db.prepare(&format!(
             "SELECT google_id, type, name, last_remote_check, inode FROM '{}' WHERE type = '{}' ORDER BY google_id;",
             TableName::AlbumsAndMediaItems,
@@ -220,7 +245,7 @@ impl PhotoDbRo for SqliteDb {
     }
 
     fn media_items_in_album(&self, inode: Inode) -> Result<Vec<PhotoDbMediaItem>, DbError> {
-        let db = self.db.lock()?;
+        let db = self.connection()?;
         let mut statment = db.prepare(&format!(
             "SELECT google_id, type, name, last_remote_check, inode
             FROM '{}' INNER JOIN '{}' ON '{}'.google_id = '{}'.media_item_google_id
@@ -247,6 +272,82 @@ impl PhotoDbRo for SqliteDb {
             .map(|media_items| media_items.len()) // TODO: Custom SQL
     }
 
+    fn media_items_page(
+        &self,
+        filter: Filter,
+        after_inode: Inode,
+        limit: usize,
+    ) -> Result<Vec<PhotoDbMediaItem>, DbError> {
+        let after_inode = after_inode as i64;
+        let limit = limit as i64;
+        let db = self.connection()?;
+        let mut statment;
+        let media_items_results = match filter {
+            Filter::ByAlbum(album_id) => {
+                statment = db.prepare(&format!(
+                    "SELECT item.google_id, item.type, item.name, item.last_remote_check, item.inode
+                    FROM '{}' AS item JOIN '{}' AS album ON item.google_id = album.media_item_google_id
+                    WHERE item.type = '{}' AND album.album_google_id = ? AND item.inode > ?
+                    AND NOT EXISTS (
+                        SELECT 1 FROM '{}' AS other JOIN '{}' AS other_album ON other.google_id = other_album.media_item_google_id
+                        WHERE other_album.album_google_id = album.album_google_id AND other.type = item.type AND other.name = item.name AND other.inode < item.inode
+                    ) ORDER BY item.inode LIMIT ?;",
+                    TableName::AlbumsAndMediaItems,
+                    TableName::MediaItemsInAlbum,
+                    MediaTypes::MediaItem,
+                    TableName::AlbumsAndMediaItems,
+                    TableName::MediaItemsInAlbum,
+                ))?;
+                statment.query_map(
+                    &[&album_id as &dyn ToSql, &after_inode, &limit],
+                    row_to_media_item,
+                )?
+            }
+            Filter::ByCategory(category) => {
+                statment = db.prepare(&format!(
+                    "SELECT item.google_id, item.type, item.name, item.last_remote_check, item.inode
+                    FROM '{}' AS item JOIN '{}' AS category ON item.google_id = category.media_item_google_id
+                    WHERE item.type = '{}' AND category.category = ? AND item.inode > ?
+                    AND NOT EXISTS (
+                        SELECT 1 FROM '{}' AS other JOIN '{}' AS other_category ON other.google_id = other_category.media_item_google_id
+                        WHERE other_category.category = category.category AND other.type = item.type AND other.name = item.name AND other.inode < item.inode
+                    ) ORDER BY item.inode LIMIT ?;",
+                    TableName::AlbumsAndMediaItems,
+                    TableName::MediaItemsInCategory,
+                    MediaTypes::MediaItem,
+                    TableName::AlbumsAndMediaItems,
+                    TableName::MediaItemsInCategory,
+                ))?;
+                statment.query_map(
+                    &[&format!("{}", category) as &dyn ToSql, &after_inode, &limit],
+                    row_to_media_item,
+                )?
+            }
+            Filter::NoFilter => {
+                statment = db.prepare(&format!(
+                    "SELECT item.google_id, item.type, item.name, item.last_remote_check, item.inode
+                    FROM '{}' AS item
+                    WHERE item.type = '{}' AND item.inode > ?
+                    AND NOT EXISTS (
+                        SELECT 1 FROM '{}' AS other
+                        WHERE other.type = item.type AND other.name = item.name AND other.inode < item.inode
+                    ) ORDER BY item.inode LIMIT ?;",
+                    TableName::AlbumsAndMediaItems,
+                    MediaTypes::MediaItem,
+                    TableName::AlbumsAndMediaItems,
+                ))?;
+                statment.query_map(&[&after_inode as &dyn ToSql, &limit], row_to_media_item)?
+            }
+        };
+
+        let mut media_items: Vec<PhotoDbMediaItem> = Vec::new();
+        for media_item_result in media_items_results {
+            let media_item = media_item_result?;
+            media_items.push(media_item);
+        }
+        Result::Ok(media_items)
+    }
+
     fn media_item_by_inode(&self, inode: Inode) -> Result<Option<PhotoDbMediaItem>, DbError> {
         let result = self.item_by_inode(inode)?;
         match result {
@@ -263,18 +364,24 @@ impl PhotoDbRo for SqliteDb {
         name: &str,
         filter: Filter,
     ) -> Result<Option<PhotoDbMediaItem>, DbError> {
-        let db = self.db.lock()?;
+        let db = self.connection()?;
 
         let result: Result<PhotoDbMediaItem, rusqlite::Error> = match filter {
             Filter::ByAlbum(album_id) => {
                 db.query_row(
-            &format!("SELECT item.google_id, item.type, item.name, item.last_remote_check, item.inode FROM '{}' AS item JOIN '{}' AS album ON item.google_id = album.media_item_google_id WHERE item.type = '{}' AND item.name = ? AND album.album_google_id = ?;", TableName::AlbumsAndMediaItems, TableName::MediaItemsInAlbum, MediaTypes::MediaItem),
+            &format!("SELECT item.google_id, item.type, item.name, item.last_remote_check, item.inode FROM '{}' AS item JOIN '{}' AS album ON item.google_id = album.media_item_google_id WHERE item.type = '{}' AND item.name = ? AND album.album_google_id = ? ORDER BY item.inode LIMIT 1;", TableName::AlbumsAndMediaItems, TableName::MediaItemsInAlbum, MediaTypes::MediaItem),
             &[&name as &dyn ToSql, &album_id], row_to_media_item,
+        )
+            }
+            Filter::ByCategory(category) => {
+                db.query_row(
+            &format!("SELECT item.google_id, item.type, item.name, item.last_remote_check, item.inode FROM '{}' AS item JOIN '{}' AS category ON item.google_id = category.media_item_google_id WHERE item.type = '{}' AND item.name = ? AND category.category = ? ORDER BY item.inode LIMIT 1;", TableName::AlbumsAndMediaItems, TableName::MediaItemsInCategory, MediaTypes::MediaItem),
+            &[&name as &dyn ToSql, &format!("{}", category)], row_to_media_item,
         )
             }
             Filter::NoFilter => {
                 db.query_row(
-            &format!("SELECT google_id, type, name, last_remote_check, inode FROM '{}' WHERE type = '{}' AND name = ?;", TableName::AlbumsAndMediaItems, MediaTypes::MediaItem),
+            &format!("SELECT google_id, type, name, last_remote_check, inode FROM '{}' WHERE type = '{}' AND name = ? ORDER BY inode LIMIT 1;", TableName::AlbumsAndMediaItems, MediaTypes::MediaItem),
             &[&name], row_to_media_item,
         )
             }
@@ -286,8 +393,21 @@ impl PhotoDbRo for SqliteDb {
         }
     }
 
+    fn media_item_by_id(&self, id: &GoogleId) -> Result<Option<PhotoDbMediaItem>, DbError> {
+        let db = self.connection()?;
+        let result: Result<PhotoDbMediaItem, rusqlite::Error> = db.query_row(
+            &format!("SELECT google_id, type, name, last_remote_check, inode FROM '{}' WHERE type = '{}' AND google_id = ?;", TableName::AlbumsAndMediaItems, MediaTypes::MediaItem),
+            &[id], row_to_media_item,
+        );
+        match result {
+            Err(rusqlite::Error::QueryReturnedNoRows) => Result::Ok(Option::None),
+            Err(error) => Result::Err(DbError::from(error)),
+            Ok(media_item) => Result::Ok(Option::Some(media_item)),
+        }
+    }
+
     fn album_by_name(&self, name: &str) -> Result<Option<PhotoDbAlbum>, DbError> {
-        let db = self.db.lock()?;
+        let db = self.connection()?;
         let result: Result<PhotoDbAlbum, rusqlite::Error> = db.query_row(
             &format!("SELECT google_id, type, name, last_remote_check, inode FROM '{}' WHERE type = '{}' AND name = ?;", TableName::AlbumsAndMediaItems, MediaTypes::Album),
             &[&name], row_to_album,
@@ -311,7 +431,7 @@ impl PhotoDbRo for SqliteDb {
     }
 
     fn item_by_inode(&self, inode: Inode) -> Result<Option<PhotoDbMediaItemAlbum>, DbError> {
-        let db = self.db.lock()?;
+        let db = self.connection()?;
         let result: Result<PhotoDbMediaItemAlbum, rusqlite::Error> = db.query_row(
             &format!(
                 "SELECT google_id, type, name, last_remote_check, inode FROM '{}' WHERE inode = ?;",
@@ -336,7 +456,7 @@ impl PhotoDbRo for SqliteDb {
     }
 
     fn exists(&self, id: &GoogleId) -> Result<bool, DbError> {
-        let db = self.db.lock()?;
+        let db = self.connection()?;
         let mut statment = db.prepare(&format!(
             "SELECT 1 FROM '{}' WHERE google_id = ?;",
             TableName::AlbumsAndMediaItems
@@ -352,14 +472,7 @@ impl PhotoDb for SqliteDb {
         filename: &str,
         last_modified_time: &UtcDateTime,
     ) -> Result<Inode, DbError> {
-        let inode = self.get_and_update_inode()?;
-        self.upsert_x(
-            id,
-            MediaTypes::MediaItem,
-            filename,
-            inode,
-            &last_modified_time,
-        )
+        self.upsert_x(id, MediaTypes::MediaItem, filename, &last_modified_time)
     }
 
     fn upsert_album(
@@ -368,8 +481,7 @@ impl PhotoDb for SqliteDb {
         title: &str,
         last_modified_time: &UtcDateTime,
     ) -> Result<Inode, DbError> {
-        let inode = self.get_and_update_inode()?;
-        self.upsert_x(id, MediaTypes::Album, title, inode, &last_modified_time)
+        self.upsert_x(id, MediaTypes::Album, title, &last_modified_time)
     }
 
     fn upsert_media_item_in_album(
@@ -377,7 +489,7 @@ impl PhotoDb for SqliteDb {
         album_id: &GoogleId,
         media_item_id: &GoogleId,
     ) -> Result<(), DbError> {
-        self.db.lock()?.execute(
+        self.connection()?.execute(
             &format!("INSERT OR REPLACE INTO '{}' (album_google_id, media_item_google_id) VALUES (?, ?);", TableName::MediaItemsInAlbum),
             &[&album_id, &media_item_id],
         )?;
@@ -391,6 +503,44 @@ impl SqliteDb {
         SqliteDb::try_new(Mutex::new(connection))
     }
 
+    // Drops everything fetched from Google Photos, keeping the tokens. The inode counter
+    // is kept too, so a running mount never sees an inode handed out twice.
+    pub fn purge_cache(&self) -> Result<(), DbError> {
+        self.connection()?.execute_batch(&format!(
+            "BEGIN;
+            DELETE FROM '{}';
+            DELETE FROM '{}';
+            DELETE FROM '{}';
+            DELETE FROM '{}';
+            DELETE FROM '{}';
+            DELETE FROM '{}';
+            COMMIT;",
+            TableName::MediaItemsInAlbum,
+            TableName::MediaItemsInCategory,
+            TableName::SharedAlbumInfo,
+            TableName::MediaItemMetadata,
+            TableName::FavouriteMediaItems,
+            TableName::AlbumsAndMediaItems
+        ))?;
+        Result::Ok(())
+    }
+
+    // Waits for the statement or transaction in flight, every later use fails with
+    // DbError::Closed. Transactions never span two locks, so none is left half done.
+    pub fn close(&self) -> Result<(), DbError> {
+        let _db = self.db.lock()?;
+        self.closed.store(true, Ordering::SeqCst);
+        Result::Ok(())
+    }
+
+    fn connection(&self) -> Result<MutexGuard<'_, rusqlite::Connection>, DbError> {
+        let db = self.db.lock()?;
+        if self.closed.load(Ordering::SeqCst) {
+            return Result::Err(DbError::Closed);
+        }
+        Result::Ok(db)
+    }
+
     #[cfg(test)]
     pub fn in_memory() -> Result<SqliteDb, DbError> {
         let connection = rusqlite::Connection::open_in_memory()?;
@@ -401,12 +551,17 @@ impl SqliteDb {
         ensure_schema(&db)?;
         ensure_schema_next_inode(&db)?;
         ensure_schema_token_storage(&db)?;
-        Result::Ok(SqliteDb { db })
+        ensure_schema_category(&db)?;
+        ensure_schema_shared_album(&db)?;
+        ensure_schema_metadata(&db)?;
+        Result::Ok(SqliteDb {
+            db,
+            closed: AtomicBool::new(false),
+        })
     }
 
     fn last_updated_x(&self, media_type: MediaTypes) -> Result<Option<UtcDateTime>, DbError> {
-        self.db
-            .lock()?
+        self.connection()?
             .query_row(
                 &format!(
                 "SELECT MIN(last_remote_check) AS min_last_remote_check FROM '{}' WHERE type = ?;",
@@ -423,18 +578,51 @@ impl SqliteDb {
         id: &GoogleId,
         media_type: MediaTypes,
         name: &str,
-        inode: Inode,
         last_modified_time: &UtcDateTime,
     ) -> Result<Inode, DbError> {
-        let media_type = format!("{}", media_type);
-        let inode_signed = inode as i64;
-        let last_modified_time = last_modified_time.timestamp();
-        self.db.lock()?.execute(
-            &format!("INSERT OR REPLACE INTO '{}' (google_id, type, name, inode, last_remote_check) VALUES (?, ?, ?, ?, ?);", TableName::AlbumsAndMediaItems),
-            &[&id as &dyn ToSql, &media_type, &name, &inode_signed, &last_modified_time],
+        let db = self.connection()?;
+        upsert_item(&db, id, media_type, name, last_modified_time)
+    }
+}
+
+// Known items keep their inode. Replacing the row instead would hand out a new inode
+// and, through the foreign keys, drop the album and category memberships. Takes the
+// connection of a caller already holding the DB lock, e.g. for a transaction.
+fn upsert_item(
+    db: &rusqlite::Connection,
+    id: &GoogleId,
+    media_type: MediaTypes,
+    name: &str,
+    last_modified_time: &UtcDateTime,
+) -> Result<Inode, DbError> {
+    let media_type = format!("{}", media_type);
+    let last_modified_time = last_modified_time.timestamp();
+    let updated = db.execute(
+        &format!(
+            "UPDATE '{}' SET type = ?, name = ?, last_remote_check = ? WHERE google_id = ?;",
+            TableName::AlbumsAndMediaItems
+        ),
+        &[&media_type as &dyn ToSql, &name, &last_modified_time, &id],
+    )?;
+    if updated > 0 {
+        let inode: i64 = db.query_row(
+            &format!(
+                "SELECT inode FROM '{}' WHERE google_id = ?;",
+                TableName::AlbumsAndMediaItems
+            ),
+            &[&id],
+            |row| row.get(0),
         )?;
-        Result::Ok(inode)
+        return Result::Ok(inode as Inode);
     }
+
+    let inode = next_inode(db)?;
+    let inode_signed = inode as i64;
+    db.execute(
+        &format!("INSERT INTO '{}' (google_id, type, name, inode, last_remote_check) VALUES (?, ?, ?, ?, ?);", TableName::AlbumsAndMediaItems),
+        &[&id as &dyn ToSql, &media_type, &name, &inode_signed, &last_modified_time],
+    )?;
+    Result::Ok(inode)
 }
 
 #[cfg(test)]
@@ -578,7 +766,7 @@ mod test {
             &String::from("Title 1 new title"),
             &now,
         )?;
-        assert_eq!(inode, 103); // TODO: should be 101
+        assert_eq!(inode, 101);
 
         let media_items = db.media_items()?;
         assert_eq!(media_items.len(), 2);
@@ -618,7 +806,7 @@ mod test {
 
         // Test upsert
         let inode = db.upsert_album(&"GoogleIdAlbum1", &"Album 1 new title", &now)?;
-        assert_eq!(inode, 103); // TODO: should be 101
+        assert_eq!(inode, 101);
 
         let albums = db.albums()?;
         assert_eq!(albums.len(), 2);
@@ -728,6 +916,91 @@ mod test {
         Result::Ok(())
     }
 
+    #[test]
+    fn sqlitedb_upsert_keeps_album_membership() -> Result<(), DbError> {
+        let db = SqliteDb::in_memory()?;
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+
+        let album_inode = db.upsert_album("GoogleIdAlbum1", "Album 1", &now)?;
+        let inode = db.upsert_media_item("GoogleIdMediaItem1", "Media Item 1", &now)?;
+        db.upsert_media_item_in_album("GoogleIdAlbum1", "GoogleIdMediaItem1")?;
+
+        assert_eq!(
+            db.upsert_album("GoogleIdAlbum1", "Album 1", &now)?,
+            album_inode
+        );
+        assert_eq!(
+            db.upsert_media_item("GoogleIdMediaItem1", "Media Item 1", &now)?,
+            inode
+        );
+        assert_eq!(db.media_items_in_album(album_inode)?.len(), 1);
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn sqlitedb_media_items_page() -> Result<(), DbError> {
+        let db = SqliteDb::in_memory()?;
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+
+        // Assert DB is empty
+        assert_eq!(db.media_items_page(Filter::NoFilter, 0, 10)?.len(), 0);
+
+        db.upsert_album("GoogleIdAlbum1", "Album 1", &now)?;
+        let inode1 = db.upsert_media_item("GoogleId1", "Title 1", &now)?;
+        let inode2 = db.upsert_media_item("GoogleId2", "Title 2", &now)?;
+        let inode3 = db.upsert_media_item("GoogleId3", "Title 3", &now)?;
+        // Same name as GoogleId1, so hidden by it
+        let inode4 = db.upsert_media_item("GoogleId4", "Title 1", &now)?;
+        db.upsert_media_item_in_album("GoogleIdAlbum1", "GoogleId2")?;
+        db.upsert_media_item_in_album("GoogleIdAlbum1", "GoogleId3")?;
+        db.set_media_items_in_category(ContentCategory::Receipts, &["GoogleId3"])?;
+
+        let inodes = |media_items: Vec<PhotoDbMediaItem>| -> Vec<Inode> {
+            media_items
+                .iter()
+                .map(|media_item| media_item.inode)
+                .collect()
+        };
+
+        assert_eq!(
+            inodes(db.media_items_page(Filter::NoFilter, 0, 10)?),
+            vec![inode1, inode2, inode3]
+        );
+        assert_eq!(
+            inodes(db.media_items_page(Filter::NoFilter, 0, 2)?),
+            vec![inode1, inode2]
+        );
+        assert_eq!(
+            inodes(db.media_items_page(Filter::NoFilter, inode2, 2)?),
+            vec![inode3]
+        );
+
+        // Updates keep the inode, so do not move items between pages
+        db.upsert_media_item("GoogleId1", "Title 1 new title", &now)?;
+        assert_eq!(
+            inodes(db.media_items_page(Filter::NoFilter, inode1, 10)?),
+            vec![inode2, inode3, inode4]
+        );
+
+        assert_eq!(
+            inodes(db.media_items_page(Filter::ByAlbum("GoogleIdAlbum1"), 0, 10)?),
+            vec![inode2, inode3]
+        );
+        assert_eq!(
+            inodes(db.media_items_page(Filter::ByAlbum("GoogleIdAlbum1"), inode2, 10)?),
+            vec![inode3]
+        );
+        assert_eq!(
+            inodes(db.media_items_page(Filter::ByCategory(ContentCategory::Receipts), 0, 10)?),
+            vec![inode3]
+        );
+
+        Result::Ok(())
+    }
+
     #[test]
     fn sqlitedb_albums() -> Result<(), DbError> {
         let db = SqliteDb::in_memory()?;
@@ -767,6 +1040,7 @@ mod test {
         // Assert when DB is empty
         assert!(db.media_item_by_inode(100)?.is_none());
         assert!(db.media_item_by_name("foo", Filter::NoFilter)?.is_none());
+        assert!(db.media_item_by_id(&String::from("GoogleId1"))?.is_none());
 
         // insert some data
         let inode1 =
@@ -779,6 +1053,10 @@ mod test {
         let by_name = db.media_item_by_name("Title 1", Filter::NoFilter)?.unwrap();
         assert_eq!(by_inode.google_id(), "GoogleId1");
         assert_eq!(by_inode, by_name);
+        assert_eq!(
+            db.media_item_by_id(&String::from("GoogleId1"))?.unwrap(),
+            by_inode
+        );
 
         // Lookup find the correct node
         assert_eq!(
@@ -803,6 +1081,21 @@ mod test {
             "GoogleId2"
         );
 
+        // Lookup by name within a category
+        assert!(db
+            .media_item_by_name("Title 1", Filter::ByCategory(ContentCategory::Receipts))?
+            .is_none());
+        db.set_media_items_in_category(ContentCategory::Receipts, &["GoogleId1"])?;
+        assert_eq!(
+            db.media_item_by_name("Title 1", Filter::ByCategory(ContentCategory::Receipts))?
+                .unwrap()
+                .google_id(),
+            "GoogleId1"
+        );
+        assert!(db
+            .media_item_by_name("Title 2", Filter::ByCategory(ContentCategory::Receipts))?
+            .is_none());
+
         Result::Ok(())
     }
 
@@ -862,4 +1155,51 @@ mod test {
 
         Result::Ok(())
     }
+
+    #[test]
+    fn sqlitedb_purge_cache() -> Result<(), DbError> {
+        let db = SqliteDb::in_memory()?;
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+
+        db.upsert_album("GoogleIdAlbum1", "Album 1", &now)?;
+        let inode = db.upsert_media_item("GoogleIdMediaItem1", "Photo1.jpg", &now)?;
+        db.upsert_media_item_in_album("GoogleIdAlbum1", "GoogleIdMediaItem1")?;
+        db.set_media_items_in_category(ContentCategory::Pets, &["GoogleIdMediaItem1"])?;
+        db.set_favourite_media_items(&["GoogleIdMediaItem1"])?;
+        db.set_oath_token(0, Option::Some(String::from("token")))?;
+
+        db.purge_cache()?;
+        assert!(db.albums()?.is_empty());
+        assert!(db.media_items()?.is_empty());
+        assert!(db.categories()?.is_empty());
+        assert!(!db.is_favourite("GoogleIdMediaItem1")?);
+        assert!(db.last_updated_media()?.is_none());
+
+        // Tokens and the inode counter are kept
+        assert!(db.has_oath_token()?);
+        assert!(db.upsert_media_item("GoogleIdMediaItem1", "Photo1.jpg", &now)? > inode);
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn sqlitedb_close() -> Result<(), DbError> {
+        let db = SqliteDb::in_memory()?;
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        db.upsert_album("GoogleIdAlbum1", "Album 1", &now)?;
+
+        db.close()?;
+        match db.albums() {
+            Result::Err(DbError::Closed) => {}
+            other => panic!("Expected DbError::Closed, got {:?}", other.map(|_| ())),
+        }
+        match db.upsert_album("GoogleIdAlbum2", "Album 2", &now) {
+            Result::Err(DbError::Closed) => {}
+            other => panic!("Expected DbError::Closed, got {:?}", other.map(|_| ())),
+        }
+
+        Result::Ok(())
+    }
 }
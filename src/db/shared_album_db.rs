@@ -0,0 +1,226 @@
+use std::iter;
+use std::result::Result;
+use std::sync::Mutex;
+
+use rusqlite;
+use rusqlite::types::ToSql;
+
+use crate::domain::{GoogleId, Inode, MediaTypes, PhotoDbAlbum, SharedAlbumInfo, UtcDateTime};
+
+use crate::db::{row_to_album, upsert_item, DbError, PhotoDbRo, SqliteDb, TableName};
+
+pub trait SharedAlbumDb: Sized {
+    fn shared_albums(&self) -> Result<Vec<PhotoDbAlbum>, DbError>;
+    fn shared_album_by_name(&self, name: &str) -> Result<Option<PhotoDbAlbum>, DbError>;
+    fn shared_album_by_inode(&self, inode: Inode) -> Result<Option<PhotoDbAlbum>, DbError>;
+    fn shared_album_info(&self, id: &GoogleId) -> Result<Option<SharedAlbumInfo>, DbError>;
+
+    fn upsert_shared_album(
+        &self,
+        id: &GoogleId,
+        title: &str,
+        share_info: &SharedAlbumInfo,
+        last_modified_time: &UtcDateTime,
+    ) -> Result<Inode, DbError>;
+}
+
+pub fn ensure_schema_shared_album(db: &Mutex<rusqlite::Connection>) -> Result<(), DbError> {
+    let db = db.lock()?;
+
+    db.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS '{}' (
+                album_google_id   TEXT NOT NULL,
+                is_owned          INTEGER NOT NULL,
+                is_joined         INTEGER NOT NULL,
+                is_collaborative  INTEGER NOT NULL,
+                PRIMARY KEY (album_google_id),
+                FOREIGN KEY (album_google_id) REFERENCES '{}' (google_id) ON DELETE CASCADE
+            );",
+            TableName::SharedAlbumInfo,
+            TableName::AlbumsAndMediaItems
+        ),
+        iter::empty::<&dyn ToSql>(),
+    )?;
+
+    Result::Ok(())
+}
+
+fn row_to_shared_album_info(row: &rusqlite::Row<'_>) -> rusqlite::Result<SharedAlbumInfo> {
+    let is_owned: bool = row.get(0)?;
+    let is_joined: bool = row.get(1)?;
+    let is_collaborative: bool = row.get(2)?;
+    Result::Ok(SharedAlbumInfo::new(is_owned, is_joined, is_collaborative))
+}
+
+impl SharedAlbumDb for SqliteDb {
+    fn shared_albums(&self) -> Result<Vec<PhotoDbAlbum>, DbError> {
+        let db = self.connection()?;
+        let mut statment = db.prepare(&format!(
+            "SELECT google_id, type, name, last_remote_check, inode FROM '{}' WHERE type = '{}' ORDER BY google_id;",
+            TableName::AlbumsAndMediaItems,
+            MediaTypes::SharedAlbum
+        ))?;
+        let albums_results = statment.query_map(iter::empty::<&dyn ToSql>(), row_to_album)?;
+
+        let mut albums: Vec<PhotoDbAlbum> = Vec::new();
+        for album_result in albums_results {
+            let album = album_result?;
+            albums.push(album);
+        }
+        Result::Ok(albums)
+    }
+
+    fn shared_album_by_name(&self, name: &str) -> Result<Option<PhotoDbAlbum>, DbError> {
+        let db = self.connection()?;
+        let result: Result<PhotoDbAlbum, rusqlite::Error> = db.query_row(
+            &format!("SELECT google_id, type, name, last_remote_check, inode FROM '{}' WHERE type = '{}' AND name = ?;", TableName::AlbumsAndMediaItems, MediaTypes::SharedAlbum),
+            &[&name], row_to_album,
+        );
+        match result {
+            Err(rusqlite::Error::QueryReturnedNoRows) => Result::Ok(Option::None),
+            Err(error) => Result::Err(DbError::from(error)),
+            Ok(album) => Result::Ok(Option::Some(album)),
+        }
+    }
+
+    fn shared_album_by_inode(&self, inode: Inode) -> Result<Option<PhotoDbAlbum>, DbError> {
+        let result = self.item_by_inode(inode)?;
+        match result {
+            None => Result::Ok(Option::None),
+            Some(item) => match item.media_type {
+                MediaTypes::SharedAlbum => Result::Ok(Option::Some(item)),
+                _ => Result::Ok(Option::None),
+            },
+        }
+    }
+
+    fn shared_album_info(&self, id: &GoogleId) -> Result<Option<SharedAlbumInfo>, DbError> {
+        let result: Result<SharedAlbumInfo, rusqlite::Error> = self.connection()?.query_row(
+            &format!(
+                "SELECT is_owned, is_joined, is_collaborative FROM '{}' WHERE album_google_id = ?;",
+                TableName::SharedAlbumInfo
+            ),
+            &[&id],
+            row_to_shared_album_info,
+        );
+        match result {
+            Err(rusqlite::Error::QueryReturnedNoRows) => Result::Ok(Option::None),
+            Err(error) => Result::Err(DbError::from(error)),
+            Ok(share_info) => Result::Ok(Option::Some(share_info)),
+        }
+    }
+
+    fn upsert_shared_album(
+        &self,
+        id: &GoogleId,
+        title: &str,
+        share_info: &SharedAlbumInfo,
+        last_modified_time: &UtcDateTime,
+    ) -> Result<Inode, DbError> {
+        // A shared album is never left without its share info
+        let mut db = self.connection()?;
+        let transaction = db.transaction()?;
+        let inode = upsert_item(
+            &transaction,
+            id,
+            MediaTypes::SharedAlbum,
+            title,
+            last_modified_time,
+        )?;
+        transaction.execute(
+            &format!("INSERT OR REPLACE INTO '{}' (album_google_id, is_owned, is_joined, is_collaborative) VALUES (?, ?, ?, ?);", TableName::SharedAlbumInfo),
+            &[&id as &dyn ToSql, &share_info.is_owned, &share_info.is_joined, &share_info.is_collaborative],
+        )?;
+        transaction.commit()?;
+        Result::Ok(inode)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::db::PhotoDb;
+
+    #[test]
+    fn sqlitedb_upsert_shared_album() -> Result<(), DbError> {
+        let db = SqliteDb::in_memory()?;
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let share_info = SharedAlbumInfo::new(false, true, true);
+
+        // Assert DB is empty
+        assert_eq!(db.shared_albums()?.len(), 0);
+        assert!(db.shared_album_info("GoogleIdSharedAlbum1")?.is_none());
+
+        // Test insert
+        let inode =
+            db.upsert_shared_album("GoogleIdSharedAlbum1", "Shared 1", &share_info, &now)?;
+        db.upsert_album("GoogleIdAlbum1", "Album 1", &now)?;
+
+        let shared_albums = db.shared_albums()?;
+        assert_eq!(shared_albums.len(), 1);
+        assert_eq!(shared_albums[0].google_id(), "GoogleIdSharedAlbum1");
+        assert_eq!(shared_albums[0].media_type, MediaTypes::SharedAlbum);
+        assert_eq!(
+            db.shared_album_info("GoogleIdSharedAlbum1")?.unwrap(),
+            share_info
+        );
+
+        // Shared albums and albums are listed separately
+        assert_eq!(db.albums()?.len(), 1);
+        assert!(db.album_by_inode(inode)?.is_none());
+        assert!(db.album_by_name("Shared 1")?.is_none());
+
+        // Test upsert
+        let share_info = SharedAlbumInfo::new(true, true, false);
+        let inode = db.upsert_shared_album(
+            "GoogleIdSharedAlbum1",
+            "Shared 1 new title",
+            &share_info,
+            &now,
+        )?;
+        let shared_albums = db.shared_albums()?;
+        assert_eq!(shared_albums.len(), 1);
+        assert_eq!(shared_albums[0].name, "Shared 1 new title");
+        assert_eq!(shared_albums[0].inode, inode);
+        assert_eq!(
+            db.shared_album_info("GoogleIdSharedAlbum1")?.unwrap(),
+            share_info
+        );
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn sqlitedb_shared_album_by_x() -> Result<(), DbError> {
+        let db = SqliteDb::in_memory()?;
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let share_info = SharedAlbumInfo::new(false, true, false);
+
+        // Assert when DB is empty
+        assert!(db.shared_album_by_inode(100)?.is_none());
+        assert!(db.shared_album_by_name("foo")?.is_none());
+
+        // insert some data
+        let inode1 = db.upsert_shared_album("GoogleId1", "Shared 1", &share_info, &now)?;
+        let inode2 = db.upsert_album("GoogleId2", "Album 1", &now)?;
+
+        assert_eq!(
+            db.shared_album_by_inode(inode1)?.unwrap().google_id(),
+            "GoogleId1"
+        );
+        assert_eq!(
+            db.shared_album_by_name("Shared 1")?.unwrap().google_id(),
+            "GoogleId1"
+        );
+        assert!(db.shared_album_by_inode(inode2)?.is_none());
+        assert!(db.shared_album_by_name("Album 1")?.is_none());
+
+        Result::Ok(())
+    }
+}
@@ -6,6 +6,11 @@ pub enum TableName {
     NextInode,
     MediaItemsInAlbum,
     OauthTokenStorage,
+    OauthTokenKeySalt,
+    MediaItemsInCategory,
+    SharedAlbumInfo,
+    MediaItemMetadata,
+    FavouriteMediaItems,
 }
 
 impl fmt::Display for TableName {
@@ -15,6 +20,11 @@ impl fmt::Display for TableName {
             TableName::NextInode => write!(f, "next_inode"),
             TableName::MediaItemsInAlbum => write!(f, "media_items_in_album"),
             TableName::OauthTokenStorage => write!(f, "oauth_token_storage"),
+            TableName::OauthTokenKeySalt => write!(f, "oauth_token_key_salt"),
+            TableName::MediaItemsInCategory => write!(f, "media_items_in_category"),
+            TableName::SharedAlbumInfo => write!(f, "shared_album_info"),
+            TableName::MediaItemMetadata => write!(f, "media_item_metadata"),
+            TableName::FavouriteMediaItems => write!(f, "favourite_media_items"),
         }
     }
 }
@@ -54,5 +64,50 @@ mod test {
             format!("{:?}", TableName::OauthTokenStorage),
             "OauthTokenStorage"
         );
+
+        assert_eq!(
+            format!("{}", TableName::OauthTokenKeySalt),
+            "oauth_token_key_salt"
+        );
+        assert_eq!(
+            format!("{:?}", TableName::OauthTokenKeySalt),
+            "OauthTokenKeySalt"
+        );
+
+        assert_eq!(
+            format!("{}", TableName::MediaItemsInCategory),
+            "media_items_in_category"
+        );
+        assert_eq!(
+            format!("{:?}", TableName::MediaItemsInCategory),
+            "MediaItemsInCategory"
+        );
+
+        assert_eq!(
+            format!("{}", TableName::SharedAlbumInfo),
+            "shared_album_info"
+        );
+        assert_eq!(
+            format!("{:?}", TableName::SharedAlbumInfo),
+            "SharedAlbumInfo"
+        );
+
+        assert_eq!(
+            format!("{}", TableName::MediaItemMetadata),
+            "media_item_metadata"
+        );
+        assert_eq!(
+            format!("{:?}", TableName::MediaItemMetadata),
+            "MediaItemMetadata"
+        );
+
+        assert_eq!(
+            format!("{}", TableName::FavouriteMediaItems),
+            "favourite_media_items"
+        );
+        assert_eq!(
+            format!("{:?}", TableName::FavouriteMediaItems),
+            "FavouriteMediaItems"
+        );
     }
 }
@@ -10,6 +10,13 @@ use crate::db::{DbError, SqliteDb, TableName};
 pub trait TokenStorageDb: Sized {
     fn get_oath_token(&self, scope_hash: u64) -> Result<Option<String>, DbError>;
     fn set_oath_token(&self, scope_hash: u64, token: Option<String>) -> Result<(), DbError>;
+    // Whether a token is stored for any scopes
+    fn has_oath_token(&self) -> Result<bool, DbError>;
+    // All stored tokens with their scope hash
+    fn oath_tokens(&self) -> Result<Vec<(u64, String)>, DbError>;
+    // Salt for deriving the token key from a passphrase, one per DB
+    fn token_key_salt(&self) -> Result<Option<Vec<u8>>, DbError>;
+    fn set_token_key_salt(&self, salt: &[u8]) -> Result<(), DbError>;
 }
 
 pub fn ensure_schema_token_storage(db: &Mutex<rusqlite::Connection>) -> Result<(), DbError> {
@@ -26,6 +33,20 @@ pub fn ensure_schema_token_storage(db: &Mutex<rusqlite::Connection>) -> Result<(
         ),
         iter::empty::<&dyn ToSql>(),
     )?;
+    db.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS '{}' (
+                id                INTEGER NOT NULL CHECK (id = 0),
+                salt              BLOB NOT NULL,
+                PRIMARY KEY (id)
+            );",
+            TableName::OauthTokenKeySalt
+        ),
+        iter::empty::<&dyn ToSql>(),
+    )?;
+
+    // Replaced plaintext tokens must not linger in free pages of the DB file
+    db.execute_batch("PRAGMA secure_delete = ON;")?;
 
     Result::Ok(())
 }
@@ -33,7 +54,7 @@ pub fn ensure_schema_token_storage(db: &Mutex<rusqlite::Connection>) -> Result<(
 impl TokenStorageDb for SqliteDb {
     fn get_oath_token(&self, scope_hash: u64) -> Result<Option<String>, DbError> {
         let scope_hash = scope_hash as i64;
-        let result: Result<String, rusqlite::Error> = self.db.lock()?.query_row(
+        let result: Result<String, rusqlite::Error> = self.connection()?.query_row(
             &format!(
                 "SELECT token FROM '{}' WHERE scope_hash = ?;",
                 TableName::OauthTokenStorage
@@ -52,7 +73,7 @@ impl TokenStorageDb for SqliteDb {
         let scope_hash = scope_hash as i64;
         match token {
             Some(token_value) => {
-                self.db.lock()?.execute(
+                self.connection()?.execute(
                     &format!(
                         "INSERT OR REPLACE INTO '{}' (scope_hash, token) VALUES (?, ?);",
                         TableName::OauthTokenStorage
@@ -61,7 +82,7 @@ impl TokenStorageDb for SqliteDb {
                 )?;
             }
             None => {
-                self.db.lock()?.execute(
+                self.connection()?.execute(
                     &format!(
                         "DELETE FROM '{}' WHERE scope_hash = ?;",
                         TableName::OauthTokenStorage
@@ -72,6 +93,59 @@ impl TokenStorageDb for SqliteDb {
         }
         Result::Ok(())
     }
+
+    fn has_oath_token(&self) -> Result<bool, DbError> {
+        let count: i64 = self.connection()?.query_row(
+            &format!("SELECT COUNT(*) FROM '{}';", TableName::OauthTokenStorage),
+            iter::empty::<&dyn ToSql>(),
+            |row| row.get(0),
+        )?;
+        Result::Ok(count > 0)
+    }
+
+    fn oath_tokens(&self) -> Result<Vec<(u64, String)>, DbError> {
+        let db = self.connection()?;
+        let mut statment = db.prepare(&format!(
+            "SELECT scope_hash, token FROM '{}' ORDER BY scope_hash;",
+            TableName::OauthTokenStorage
+        ))?;
+        let rows = statment.query_map(iter::empty::<&dyn ToSql>(), |row| {
+            let scope_hash: i64 = row.get(0)?;
+            Result::Ok((scope_hash as u64, row.get(1)?))
+        })?;
+        let mut tokens = Vec::new();
+        for token in rows {
+            tokens.push(token?);
+        }
+        Result::Ok(tokens)
+    }
+
+    fn token_key_salt(&self) -> Result<Option<Vec<u8>>, DbError> {
+        let result: Result<Vec<u8>, rusqlite::Error> = self.connection()?.query_row(
+            &format!(
+                "SELECT salt FROM '{}' WHERE id = 0;",
+                TableName::OauthTokenKeySalt
+            ),
+            iter::empty::<&dyn ToSql>(),
+            |row| row.get(0),
+        );
+        match result {
+            Err(rusqlite::Error::QueryReturnedNoRows) => Result::Ok(Option::None),
+            Err(error) => Result::Err(DbError::from(error)),
+            Ok(salt) => Result::Ok(Option::Some(salt)),
+        }
+    }
+
+    fn set_token_key_salt(&self, salt: &[u8]) -> Result<(), DbError> {
+        self.connection()?.execute(
+            &format!(
+                "INSERT OR REPLACE INTO '{}' (id, salt) VALUES (0, ?);",
+                TableName::OauthTokenKeySalt
+            ),
+            &[&salt],
+        )?;
+        Result::Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -83,6 +157,7 @@ mod test {
         let db = SqliteDb::in_memory().unwrap();
 
         assert!(db.get_oath_token(0).unwrap().is_none());
+        assert!(!db.has_oath_token().unwrap());
 
         db.set_oath_token(0, Option::None).unwrap();
         assert!(db.get_oath_token(0).unwrap().is_none());
@@ -91,6 +166,7 @@ mod test {
         db.set_oath_token(0, Option::Some(String::from(token0_ver0)))
             .unwrap();
         assert_eq!(db.get_oath_token(0).unwrap().unwrap(), token0_ver0);
+        assert!(db.has_oath_token().unwrap());
 
         db.set_oath_token(0, Option::None).unwrap();
         assert!(db.get_oath_token(0).unwrap().is_none());
@@ -115,4 +191,41 @@ mod test {
         assert!(db.get_oath_token(0).unwrap().is_none());
         assert_eq!(db.get_oath_token(1).unwrap().unwrap(), token1_ver0);
     }
+
+    #[test]
+    fn sqlitedb_oath_tokens() {
+        let db = SqliteDb::in_memory().unwrap();
+        assert!(db.oath_tokens().unwrap().is_empty());
+
+        db.set_oath_token(2, Option::Some(String::from("token2")))
+            .unwrap();
+        db.set_oath_token(1, Option::Some(String::from("token1")))
+            .unwrap();
+        // Hashes are u64, but stored as i64
+        db.set_oath_token(u64::max_value(), Option::Some(String::from("token_max")))
+            .unwrap();
+        assert_eq!(
+            db.oath_tokens().unwrap(),
+            vec![
+                (u64::max_value(), String::from("token_max")),
+                (1, String::from("token1")),
+                (2, String::from("token2"))
+            ]
+        );
+
+        db.set_oath_token(1, Option::None).unwrap();
+        assert_eq!(db.oath_tokens().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn sqlitedb_token_key_salt() {
+        let db = SqliteDb::in_memory().unwrap();
+        assert!(db.token_key_salt().unwrap().is_none());
+
+        db.set_token_key_salt(&[1, 2, 3]).unwrap();
+        assert_eq!(db.token_key_salt().unwrap().unwrap(), vec![1, 2, 3]);
+
+        db.set_token_key_salt(&[4, 5, 6]).unwrap();
+        assert_eq!(db.token_key_salt().unwrap().unwrap(), vec![4, 5, 6]);
+    }
 }
@@ -2,11 +2,13 @@ use chrono::prelude::*;
 use chrono::Utc;
 use std::convert::From;
 use std::fmt;
+use std::str::FromStr;
 
 #[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
 pub enum MediaTypes {
     Album,
     MediaItem,
+    SharedAlbum,
 }
 
 impl fmt::Display for MediaTypes {
@@ -14,6 +16,7 @@ impl fmt::Display for MediaTypes {
         match self {
             MediaTypes::Album => write!(f, "album"),
             MediaTypes::MediaItem => write!(f, "media_item"),
+            MediaTypes::SharedAlbum => write!(f, "shared_album"),
         }
     }
 }
@@ -23,11 +26,128 @@ impl<'a> From<&'a str> for MediaTypes {
         match media_type {
             "album" => MediaTypes::Album,
             "media_item" => MediaTypes::MediaItem,
+            "shared_album" => MediaTypes::SharedAlbum,
             _ => panic!("Unknown media type {}", media_type),
         }
     }
 }
 
+#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+pub enum ContentCategory {
+    Animals,
+    Arts,
+    Birthdays,
+    Cityscapes,
+    Crafts,
+    Documents,
+    Fashion,
+    Flowers,
+    Food,
+    Gardens,
+    Holidays,
+    Houses,
+    Landmarks,
+    Landscapes,
+    Night,
+    People,
+    Performances,
+    Pets,
+    Receipts,
+    Screenshots,
+    Selfies,
+    Sport,
+    Travel,
+    Utility,
+    Weddings,
+    Whiteboards,
+}
+
+impl ContentCategory {
+    pub fn all() -> &'static [ContentCategory] {
+        &[
+            ContentCategory::Animals,
+            ContentCategory::Arts,
+            ContentCategory::Birthdays,
+            ContentCategory::Cityscapes,
+            ContentCategory::Crafts,
+            ContentCategory::Documents,
+            ContentCategory::Fashion,
+            ContentCategory::Flowers,
+            ContentCategory::Food,
+            ContentCategory::Gardens,
+            ContentCategory::Holidays,
+            ContentCategory::Houses,
+            ContentCategory::Landmarks,
+            ContentCategory::Landscapes,
+            ContentCategory::Night,
+            ContentCategory::People,
+            ContentCategory::Performances,
+            ContentCategory::Pets,
+            ContentCategory::Receipts,
+            ContentCategory::Screenshots,
+            ContentCategory::Selfies,
+            ContentCategory::Sport,
+            ContentCategory::Travel,
+            ContentCategory::Utility,
+            ContentCategory::Weddings,
+            ContentCategory::Whiteboards,
+        ]
+    }
+
+    // Lower case name used for the directory in the FUSE tree
+    pub fn dir_name(self) -> String {
+        format!("{}", self).to_lowercase()
+    }
+}
+
+// Upper case name as used by the Google Photos contentFilter
+impl fmt::Display for ContentCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ContentCategory::Animals => "ANIMALS",
+            ContentCategory::Arts => "ARTS",
+            ContentCategory::Birthdays => "BIRTHDAYS",
+            ContentCategory::Cityscapes => "CITYSCAPES",
+            ContentCategory::Crafts => "CRAFTS",
+            ContentCategory::Documents => "DOCUMENTS",
+            ContentCategory::Fashion => "FASHION",
+            ContentCategory::Flowers => "FLOWERS",
+            ContentCategory::Food => "FOOD",
+            ContentCategory::Gardens => "GARDENS",
+            ContentCategory::Holidays => "HOLIDAYS",
+            ContentCategory::Houses => "HOUSES",
+            ContentCategory::Landmarks => "LANDMARKS",
+            ContentCategory::Landscapes => "LANDSCAPES",
+            ContentCategory::Night => "NIGHT",
+            ContentCategory::People => "PEOPLE",
+            ContentCategory::Performances => "PERFORMANCES",
+            ContentCategory::Pets => "PETS",
+            ContentCategory::Receipts => "RECEIPTS",
+            ContentCategory::Screenshots => "SCREENSHOTS",
+            ContentCategory::Selfies => "SELFIES",
+            ContentCategory::Sport => "SPORT",
+            ContentCategory::Travel => "TRAVEL",
+            ContentCategory::Utility => "UTILITY",
+            ContentCategory::Weddings => "WEDDINGS",
+            ContentCategory::Whiteboards => "WHITEBOARDS",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ContentCategory {
+    type Err = String;
+
+    fn from_str(category: &str) -> Result<Self, Self::Err> {
+        let upper_case = category.trim().to_uppercase();
+        ContentCategory::all()
+            .iter()
+            .find(|known| format!("{}", known) == upper_case)
+            .cloned()
+            .ok_or_else(|| format!("Unknown content category {}", category))
+    }
+}
+
 pub type Inode = u64;
 pub type UtcDateTime = DateTime<Utc>;
 pub type GoogleId = str;
@@ -61,11 +181,38 @@ impl PhotoDbMediaItemAlbum {
     pub fn google_id(&self) -> &GoogleId {
         &self.id
     }
+
+    pub fn is_video(&self) -> bool {
+        let filename_lowercase = self.name.to_lowercase();
+        filename_lowercase.ends_with(".mp4")
+            || filename_lowercase.ends_with(".mts")
+            || filename_lowercase.ends_with(".avi") // TODO: Use MIME Type
+    }
 }
 
 pub type PhotoDbAlbum = PhotoDbMediaItemAlbum;
 pub type PhotoDbMediaItem = PhotoDbMediaItemAlbum;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, new)]
+pub struct SharedAlbumInfo {
+    pub is_owned: bool,
+    pub is_joined: bool,
+    pub is_collaborative: bool,
+}
+
+// Everything known about a media item besides its name, as reported by the API
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct MediaItemMetadata {
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+    pub product_url: Option<String>,
+    pub creation_time: Option<UtcDateTime>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -74,6 +221,7 @@ mod test {
     fn media_types_from_string() {
         assert_eq!(MediaTypes::from("album"), MediaTypes::Album);
         assert_eq!(MediaTypes::from("media_item"), MediaTypes::MediaItem);
+        assert_eq!(MediaTypes::from("shared_album"), MediaTypes::SharedAlbum);
     }
 
     #[test]
@@ -83,5 +231,54 @@ mod test {
 
         assert_eq!(format!("{}", MediaTypes::MediaItem), "media_item");
         assert_eq!(format!("{:?}", MediaTypes::MediaItem), "MediaItem");
+
+        assert_eq!(format!("{}", MediaTypes::SharedAlbum), "shared_album");
+        assert_eq!(format!("{:?}", MediaTypes::SharedAlbum), "SharedAlbum");
+    }
+
+    #[test]
+    fn content_category_from_string() {
+        assert_eq!(
+            "RECEIPTS".parse::<ContentCategory>(),
+            Result::Ok(ContentCategory::Receipts)
+        );
+        assert_eq!(
+            "receipts".parse::<ContentCategory>(),
+            Result::Ok(ContentCategory::Receipts)
+        );
+        assert_eq!(
+            " Screenshots ".parse::<ContentCategory>(),
+            Result::Ok(ContentCategory::Screenshots)
+        );
+        assert!("not_a_category".parse::<ContentCategory>().is_err());
+    }
+
+    #[test]
+    fn content_category_to_string() {
+        assert_eq!(format!("{}", ContentCategory::Documents), "DOCUMENTS");
+        assert_eq!(ContentCategory::Documents.dir_name(), "documents");
+
+        for category in ContentCategory::all() {
+            assert_eq!(
+                format!("{}", category).parse::<ContentCategory>(),
+                Result::Ok(*category)
+            );
+        }
+    }
+
+    #[test]
+    fn media_item_is_video() {
+        let media_item = |name: &str| {
+            PhotoDbMediaItem::new(
+                String::from("GoogleId1"),
+                String::from(name),
+                MediaTypes::MediaItem,
+                Utc::now(),
+                100,
+            )
+        };
+        assert!(media_item("clip.mp4").is_video());
+        assert!(media_item("CLIP.MTS").is_video());
+        assert!(!media_item("photo.jpg").is_video());
     }
 }
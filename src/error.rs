@@ -1,11 +1,20 @@
 use std::convert::From;
 use std::fmt;
+use std::io;
 
 use crate::db;
+use crate::photolib::{OauthTokenStorageError, RemotePhotoLibError};
 
 #[derive(Debug)]
 pub enum PhotoOxideError {
     DbError(db::DbError),
+    RemotePhotoLibError(RemotePhotoLibError),
+    TokenStorageError(OauthTokenStorageError),
+    IoError(io::Error),
+    ConfigError(String),
+    AuthError(String),
+    UpdateError(String),
+    NotFound(String),
 }
 
 impl From<db::DbError> for PhotoOxideError {
@@ -14,10 +23,41 @@ impl From<db::DbError> for PhotoOxideError {
     }
 }
 
+impl From<RemotePhotoLibError> for PhotoOxideError {
+    fn from(error: RemotePhotoLibError) -> Self {
+        PhotoOxideError::RemotePhotoLibError(error)
+    }
+}
+
+impl From<OauthTokenStorageError> for PhotoOxideError {
+    fn from(error: OauthTokenStorageError) -> Self {
+        PhotoOxideError::TokenStorageError(error)
+    }
+}
+
+impl From<io::Error> for PhotoOxideError {
+    fn from(error: io::Error) -> Self {
+        PhotoOxideError::IoError(error)
+    }
+}
+
+impl From<toml::de::Error> for PhotoOxideError {
+    fn from(error: toml::de::Error) -> Self {
+        PhotoOxideError::ConfigError(format!("{}", error))
+    }
+}
+
 impl std::error::Error for PhotoOxideError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             PhotoOxideError::DbError(err) => Option::Some(err),
+            PhotoOxideError::RemotePhotoLibError(err) => Option::Some(err),
+            PhotoOxideError::TokenStorageError(err) => Option::Some(err),
+            PhotoOxideError::IoError(err) => Option::Some(err),
+            PhotoOxideError::ConfigError(_) => Option::None,
+            PhotoOxideError::AuthError(_) => Option::None,
+            PhotoOxideError::UpdateError(_) => Option::None,
+            PhotoOxideError::NotFound(_) => Option::None,
         }
     }
 }
@@ -26,6 +66,21 @@ impl fmt::Display for PhotoOxideError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PhotoOxideError::DbError(err) => write!(f, "PhotoOxideError: DbError({:?})", err),
+            PhotoOxideError::RemotePhotoLibError(err) => {
+                write!(f, "PhotoOxideError: RemotePhotoLibError({})", err)
+            }
+            PhotoOxideError::TokenStorageError(err) => {
+                write!(f, "PhotoOxideError: TokenStorageError({})", err)
+            }
+            PhotoOxideError::IoError(err) => write!(f, "PhotoOxideError: IoError({})", err),
+            PhotoOxideError::ConfigError(msg) => {
+                write!(f, "PhotoOxideError: ConfigError({})", msg)
+            }
+            PhotoOxideError::AuthError(msg) => write!(f, "PhotoOxideError: AuthError({})", msg),
+            PhotoOxideError::UpdateError(msg) => {
+                write!(f, "PhotoOxideError: UpdateError({})", msg)
+            }
+            PhotoOxideError::NotFound(name) => write!(f, "PhotoOxideError: NotFound({})", name),
         }
     }
 }
@@ -40,5 +95,12 @@ mod test {
             format!("{}", PhotoOxideError::DbError(db::DbError::LockingError)),
             "PhotoOxideError: DbError(LockingError)"
         );
+        assert_eq!(
+            format!(
+                "{}",
+                PhotoOxideError::NotFound(String::from("IMG_0001.jpg"))
+            ),
+            "PhotoOxideError: NotFound(IMG_0001.jpg)"
+        );
     }
 }
@@ -2,6 +2,8 @@
 extern crate log;
 #[macro_use]
 extern crate derive_new;
+#[macro_use]
+extern crate serde_derive;
 
 use env_logger;
 
@@ -11,23 +13,42 @@ use google_photoslibrary1 as photoslibrary1;
 use hyper;
 use hyper_rustls;
 
-use serde_json;
 use yup_oauth2 as oauth2;
 
 use scheduled_executor;
 
-use std::env;
+use structopt::StructOpt;
+
 use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, Write};
 use std::option::Option;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 use crate::oauth2::{
-    Authenticator, ConsoleApplicationSecret, DefaultAuthenticatorDelegate, FlowType,
+    ApplicationSecret, Authenticator, DefaultAuthenticatorDelegate, FlowType, GetToken,
+    GOOGLE_DEVICE_CODE_URL,
 };
 use crate::photoslibrary1::PhotosLibrary;
 
 mod background_update;
-use crate::background_update::{BackgroundAlbumUpdate, BackgroundMediaUpdate, BackgroundUpdate};
+use crate::background_update::{
+    BackgroundAlbumUpdate, BackgroundCategoryUpdate, BackgroundMediaUpdate,
+    BackgroundSharedAlbumUpdate, BackgroundUpdate,
+};
+
+mod client_secret;
+use crate::client_secret::client_secret;
+
+mod config;
+use crate::config::{Config, RefreshConfig};
+
+mod cli;
+use crate::cli::{Command, Options};
+
+mod daemon;
+use crate::daemon::{daemonize, PidFile};
 
 mod domain;
 
@@ -35,10 +56,16 @@ mod error;
 use crate::error::PhotoOxideError;
 
 mod db;
-use crate::db::SqliteDb;
+use crate::db::{Filter, PhotoDbRo, SharedAlbumDb, SqliteDb, TokenStorageDb};
+
+mod notify;
+use crate::notify::Notifier;
 
 mod photolib;
-use crate::photolib::{HttpRemotePhotoLib, OauthTokenStorage};
+use crate::photolib::{
+    revoke_token, HttpRemotePhotoLib, MediaVariant, NonInteractiveDelegate, OauthTokenStorage,
+    RemotePhotoLibData, SharedAuthenticator,
+};
 
 mod photofs;
 use crate::photofs::*;
@@ -46,68 +73,172 @@ use crate::photofs::*;
 mod rust_filesystem;
 use crate::rust_filesystem::RustFilesystemReal;
 
-const CLIENT_SECRET: &str = include_str!("../client_secret.json");
+mod shutdown;
+use crate::shutdown::unmount_on_signal;
 
-fn main() -> Result<(), PhotoOxideError> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("photooxide=info,photooxide::db::debug,photooxide::photofs=error,photooxide::photolib=debug")).init();
-    info!("Logging init");
+mod token_key;
+use crate::token_key::token_cipher;
+
+// Metadata calls (listings, base URLs) and downloads are limited separately, so a few
+// large downloads do not hold up browsing
+const MAX_CONCURRENT_API_CALLS: usize = 2;
+const MAX_CONCURRENT_DOWNLOADS: usize = 4;
 
-    let db = Arc::new(SqliteDb::from_path("cache.sqlite")?);
-
-    let auth;
-    {
-        // Get an ApplicationSecret instance by some means. It contains the `client_id` and
-        // `client_secret`, among other things.
-        let secret = serde_json::from_str::<ConsoleApplicationSecret>(CLIENT_SECRET)
-            .unwrap()
-            .installed
-            .unwrap();
-
-        let token_storage = OauthTokenStorage::new(db.clone());
-        auth = Authenticator::new(
-            &secret,
-            DefaultAuthenticatorDelegate,
-            hyper::Client::with_connector(hyper::net::HttpsConnector::new(
-                hyper_rustls::TlsClient::new(),
-            )),
-            token_storage,
-            Option::Some(FlowType::InstalledInteractive),
-        );
+type PhotoAuthenticator =
+    Authenticator<NonInteractiveDelegate, OauthTokenStorage<SqliteDb>, hyper::Client>;
+type PhotoLib = HttpRemotePhotoLib<hyper::Client, SharedAuthenticator<PhotoAuthenticator>>;
+
+fn https_client() -> hyper::Client {
+    hyper::Client::with_connector(hyper::net::HttpsConnector::new(
+        hyper_rustls::TlsClient::new(),
+    ))
+}
+
+// Tokens stored before they were encrypted are migrated as soon as the key is known
+fn token_storage(
+    db: &Arc<SqliteDb>,
+    config: &Config,
+) -> Result<OauthTokenStorage<SqliteDb>, PhotoOxideError> {
+    let storage = OauthTokenStorage::new(db.clone(), Arc::new(token_cipher(config, &**db)?));
+    let migrated = storage.encrypt_plaintext_tokens()?;
+    if migrated > 0 {
+        info!("Encrypted {} token(s) stored in plain text", migrated);
     }
+    Result::Ok(storage)
+}
+
+// Used for all API calls, it fails rather than asks for authorization. The scopes of the
+// mount mode decide which of the stored tokens is used.
+fn shared_authenticator(
+    db: &Arc<SqliteDb>,
+    config: &Config,
+    secret: &ApplicationSecret,
+) -> Result<SharedAuthenticator<PhotoAuthenticator>, PhotoOxideError> {
+    let authenticator = Authenticator::new(
+        secret,
+        NonInteractiveDelegate,
+        https_client(),
+        token_storage(db, config)?,
+        Option::Some(FlowType::InstalledInteractive),
+    );
+    Result::Ok(SharedAuthenticator::new(
+        Arc::new(Mutex::new(authenticator)),
+        config.mount.mode.scopes(),
+    ))
+}
 
-    let remote_photo_lib;
-    {
-        let api_http_client = hyper::Client::with_connector(hyper::net::HttpsConnector::new(
-            hyper_rustls::TlsClient::new(),
-        ));
-        let data_http_client = hyper::Client::with_connector(hyper::net::HttpsConnector::new(
-            hyper_rustls::TlsClient::new(),
-        ));
-
-        let photos_library = PhotosLibrary::new(api_http_client, auth);
-        remote_photo_lib = Arc::new(Mutex::new(HttpRemotePhotoLib::new(
-            photos_library,
-            data_http_client,
-        )));
+// Fetches, or refreshes, the stored token up front, so a missing one is reported
+// before mounting rather than on the first listing
+fn require_token(
+    auth: &SharedAuthenticator<PhotoAuthenticator>,
+    config: &Config,
+) -> Result<(), PhotoOxideError> {
+    let mode = config.mount.mode;
+    auth.clone()
+        .token(&mode.scopes())
+        .map(|_| ())
+        .map_err(|error| {
+            PhotoOxideError::AuthError(format!(
+                "No valid token stored ({}), run `photooxide auth{}` first",
+                error,
+                if mode.is_writable() {
+                    " --read-write"
+                } else {
+                    ""
+                }
+            ))
+        })
+}
+
+fn remote_photo_lib(auth: &SharedAuthenticator<PhotoAuthenticator>) -> Arc<PhotoLib> {
+    let photos_libraries = (0..MAX_CONCURRENT_API_CALLS)
+        .map(|_| PhotosLibrary::new(https_client(), auth.clone()))
+        .collect();
+    let data_http_clients = (0..MAX_CONCURRENT_DOWNLOADS)
+        .map(|_| https_client())
+        .collect();
+
+    Arc::new(HttpRemotePhotoLib::new(photos_libraries, data_http_clients))
+}
+
+fn updaters(
+    remote_photo_lib: &Arc<PhotoLib>,
+    db: &Arc<SqliteDb>,
+    refresh: &RefreshConfig,
+    notifier: &Notifier,
+) -> Vec<Box<dyn BackgroundUpdate>> {
+    vec![
+        Box::new(BackgroundMediaUpdate {
+            remote_photo_lib: remote_photo_lib.clone(),
+            db: db.clone(),
+            schedule: refresh.media_items,
+            notifier: notifier.clone(),
+        }),
+        Box::new(BackgroundAlbumUpdate {
+            remote_photo_lib: remote_photo_lib.clone(),
+            db: db.clone(),
+            schedule: refresh.albums,
+            notifier: notifier.clone(),
+        }),
+        Box::new(BackgroundSharedAlbumUpdate {
+            remote_photo_lib: remote_photo_lib.clone(),
+            db: db.clone(),
+            schedule: refresh.shared_albums,
+            notifier: notifier.clone(),
+        }),
+        Box::new(BackgroundCategoryUpdate {
+            remote_photo_lib: remote_photo_lib.clone(),
+            db: db.clone(),
+            schedule: refresh.categories,
+            notifier: notifier.clone(),
+            categories: refresh.content_categories(),
+        }),
+    ]
+}
+
+fn mount(db: Arc<SqliteDb>, config: Config) -> Result<(), PhotoOxideError> {
+    let mountpoint = match config.mount.mountpoint {
+        Option::Some(ref mountpoint) => mountpoint.clone(),
+        Option::None => {
+            return Result::Err(PhotoOxideError::ConfigError(String::from(
+                "No mountpoint given or configured",
+            )))
+        }
+    };
+    let auth = shared_authenticator(&db, &config, &client_secret(&config)?)?;
+    require_token(&auth, &config)?;
+
+    // Only once authorized, so a missing token is still reported on the terminal
+    if config.mount.daemon {
+        info!("Detaching from the terminal");
+        daemonize()?;
     }
+    let _pid_file = match config.mount.pid_file {
+        Option::Some(ref path) => Option::Some(PidFile::create(path)?),
+        Option::None => Option::None,
+    };
+    let notifier = Notifier::from_env();
 
-    let fs = RustFilesystemReal::new(PhotoFs::new(remote_photo_lib.clone(), db.clone()));
+    let remote_photo_lib = remote_photo_lib(&auth);
+
+    let fs = RustFilesystemReal::new(PhotoFs::with_config(
+        remote_photo_lib.clone(),
+        db.clone(),
+        PhotoFsConfig {
+            writable: config.mount.mode.is_writable(),
+            ..config.filesystem.clone()
+        },
+    ));
 
     let executor;
-    let mut scheduled_tasks: Vec<(&str, scheduled_executor::executor::TaskHandle)> = Vec::new();
-    if env::var("PHOTOOXIDE_DISABLE_REFRESH").is_err() {
+    let mut scheduled_tasks: Vec<(
+        &str,
+        scheduled_executor::executor::TaskHandle,
+        Arc<Mutex<()>>,
+    )> = Vec::new();
+    if config.refresh.enabled {
         executor = scheduled_executor::ThreadPoolExecutor::new(2).unwrap();
-        let updaters: Vec<Box<dyn BackgroundUpdate>> = vec![
-            Box::new(BackgroundAlbumUpdate {
-                remote_photo_lib: remote_photo_lib.clone(),
-                db: db.clone(),
-            }),
-            Box::new(BackgroundMediaUpdate {
-                remote_photo_lib: remote_photo_lib.clone(),
-                db: db.clone(),
-            }),
-        ];
-        for updater in updaters {
+        for updater in updaters(&remote_photo_lib, &db, &config.refresh, &notifier) {
             let name = updater.name();
             let delay = updater
                 .delay()
@@ -118,40 +249,227 @@ fn main() -> Result<(), PhotoOxideError> {
                 .to_std()
                 .expect("Failed to convert to std::time::duration");
 
-            let task = executor.schedule_fixed_rate(delay, interval, move |_remote| match updater
-                .update()
-            {
-                Err(msg) => error!("Background update of {} failed: {}", name, msg),
-                Ok(_) => debug!("Background update of {} OK!", name),
+            // Held while the update runs, so the shutdown can wait for it
+            let running = Arc::new(Mutex::new(()));
+            let task_running = running.clone();
+            let task_remote_photo_lib = remote_photo_lib.clone();
+            let task = executor.schedule_fixed_rate(delay, interval, move |_remote| {
+                let _running = task_running.lock();
+                match updater.update() {
+                    Err(_) if task_remote_photo_lib.is_stopped() => {
+                        info!("Background update of {} stopped", name)
+                    }
+                    Err(msg) => error!("Background update of {} failed: {}", name, msg),
+                    Ok(_) => debug!("Background update of {} OK!", name),
+                }
             });
-            scheduled_tasks.push((name, task));
+            scheduled_tasks.push((name, task, running));
         }
     }
 
-    let mountpoint = env::args_os().nth(1).unwrap();
-    let options = ["-o", "ro", "-o", "fsname=photooxide"] // "-o", "default_permissions",
+    let mut options = vec![format!("fsname={}", config.mount.fsname)]; // "default_permissions",
+    if !config.mount.mode.is_writable() {
+        options.push(String::from("ro"));
+    }
+    if config.mount.allow_other {
+        options.push(String::from("allow_other"));
+        if config.filesystem.allowed_uids.is_empty() && config.filesystem.allowed_gids.is_empty() {
+            warn!("allow_other without allowed_uids or allowed_gids, other users are still denied");
+        }
+    }
+    options.extend(config.mount.options.iter().cloned());
+    let options = options
         .iter()
-        .map(|o| o.as_ref())
+        .flat_map(|option| vec![OsStr::new("-o"), OsStr::new(option)])
         .collect::<Vec<&OsStr>>();
 
+    unmount_on_signal(&mountpoint)?;
     info!("starting FUSE mount at {:?} with {:?}", mountpoint, options);
-    match fuse::mount(fs, &mountpoint, &options) {
+    // The mount is live once the session exists, requests wait for run to answer them
+    match fuse::Session::new(fs, &mountpoint, &options) {
         Err(msg) => error!("FUSE mount failed: {}", msg),
-        Ok(_) => info!("FUSE mount ended without error"),
+        Ok(mut session) => {
+            notifier.ready();
+            notifier.status(&format!("mounted at {}", mountpoint.display()));
+            match session.run() {
+                Err(msg) => error!("FUSE session failed: {}", msg),
+                Ok(_) => info!("FUSE mount ended without error"),
+            }
+        }
     }
     info!("Ended FUSE mount");
+    notifier.stopping();
 
+    // Running updates give up at their next listing page, the DB is only closed once none
+    // is left, so no write is cut short
     info!("Stopping background tasks...");
     for task in &scheduled_tasks {
         task.1.stop();
     }
+    remote_photo_lib.stop();
     for task in &scheduled_tasks {
         while !task.1.stopped() {
             std::thread::sleep(std::time::Duration::from_millis(100));
         }
+        let _running = task.2.lock();
         debug!("Task {:?} stopped", task.0);
     }
+    db.close()?;
     info!("...stopped background tasks");
 
     Result::Ok(())
 }
+
+fn sync(db: Arc<SqliteDb>, config: Config) -> Result<(), PhotoOxideError> {
+    let auth = shared_authenticator(&db, &config, &client_secret(&config)?)?;
+    require_token(&auth, &config)?;
+    let remote_photo_lib = remote_photo_lib(&auth);
+    // A oneshot service, e.g. run by a timer, shows the progress in systemctl status too
+    let notifier = Notifier::from_env();
+    for updater in updaters(&remote_photo_lib, &db, &config.refresh, &notifier) {
+        info!("Updating {}", updater.name());
+        updater
+            .update()
+            .map_err(|msg| PhotoOxideError::UpdateError(format!("{}: {}", updater.name(), msg)))?;
+    }
+    Result::Ok(())
+}
+
+// The default out of band flow prints a URL to open on any machine with a browser and
+// reads the code shown there back from the console. The device flow instead shows a code
+// to enter at a fixed URL. Either way nothing is asked when a valid token is stored.
+fn auth(db: Arc<SqliteDb>, config: Config, device: bool) -> Result<(), PhotoOxideError> {
+    let flow = if device {
+        FlowType::Device(String::from(GOOGLE_DEVICE_CODE_URL))
+    } else {
+        FlowType::InstalledInteractive
+    };
+    Authenticator::new(
+        &client_secret(&config)?,
+        DefaultAuthenticatorDelegate,
+        https_client(),
+        token_storage(&db, &config)?,
+        Option::Some(flow),
+    )
+    .token(&config.mount.mode.scopes())
+    .map_err(|error| PhotoOxideError::AuthError(format!("{}", error)))?;
+    println!("Authorized, token stored in {}", config.db_path.display());
+    Result::Ok(())
+}
+
+// Each token is revoked before its row is removed, so a failed revocation can be retried.
+// Cached metadata stays unless purged, a later `auth` for the same account can reuse it.
+fn logout(db: Arc<SqliteDb>, config: Config, purge: bool) -> Result<(), PhotoOxideError> {
+    let storage = token_storage(&db, &config)?;
+    let client = https_client();
+    for (scope_hash, token) in db.oath_tokens()? {
+        match storage.decode(scope_hash, &token) {
+            Ok(token) => revoke_token(&client, &token)?,
+            Err(error) => warn!("Removing unreadable token without revoking it: {}", error),
+        }
+        db.set_oath_token(scope_hash, Option::None)?;
+    }
+    println!("Logged out");
+
+    if purge {
+        db.purge_cache()?;
+        println!("Removed cached albums and media items");
+    }
+    Result::Ok(())
+}
+
+fn status(db: Arc<SqliteDb>, config: Config) -> Result<(), PhotoOxideError> {
+    let authorized = if db.has_oath_token()? { "yes" } else { "no" };
+    let last_updated = |time: Option<domain::UtcDateTime>| match time {
+        Option::Some(time) => format!("{}", time),
+        Option::None => String::from("never"),
+    };
+
+    println!("DB:                    {}", config.db_path.display());
+    println!("Authorized:            {}", authorized);
+    println!("Media items:           {}", db.media_items()?.len());
+    println!("Albums:                {}", db.albums()?.len());
+    println!("Shared albums:         {}", db.shared_albums()?.len());
+    println!(
+        "Media last refreshed:  {}",
+        last_updated(db.last_updated_media()?)
+    );
+    println!(
+        "Albums last refreshed: {}",
+        last_updated(db.last_updated_album()?)
+    );
+    Result::Ok(())
+}
+
+fn ls(db: Arc<SqliteDb>, media: bool, album: Option<String>) -> Result<(), PhotoOxideError> {
+    let items = match album {
+        Option::Some(name) => match db.album_by_name(&name)? {
+            Option::Some(album) => db.media_items_in_album(album.inode)?,
+            Option::None => return Result::Err(PhotoOxideError::NotFound(name)),
+        },
+        Option::None if media => db.media_items()?,
+        Option::None => db.albums()?,
+    };
+    for item in items {
+        println!("{}", item.name);
+    }
+    Result::Ok(())
+}
+
+fn get(
+    db: Arc<SqliteDb>,
+    config: Config,
+    item: &str,
+    output: Option<&Path>,
+) -> Result<(), PhotoOxideError> {
+    let media_item = match db.media_item_by_name(item, Filter::NoFilter)? {
+        Option::Some(media_item) => media_item,
+        Option::None => match db.media_item_by_id(item)? {
+            Option::Some(media_item) => media_item,
+            Option::None => return Result::Err(PhotoOxideError::NotFound(String::from(item))),
+        },
+    };
+
+    let auth = shared_authenticator(&db, &config, &client_secret(&config)?)?;
+    require_token(&auth, &config)?;
+    let data = remote_photo_lib(&auth).media_item(
+        media_item.google_id(),
+        media_item.is_video(),
+        MediaVariant::Original,
+    )?;
+    match output {
+        Option::Some(path) => File::create(path)?.write_all(&data)?,
+        Option::None => io::stdout().write_all(&data)?,
+    }
+    Result::Ok(())
+}
+
+fn main() -> Result<(), PhotoOxideError> {
+    let options = Options::from_args();
+
+    let mut config = Config::load(options.config.as_deref())?;
+
+    env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or(config.log_filter.as_str()),
+    )
+    .init();
+    info!("Logging init");
+
+    config.apply_env();
+    options.apply(&mut config);
+
+    if let Option::Some(dir) = config.db_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let db = Arc::new(SqliteDb::from_path(&config.db_path)?);
+
+    match options.command {
+        Command::Mount { .. } => mount(db, config),
+        Command::Sync => sync(db, config),
+        Command::Auth { device, .. } => auth(db, config, device),
+        Command::Logout { purge } => logout(db, config, purge),
+        Command::Status => status(db, config),
+        Command::Ls { media, album } => ls(db, media, album),
+        Command::Get { item, output } => get(db, config, &item, output.as_deref()),
+    }
+}
@@ -0,0 +1,88 @@
+use std::env;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+
+// sd_notify(3) without libsystemd: each state is a datagram to the socket systemd passes
+// in NOTIFY_SOCKET. Without one, as outside of Type=notify services, nothing is sent.
+#[derive(Clone, Debug)]
+pub struct Notifier {
+    socket: Option<PathBuf>,
+}
+
+impl Notifier {
+    pub fn from_env() -> Notifier {
+        let socket = match env::var_os("NOTIFY_SOCKET") {
+            Option::Some(ref socket) if socket.to_string_lossy().starts_with('@') => {
+                warn!("Abstract NOTIFY_SOCKET is not supported, not notifying systemd");
+                Option::None
+            }
+            Option::Some(socket) => Option::Some(PathBuf::from(socket)),
+            Option::None => Option::None,
+        };
+        Notifier { socket }
+    }
+
+    pub fn ready(&self) {
+        self.notify("READY=1");
+    }
+
+    pub fn stopping(&self) {
+        self.notify("STOPPING=1");
+    }
+
+    pub fn status(&self, status: &str) {
+        self.notify(&format!("STATUS={}", status));
+    }
+
+    pub fn progress(&self, what: &str, done: usize, total: usize) {
+        self.status(&format!("syncing {} {}/{}", what, done, total));
+    }
+
+    fn notify(&self, state: &str) {
+        if let Option::Some(ref socket) = self.socket {
+            let sent = UnixDatagram::unbound().and_then(|datagram| {
+                datagram.send_to(state.as_bytes(), socket)?;
+                Result::Ok(())
+            });
+            if let Err(error) = sent {
+                warn!("Failed to notify systemd of {:?}: {}", state, error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn notifier_sends_states() {
+        let dir = env::temp_dir().join(format!("photooxide-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify.socket");
+        let _ = fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        let notifier = Notifier {
+            socket: Option::Some(path.clone()),
+        };
+        let mut buf = [0u8; 64];
+        notifier.ready();
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+        notifier.progress("albums", 120, 340);
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"STATUS=syncing albums 120/340");
+
+        // Without a socket nothing is sent, nor does it fail
+        Notifier {
+            socket: Option::None,
+        }
+        .ready();
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+use super::utils::{DEFAULT_DIR_MODE, DEFAULT_FILE_MODE};
+
+const DEFAULT_THUMBNAIL_SIZES: [u32; 3] = [256, 1024, 2048];
+const DEFAULT_CONTENT_CACHE_BYTES: usize = 512 * 1024 * 1024;
+const DEFAULT_PREFETCH_DEPTH: usize = 2;
+const DEFAULT_FIXED_TTL_SECONDS: u64 = 60 * 60;
+const DEFAULT_ALBUM_TTL_SECONDS: u64 = 2 * 60;
+const DEFAULT_MEDIA_TTL_SECONDS: u64 = 2 * 60;
+const DEFAULT_NEGATIVE_TTL_SECONDS: u64 = 30;
+
+// Read from the [filesystem] section of the config file, unset fields keep their default
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhotoFsConfig {
+    // Bounding box edge, in pixels, of every /thumbnails/<size> directory
+    pub thumbnail_sizes: Vec<u32>,
+    // Serve a NAME.xmp sidecar next to every media item
+    pub xmp_sidecars: bool,
+    // Rewrite the EXIF/XMP of served JPEGs from the Google Photos metadata
+    pub jpeg_metadata: bool,
+    // List album contents as symlinks into /media instead of a second copy of each file
+    pub album_symlinks: bool,
+    // Memory for downloaded content, content of open files is kept even beyond it
+    pub content_cache_bytes: usize,
+    // Files downloaded ahead when files of a directory are opened in order, 0 disables it
+    pub prefetch_depth: usize,
+    // Bandwidth cap for prefetching, 0 means no cap
+    pub prefetch_bytes_per_second: usize,
+    // How long the kernel may cache entries and attributes of the fixed directories
+    // (root, categories, thumbnails), which only change with the configuration
+    pub fixed_ttl_seconds: u64,
+    // Same for albums, shared albums and categories, whose sizes change on refresh
+    pub album_ttl_seconds: u64,
+    // Same for media items and their sidecars
+    pub media_ttl_seconds: u64,
+    // How long a name that was not found is remembered as missing, 0 disables it
+    pub negative_ttl_seconds: u64,
+    // Owner of all files, the mounting user and its primary group when not set
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    // Permission bits of regular files and directories, symlinks are always 0o777
+    pub file_mode: u16,
+    pub dir_mode: u16,
+    // Users allowed to use the mount besides the mounting user, checked by uid or by the
+    // primary and supplementary groups of the request. Only the mounting user when empty.
+    pub allowed_uids: Vec<u32>,
+    pub allowed_gids: Vec<u32>,
+    // Whether the token has write scopes, set from the mount mode rather than this section
+    #[serde(skip)]
+    pub writable: bool,
+}
+
+impl Default for PhotoFsConfig {
+    fn default() -> PhotoFsConfig {
+        PhotoFsConfig {
+            thumbnail_sizes: DEFAULT_THUMBNAIL_SIZES.to_vec(),
+            xmp_sidecars: false,
+            jpeg_metadata: false,
+            album_symlinks: false,
+            content_cache_bytes: DEFAULT_CONTENT_CACHE_BYTES,
+            prefetch_depth: DEFAULT_PREFETCH_DEPTH,
+            prefetch_bytes_per_second: 0,
+            fixed_ttl_seconds: DEFAULT_FIXED_TTL_SECONDS,
+            album_ttl_seconds: DEFAULT_ALBUM_TTL_SECONDS,
+            media_ttl_seconds: DEFAULT_MEDIA_TTL_SECONDS,
+            negative_ttl_seconds: DEFAULT_NEGATIVE_TTL_SECONDS,
+            uid: Option::None,
+            gid: Option::None,
+            file_mode: DEFAULT_FILE_MODE,
+            dir_mode: DEFAULT_DIR_MODE,
+            allowed_uids: Vec::new(),
+            allowed_gids: Vec::new(),
+            writable: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_thumbnail_sizes() {
+        assert_eq!(
+            PhotoFsConfig::default().thumbnail_sizes,
+            vec![256, 1024, 2048]
+        );
+        assert!(!PhotoFsConfig::default().xmp_sidecars);
+        assert!(!PhotoFsConfig::default().jpeg_metadata);
+        assert!(!PhotoFsConfig::default().album_symlinks);
+        assert_eq!(
+            PhotoFsConfig::default().content_cache_bytes,
+            512 * 1024 * 1024
+        );
+        assert_eq!(PhotoFsConfig::default().prefetch_depth, 2);
+        assert_eq!(PhotoFsConfig::default().prefetch_bytes_per_second, 0);
+        assert_eq!(PhotoFsConfig::default().fixed_ttl_seconds, 3600);
+        assert_eq!(PhotoFsConfig::default().album_ttl_seconds, 120);
+        assert_eq!(PhotoFsConfig::default().media_ttl_seconds, 120);
+        assert_eq!(PhotoFsConfig::default().negative_ttl_seconds, 30);
+        assert_eq!(PhotoFsConfig::default().uid, Option::None);
+        assert_eq!(PhotoFsConfig::default().gid, Option::None);
+        assert_eq!(PhotoFsConfig::default().file_mode, 0o644);
+        assert_eq!(PhotoFsConfig::default().dir_mode, 0o755);
+        assert!(PhotoFsConfig::default().allowed_uids.is_empty());
+        assert!(PhotoFsConfig::default().allowed_gids.is_empty());
+        assert!(!PhotoFsConfig::default().writable);
+    }
+}
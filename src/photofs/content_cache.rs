@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::domain::Inode;
+
+enum CacheEntry {
+    // Some thread is downloading the content, others wait for it
+    Loading,
+    Loaded { data: Arc<Vec<u8>>, last_used: u64 },
+}
+
+struct CacheState {
+    entries: HashMap<Inode, CacheEntry>,
+    used_bytes: usize,
+    clock: u64,
+}
+
+// Content of opened files, shared by all handles of the same inode. Unused content
+// is evicted least recently used first once more than max_bytes are held.
+pub struct ContentCache {
+    state: Mutex<CacheState>,
+    loaded: Condvar,
+    max_bytes: usize,
+}
+
+impl ContentCache {
+    pub fn new(max_bytes: usize) -> ContentCache {
+        ContentCache {
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                used_bytes: 0,
+                clock: 0,
+            }),
+            loaded: Condvar::new(),
+            max_bytes,
+        }
+    }
+
+    // Only one caller loads the content of an inode, concurrent callers wait for it.
+    // Failed loads are not cached, the next caller tries again.
+    pub fn get_or_load<F, E>(&self, inode: Inode, load: F) -> Result<Arc<Vec<u8>>, E>
+    where
+        F: FnOnce() -> Result<Vec<u8>, E>,
+    {
+        {
+            let mut state = self.state.lock().unwrap();
+            loop {
+                state.clock += 1;
+                let now = state.clock;
+                match state.entries.get_mut(&inode) {
+                    Some(CacheEntry::Loaded { data, last_used }) => {
+                        *last_used = now;
+                        return Result::Ok(data.clone());
+                    }
+                    Some(CacheEntry::Loading) => state = self.loaded.wait(state).unwrap(),
+                    None => {
+                        state.entries.insert(inode, CacheEntry::Loading);
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Not holding the lock, other inodes can be served while this one loads
+        let loading = LoadingGuard { cache: self, inode };
+        let result = load();
+        mem::forget(loading);
+
+        let mut state = self.state.lock().unwrap();
+        let result = match result {
+            Ok(data) => {
+                let data = Arc::new(data);
+                state.clock += 1;
+                state.used_bytes += data.len();
+                let last_used = state.clock;
+                state.entries.insert(
+                    inode,
+                    CacheEntry::Loaded {
+                        data: data.clone(),
+                        last_used,
+                    },
+                );
+                self.evict(&mut state);
+                Result::Ok(data)
+            }
+            Err(error) => {
+                state.entries.remove(&inode);
+                Result::Err(error)
+            }
+        };
+        self.loaded.notify_all();
+        result
+    }
+
+    // Evicts what is no longer used by any open handle, e.g. after a release
+    pub fn trim(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.evict(&mut state);
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.state.lock().unwrap().used_bytes
+    }
+
+    fn evict(&self, state: &mut CacheState) {
+        while state.used_bytes > self.max_bytes {
+            // Content still referenced by open handles can not be freed
+            let oldest_unused = state
+                .entries
+                .iter()
+                .filter_map(|(inode, entry)| match entry {
+                    CacheEntry::Loaded { data, last_used } if Arc::strong_count(data) == 1 => {
+                        Option::Some((*last_used, *inode))
+                    }
+                    _ => Option::None,
+                })
+                .min();
+            match oldest_unused {
+                Some((_, inode)) => {
+                    if let Some(CacheEntry::Loaded { data, .. }) = state.entries.remove(&inode) {
+                        debug!("Evicting content of inode={} size={}", inode, data.len());
+                        state.used_bytes -= data.len();
+                    }
+                }
+                None => {
+                    debug!(
+                        "Content cache above its limit, {} of {} bytes in use by open files",
+                        state.used_bytes, self.max_bytes
+                    );
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Stops waiters from waiting forever when loading panics
+struct LoadingGuard<'a> {
+    cache: &'a ContentCache,
+    inode: Inode,
+}
+
+impl<'a> Drop for LoadingGuard<'a> {
+    fn drop(&mut self) {
+        let mut state = match self.cache.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        state.entries.remove(&self.inode);
+        self.cache.loaded.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    fn load(data: &[u8]) -> impl FnOnce() -> Result<Vec<u8>, ()> {
+        let data = data.to_vec();
+        move || Result::Ok(data)
+    }
+
+    #[test]
+    fn content_cache_shares_content() {
+        let cache = ContentCache::new(1024);
+
+        let first = cache.get_or_load(101, load(b"ABC")).unwrap();
+        let second = cache
+            .get_or_load(101, || -> Result<Vec<u8>, ()> { panic!("Loaded twice") })
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.used_bytes(), 3);
+
+        let other = cache.get_or_load(102, load(b"DE")).unwrap();
+        assert_eq!(*other, b"DE".to_vec());
+        assert_eq!(cache.used_bytes(), 5);
+    }
+
+    #[test]
+    fn content_cache_does_not_cache_errors() {
+        let cache = ContentCache::new(1024);
+
+        assert_eq!(
+            cache.get_or_load(101, || Result::Err("failed")),
+            Err("failed")
+        );
+        assert_eq!(cache.used_bytes(), 0);
+        assert_eq!(
+            *cache.get_or_load(101, load(b"ABC")).unwrap(),
+            b"ABC".to_vec()
+        );
+    }
+
+    #[test]
+    fn content_cache_evicts_least_recently_used() {
+        let cache = ContentCache::new(4);
+
+        drop(cache.get_or_load(101, load(b"AB")));
+        drop(cache.get_or_load(102, load(b"CD")));
+        // Use 101 again, so 102 is now the least recently used
+        drop(cache.get_or_load(101, load(b"XX")));
+        drop(cache.get_or_load(103, load(b"EF")));
+        assert_eq!(cache.used_bytes(), 4);
+
+        assert_eq!(
+            *cache.get_or_load(101, load(b"XX")).unwrap(),
+            b"AB".to_vec()
+        );
+        assert_eq!(
+            *cache.get_or_load(102, load(b"XX")).unwrap(),
+            b"XX".to_vec()
+        );
+    }
+
+    #[test]
+    fn content_cache_keeps_open_content() {
+        let cache = ContentCache::new(2);
+
+        let open = cache.get_or_load(101, load(b"ABC")).unwrap();
+        drop(cache.get_or_load(102, load(b"DE")));
+        cache.trim();
+        // 101 is above the limit on its own but still open
+        assert_eq!(cache.used_bytes(), 3);
+        assert!(Arc::ptr_eq(
+            &open,
+            &cache.get_or_load(101, load(b"XX")).unwrap()
+        ));
+
+        drop(open);
+        cache.trim();
+        assert_eq!(cache.used_bytes(), 0);
+    }
+
+    #[test]
+    fn content_cache_load_panics() {
+        let cache = Arc::new(ContentCache::new(1024));
+        {
+            let cache = cache.clone();
+            let result = thread::spawn(move || {
+                cache.get_or_load(101, || -> Result<Vec<u8>, ()> { panic!("Load failed") })
+            })
+            .join();
+            assert!(result.is_err());
+        }
+        assert_eq!(
+            *cache.get_or_load(101, load(b"ABC")).unwrap(),
+            b"ABC".to_vec()
+        );
+    }
+
+    #[test]
+    fn content_cache_concurrent_loads_wait() {
+        let cache = Arc::new(ContentCache::new(1024));
+        let loads = Arc::new(AtomicUsize::new(0));
+        let (started_sender, started) = mpsc::channel();
+        let (finish, finish_receiver) = mpsc::channel::<()>();
+
+        let loader = {
+            let cache = cache.clone();
+            let loads = loads.clone();
+            thread::spawn(move || {
+                cache.get_or_load(101, move || -> Result<Vec<u8>, ()> {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    started_sender.send(()).unwrap();
+                    finish_receiver.recv().unwrap();
+                    Result::Ok(b"ABC".to_vec())
+                })
+            })
+        };
+        started.recv().unwrap();
+
+        let waiter = {
+            let cache = cache.clone();
+            let loads = loads.clone();
+            thread::spawn(move || {
+                cache.get_or_load(101, move || -> Result<Vec<u8>, ()> {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    Result::Ok(b"XX".to_vec())
+                })
+            })
+        };
+        thread::sleep(Duration::from_millis(50));
+        finish.send(()).unwrap();
+
+        let first = loader.join().unwrap().unwrap();
+        let second = waiter.join().unwrap().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+    }
+}
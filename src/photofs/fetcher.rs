@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use crate::db::{DbError, MetadataDb};
+use crate::domain::{Inode, PhotoDbMediaItem};
+use crate::photolib::{MediaVariant, RemotePhotoLibData};
+use crate::rust_filesystem::{FuseError, FuseResult};
+
+use super::content_cache::ContentCache;
+use super::jpeg::{is_jpeg, rewrite_jpeg};
+use super::size_map::SizeMap;
+
+// A few MB at most, an evicted size is estimated again until the view is fetched
+const MAX_KNOWN_SIZES: usize = 100_000;
+
+// Downloads views into the content cache. Shared between the FUSE threads and the
+// prefetcher, so kept apart from the rest of PhotoFs.
+pub struct ContentFetcher<X, Y>
+where
+    X: RemotePhotoLibData,
+    Y: MetadataDb,
+{
+    photo_lib: Arc<X>,
+    photo_db: Arc<Y>,
+    jpeg_metadata: bool,
+    content_cache: ContentCache,
+    // Sizes of the downloaded (and maybe rewritten) views, once fetched
+    known_sizes: SizeMap,
+}
+
+impl<X, Y> ContentFetcher<X, Y>
+where
+    X: RemotePhotoLibData,
+    Y: MetadataDb,
+{
+    pub fn new(
+        photo_lib: Arc<X>,
+        photo_db: Arc<Y>,
+        jpeg_metadata: bool,
+        content_cache_bytes: usize,
+    ) -> ContentFetcher<X, Y> {
+        ContentFetcher {
+            photo_lib,
+            photo_db,
+            jpeg_metadata,
+            content_cache: ContentCache::new(content_cache_bytes),
+            known_sizes: SizeMap::new(MAX_KNOWN_SIZES),
+        }
+    }
+
+    pub fn fetch(
+        &self,
+        inode: Inode,
+        media_item: &PhotoDbMediaItem,
+        variant: MediaVariant,
+    ) -> FuseResult<Arc<Vec<u8>>> {
+        let mut downloaded = 0;
+        self.fetch_counting(inode, media_item, variant, &mut downloaded)
+    }
+
+    // Returns the number of bytes downloaded, zero when the content was already cached
+    pub fn prefetch(
+        &self,
+        inode: Inode,
+        media_item: &PhotoDbMediaItem,
+        variant: MediaVariant,
+    ) -> usize {
+        let mut downloaded = 0;
+        if let Err(error) = self.fetch_counting(inode, media_item, variant, &mut downloaded) {
+            warn!("Failed to prefetch {:?}: {:?}", media_item.name, error);
+        }
+        downloaded
+    }
+
+    pub fn known_size(&self, inode: Inode) -> Option<usize> {
+        self.known_sizes.get(inode)
+    }
+
+    // Evicts what is no longer used by any open handle, e.g. after a release
+    pub fn trim(&self) {
+        self.content_cache.trim();
+    }
+
+    fn fetch_counting(
+        &self,
+        inode: Inode,
+        media_item: &PhotoDbMediaItem,
+        variant: MediaVariant,
+        downloaded: &mut usize,
+    ) -> FuseResult<Arc<Vec<u8>>> {
+        let data = self.content_cache.get_or_load(inode, || {
+            let data = self.download(media_item, variant)?;
+            *downloaded = data.len();
+            FuseResult::Ok(data)
+        })?;
+        debug!(
+            "Content cache holds {} bytes",
+            self.content_cache.used_bytes()
+        );
+        self.known_sizes.insert(inode, data.len());
+        Result::Ok(data)
+    }
+
+    fn download(
+        &self,
+        media_item: &PhotoDbMediaItem,
+        variant: MediaVariant,
+    ) -> FuseResult<Vec<u8>> {
+        match self
+            .photo_lib
+            .media_item(media_item.google_id(), media_item.is_video(), variant)
+        {
+            Err(error) => {
+                error!("Failed to fetch media item from remote: {:?}", error);
+                Result::Err(FuseError::FunctionNotImplemented)
+            }
+            Ok(data) => Result::Ok(self.with_jpeg_metadata(media_item, data)?),
+        }
+    }
+
+    // Serves the downloaded data as is unless JPEG metadata rewriting is enabled
+    fn with_jpeg_metadata(
+        &self,
+        media_item: &PhotoDbMediaItem,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, DbError> {
+        if !self.jpeg_metadata || !is_jpeg(&data) {
+            return Result::Ok(data);
+        }
+        let id = media_item.google_id();
+        let metadata = self.photo_db.media_item_metadata(id)?.unwrap_or_default();
+        let albums = self.photo_db.albums_for_media_item(id)?;
+        match rewrite_jpeg(&data, &metadata, &albums, self.photo_db.is_favourite(id)?) {
+            Option::Some(rewritten) => Result::Ok(rewritten),
+            Option::None => {
+                warn!(
+                    "Failed to rewrite the metadata of {:?}, serving it unchanged",
+                    media_item.name
+                );
+                Result::Ok(data)
+            }
+        }
+    }
+}
@@ -0,0 +1,560 @@
+use crate::domain::{MediaItemMetadata, PhotoDbAlbum};
+
+use super::xmp::xmp_meta;
+
+const MARKER_PREFIX: u8 = 0xFF;
+const MARKER_SOI: u8 = 0xD8;
+const MARKER_SOS: u8 = 0xDA;
+const MARKER_EOI: u8 = 0xD9;
+const MARKER_APP0: u8 = 0xE0;
+const MARKER_APP1: u8 = 0xE1;
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+// The length field of a segment counts itself
+const MAX_SEGMENT_PAYLOAD: usize = 0xFFFF - 2;
+
+const TAG_IMAGE_DESCRIPTION: u16 = 0x010E;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_XP_KEYWORDS: u16 = 0x9C9E;
+
+const TYPE_BYTE: u16 = 1;
+const TYPE_ASCII: u16 = 2;
+const TYPE_LONG: u16 = 4;
+
+// Little endian TIFF header with an empty IFD0, used when a JPEG has no EXIF
+const EMPTY_TIFF: &[u8] = &[
+    b'I', b'I', 42, 0, 8, 0, 0, 0, // header, IFD0 at 8
+    0, 0, // no entries
+    0, 0, 0, 0, // no next IFD
+];
+
+pub fn is_jpeg(data: &[u8]) -> bool {
+    data.len() > 2 && data[0] == MARKER_PREFIX && data[1] == MARKER_SOI
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    fn u16(self, data: &[u8], at: usize) -> Option<u16> {
+        let bytes = data.get(at..at + 2)?;
+        Option::Some(match self {
+            ByteOrder::Little => u16::from(bytes[0]) | u16::from(bytes[1]) << 8,
+            ByteOrder::Big => u16::from(bytes[0]) << 8 | u16::from(bytes[1]),
+        })
+    }
+
+    fn u32(self, data: &[u8], at: usize) -> Option<u32> {
+        let high = self.u16(data, at)?;
+        let low = self.u16(data, at + 2)?;
+        Option::Some(match self {
+            ByteOrder::Little => u32::from(low) << 16 | u32::from(high),
+            ByteOrder::Big => u32::from(high) << 16 | u32::from(low),
+        })
+    }
+
+    fn u16_bytes(self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::Little => [value as u8, (value >> 8) as u8],
+            ByteOrder::Big => [(value >> 8) as u8, value as u8],
+        }
+    }
+
+    fn u32_bytes(self, value: u32) -> [u8; 4] {
+        let high = self.u16_bytes((value >> 16) as u16);
+        let low = self.u16_bytes(value as u16);
+        match self {
+            ByteOrder::Little => [low[0], low[1], high[0], high[1]],
+            ByteOrder::Big => [high[0], high[1], low[0], low[1]],
+        }
+    }
+}
+
+// The value is kept as raw bytes (inline value or offset), so entries that are
+// copied into a new IFD still point at their data in the original TIFF.
+#[derive(Clone, Debug)]
+struct IfdEntry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    value: [u8; 4],
+}
+
+fn read_ifd(tiff: &[u8], order: ByteOrder, offset: usize) -> Option<(Vec<IfdEntry>, u32)> {
+    let count = order.u16(tiff, offset)? as usize;
+    let mut entries = Vec::with_capacity(count);
+    for index in 0..count {
+        let at = offset + 2 + index * 12;
+        let value = tiff.get(at + 8..at + 12)?;
+        entries.push(IfdEntry {
+            tag: order.u16(tiff, at)?,
+            field_type: order.u16(tiff, at + 2)?,
+            count: order.u32(tiff, at + 4)?,
+            value: [value[0], value[1], value[2], value[3]],
+        });
+    }
+    let next = order.u32(tiff, offset + 2 + count * 12)?;
+    Option::Some((entries, next))
+}
+
+// Words in TIFF start at even offsets
+fn align(tiff: &mut Vec<u8>) {
+    if tiff.len() % 2 == 1 {
+        tiff.push(0);
+    }
+}
+
+// Appends the IFD to the end of the TIFF and returns its offset
+fn write_ifd(tiff: &mut Vec<u8>, order: ByteOrder, entries: &mut [IfdEntry], next: u32) -> u32 {
+    entries.sort_by_key(|entry| entry.tag);
+    align(tiff);
+    let offset = tiff.len() as u32;
+    tiff.extend_from_slice(&order.u16_bytes(entries.len() as u16));
+    for entry in entries.iter() {
+        tiff.extend_from_slice(&order.u16_bytes(entry.tag));
+        tiff.extend_from_slice(&order.u16_bytes(entry.field_type));
+        tiff.extend_from_slice(&order.u32_bytes(entry.count));
+        tiff.extend_from_slice(&entry.value);
+    }
+    tiff.extend_from_slice(&order.u32_bytes(next));
+    offset
+}
+
+// Values that do not fit into the entry are appended to the end of the TIFF
+fn set_entry(
+    tiff: &mut Vec<u8>,
+    order: ByteOrder,
+    entries: &mut Vec<IfdEntry>,
+    tag: u16,
+    field_type: u16,
+    data: &[u8],
+) {
+    let mut value = [0u8; 4];
+    if data.len() <= 4 {
+        value[..data.len()].copy_from_slice(data);
+    } else {
+        align(tiff);
+        value = order.u32_bytes(tiff.len() as u32);
+        tiff.extend_from_slice(data);
+    }
+    entries.retain(|entry| entry.tag != tag);
+    entries.push(IfdEntry {
+        tag,
+        field_type,
+        count: data.len() as u32,
+        value,
+    });
+}
+
+fn ascii(text: &str) -> Vec<u8> {
+    let mut data = text.as_bytes().to_vec();
+    data.push(0);
+    data
+}
+
+// XPKeywords are UCS-2 little endian, whatever the byte order of the TIFF
+fn xp_keywords(keywords: &[&str]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for unit in keywords.join(";").encode_utf16() {
+        data.push(unit as u8);
+        data.push((unit >> 8) as u8);
+    }
+    data.extend_from_slice(&[0, 0]);
+    data
+}
+
+// Never moves existing data: the edited IFD0 (and Exif IFD) are appended as
+// new IFDs and the old ones are simply no longer referenced.
+fn rewrite_tiff(tiff: &[u8], metadata: &MediaItemMetadata, keywords: &[&str]) -> Option<Vec<u8>> {
+    let order = match tiff.get(0..2)? {
+        b"II" => ByteOrder::Little,
+        b"MM" => ByteOrder::Big,
+        _ => return Option::None,
+    };
+    if order.u16(tiff, 2)? != 42 {
+        return Option::None;
+    }
+    let (mut ifd0, next) = read_ifd(tiff, order, order.u32(tiff, 4)? as usize)?;
+    let mut tiff = tiff.to_vec();
+
+    if let Some(description) = &metadata.description {
+        let data = ascii(description);
+        set_entry(
+            &mut tiff,
+            order,
+            &mut ifd0,
+            TAG_IMAGE_DESCRIPTION,
+            TYPE_ASCII,
+            &data,
+        );
+    }
+    if !keywords.is_empty() {
+        let data = xp_keywords(keywords);
+        set_entry(
+            &mut tiff,
+            order,
+            &mut ifd0,
+            TAG_XP_KEYWORDS,
+            TYPE_BYTE,
+            &data,
+        );
+    }
+
+    if let Some(creation_time) = &metadata.creation_time {
+        let exif_ifd_entry = ifd0.iter().find(|entry| entry.tag == TAG_EXIF_IFD);
+        let mut exif_ifd = match exif_ifd_entry {
+            Some(entry) => read_ifd(&tiff, order, order.u32(&entry.value, 0)? as usize)?.0,
+            None => Vec::new(),
+        };
+        if exif_ifd
+            .iter()
+            .all(|entry| entry.tag != TAG_DATE_TIME_ORIGINAL)
+        {
+            // EXIF has no time zone, the creation time is in UTC
+            let data = ascii(&creation_time.format("%Y:%m:%d %H:%M:%S").to_string());
+            set_entry(
+                &mut tiff,
+                order,
+                &mut exif_ifd,
+                TAG_DATE_TIME_ORIGINAL,
+                TYPE_ASCII,
+                &data,
+            );
+            let exif_ifd_offset = write_ifd(&mut tiff, order, &mut exif_ifd, 0);
+            let data = order.u32_bytes(exif_ifd_offset);
+            set_entry(&mut tiff, order, &mut ifd0, TAG_EXIF_IFD, TYPE_LONG, &data);
+        }
+    }
+
+    let ifd0_offset = write_ifd(&mut tiff, order, &mut ifd0, next);
+    tiff[4..8].copy_from_slice(&order.u32_bytes(ifd0_offset));
+    Option::Some(tiff)
+}
+
+fn segment(marker: u8, header: &[u8], payload: &[u8]) -> Option<Vec<u8>> {
+    let length = header.len() + payload.len();
+    if length > MAX_SEGMENT_PAYLOAD {
+        return Option::None;
+    }
+    let mut segment = vec![
+        MARKER_PREFIX,
+        marker,
+        ((length + 2) >> 8) as u8,
+        (length + 2) as u8,
+    ];
+    segment.extend_from_slice(header);
+    segment.extend_from_slice(payload);
+    Option::Some(segment)
+}
+
+// Writes the description, creation time (unless the camera set one) and album
+// names into the EXIF of a JPEG. An XMP packet is only added when the JPEG has
+// none, merging into an existing one could lose data (e.g. panorama details).
+// None if the data is not a JPEG that can be rewritten.
+pub fn rewrite_jpeg(
+    data: &[u8],
+    metadata: &MediaItemMetadata,
+    albums: &[PhotoDbAlbum],
+    is_favourite: bool,
+) -> Option<Vec<u8>> {
+    if !is_jpeg(data) {
+        return Option::None;
+    }
+
+    // (marker, start, end) of every segment before the image data
+    let mut segments: Vec<(u8, usize, usize)> = Vec::new();
+    let mut position = 2;
+    loop {
+        if *data.get(position)? != MARKER_PREFIX {
+            return Option::None;
+        }
+        let marker = *data.get(position + 1)?;
+        if marker == MARKER_PREFIX {
+            // Fill byte
+            position += 1;
+            continue;
+        }
+        if marker == MARKER_SOS || marker == MARKER_EOI {
+            break;
+        }
+        let length =
+            (usize::from(*data.get(position + 2)?) << 8) | usize::from(*data.get(position + 3)?);
+        let end = position + 2 + length;
+        if length < 2 || end > data.len() {
+            return Option::None;
+        }
+        segments.push((marker, position, end));
+        position = end;
+    }
+    let image_data = &data[position..];
+
+    let payload = |start: usize, end: usize| &data[start + 4..end];
+    let exif_segment = segments.iter().position(|(marker, start, end)| {
+        *marker == MARKER_APP1 && payload(*start, *end).starts_with(EXIF_HEADER)
+    });
+    let has_xmp = segments.iter().any(|(marker, start, end)| {
+        *marker == MARKER_APP1 && payload(*start, *end).starts_with(XMP_HEADER)
+    });
+
+    let keywords: Vec<&str> = albums.iter().map(|album| album.name.as_str()).collect();
+    let tiff = match exif_segment {
+        Some(index) => {
+            let (_, start, end) = segments[index];
+            &payload(start, end)[EXIF_HEADER.len()..]
+        }
+        None => EMPTY_TIFF,
+    };
+    let exif = segment(
+        MARKER_APP1,
+        EXIF_HEADER,
+        &rewrite_tiff(tiff, metadata, &keywords)?,
+    )?;
+    let xmp = if has_xmp {
+        Vec::new()
+    } else {
+        let packet = format!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n{}<?xpacket end=\"w\"?>",
+            xmp_meta(metadata, albums, is_favourite)
+        );
+        segment(MARKER_APP1, XMP_HEADER, packet.as_bytes())?
+    };
+
+    // New segments go where the EXIF was, or after the JFIF header
+    let insert_at = match exif_segment {
+        Some(index) => index,
+        None => match segments.first() {
+            Some((MARKER_APP0, _, _)) => 1,
+            _ => 0,
+        },
+    };
+
+    let mut rewritten = Vec::with_capacity(data.len() + exif.len() + xmp.len());
+    rewritten.extend_from_slice(&data[0..2]);
+    for (index, (_, start, end)) in segments.iter().enumerate() {
+        if index == insert_at {
+            rewritten.extend_from_slice(&exif);
+            rewritten.extend_from_slice(&xmp);
+        }
+        if Option::Some(index) != exif_segment {
+            rewritten.extend_from_slice(&data[*start..*end]);
+        }
+    }
+    if insert_at == segments.len() {
+        rewritten.extend_from_slice(&exif);
+        rewritten.extend_from_slice(&xmp);
+    }
+    rewritten.extend_from_slice(image_data);
+    Option::Some(rewritten)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::domain::MediaTypes;
+
+    const JFIF: &[u8] = &[
+        0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00,
+        0x01, 0x00, 0x00,
+    ];
+    const IMAGE_DATA: &[u8] = &[0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9];
+
+    fn jpeg(segments: &[&[u8]]) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+        for segment in segments {
+            data.extend_from_slice(segment);
+        }
+        data.extend_from_slice(IMAGE_DATA);
+        data
+    }
+
+    // Returns the APP1 payloads of a JPEG
+    fn app1_payloads(data: &[u8]) -> Vec<Vec<u8>> {
+        let mut payloads = Vec::new();
+        let mut position = 2;
+        while data[position + 1] != MARKER_SOS {
+            let length = (usize::from(data[position + 2]) << 8) | usize::from(data[position + 3]);
+            if data[position + 1] == MARKER_APP1 {
+                payloads.push(data[position + 4..position + 2 + length].to_vec());
+            }
+            position += 2 + length;
+        }
+        payloads
+    }
+
+    fn ascii_value(
+        tiff: &[u8],
+        order: ByteOrder,
+        entries: &[IfdEntry],
+        tag: u16,
+    ) -> Option<String> {
+        let entry = entries.iter().find(|entry| entry.tag == tag)?;
+        let offset = order.u32(&entry.value, 0)? as usize;
+        let bytes = &tiff[offset..offset + entry.count as usize - 1];
+        Option::Some(String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    fn metadata() -> MediaItemMetadata {
+        MediaItemMetadata {
+            description: Option::Some(String::from("At the beach")),
+            creation_time: Option::Some(Utc::timestamp(&Utc, 1_546_300_800, 0)),
+            ..MediaItemMetadata::default()
+        }
+    }
+
+    fn albums() -> Vec<PhotoDbAlbum> {
+        vec![PhotoDbAlbum::new(
+            String::from("GoogleId1"),
+            String::from("Holiday"),
+            MediaTypes::Album,
+            Utc::now(),
+            101,
+        )]
+    }
+
+    #[test]
+    fn byte_order() {
+        for order in &[ByteOrder::Little, ByteOrder::Big] {
+            assert_eq!(order.u16(&order.u16_bytes(0x1234), 0), Option::Some(0x1234));
+            assert_eq!(
+                order.u32(&order.u32_bytes(0x1234_5678), 0),
+                Option::Some(0x1234_5678)
+            );
+        }
+        assert_eq!(
+            ByteOrder::Big.u32_bytes(0x1234_5678),
+            [0x12, 0x34, 0x56, 0x78]
+        );
+        assert_eq!(
+            ByteOrder::Little.u32_bytes(0x1234_5678),
+            [0x78, 0x56, 0x34, 0x12]
+        );
+    }
+
+    #[test]
+    fn rewrite_not_a_jpeg() {
+        assert!(rewrite_jpeg(b"GIF89a", &metadata(), &albums(), false).is_none());
+        assert!(rewrite_jpeg(&[0xFF, 0xD8, 0x00], &metadata(), &albums(), false).is_none());
+    }
+
+    #[test]
+    fn rewrite_truncated_jpeg() {
+        // Cut right after the first byte of a segment length
+        assert!(rewrite_jpeg(
+            &[0xFF, 0xD8, 0xFF, MARKER_APP1, 0x00],
+            &metadata(),
+            &albums(),
+            false
+        )
+        .is_none());
+
+        // Nor does any other cut panic
+        let data = jpeg(&[JFIF]);
+        for len in 0..data.len() {
+            rewrite_jpeg(&data[..len], &metadata(), &albums(), false);
+        }
+    }
+
+    #[test]
+    fn rewrite_jpeg_without_exif() {
+        let data = jpeg(&[JFIF]);
+        let rewritten = rewrite_jpeg(&data, &metadata(), &albums(), true).unwrap();
+
+        // JFIF stays the first segment and the image data is untouched
+        assert_eq!(&rewritten[2..2 + JFIF.len()], JFIF);
+        assert!(rewritten.ends_with(IMAGE_DATA));
+
+        let payloads = app1_payloads(&rewritten);
+        assert_eq!(payloads.len(), 2);
+        assert!(payloads[0].starts_with(EXIF_HEADER));
+        assert!(payloads[1].starts_with(XMP_HEADER));
+        let xmp = String::from_utf8(payloads[1][XMP_HEADER.len()..].to_vec()).unwrap();
+        assert!(xmp.contains("<rdf:li>Holiday</rdf:li>"));
+        assert!(xmp.contains("xmp:Rating=\"5\""));
+
+        let tiff = &payloads[0][EXIF_HEADER.len()..];
+        let order = ByteOrder::Little;
+        let (ifd0, _) = read_ifd(tiff, order, order.u32(tiff, 4).unwrap() as usize).unwrap();
+        assert_eq!(
+            ascii_value(tiff, order, &ifd0, TAG_IMAGE_DESCRIPTION),
+            Option::Some(String::from("At the beach"))
+        );
+        let exif_ifd_entry = ifd0.iter().find(|entry| entry.tag == TAG_EXIF_IFD).unwrap();
+        let (exif_ifd, _) = read_ifd(
+            tiff,
+            order,
+            order.u32(&exif_ifd_entry.value, 0).unwrap() as usize,
+        )
+        .unwrap();
+        assert_eq!(
+            ascii_value(tiff, order, &exif_ifd, TAG_DATE_TIME_ORIGINAL),
+            Option::Some(String::from("2019:01:01 00:00:00"))
+        );
+        let keywords = ifd0
+            .iter()
+            .find(|entry| entry.tag == TAG_XP_KEYWORDS)
+            .unwrap();
+        assert_eq!(keywords.count as usize, xp_keywords(&["Holiday"]).len());
+    }
+
+    #[test]
+    fn rewrite_jpeg_with_exif() {
+        // Big endian EXIF with a description and a camera set DateTimeOriginal
+        let mut tiff: Vec<u8> = vec![b'M', b'M', 0, 42, 0, 0, 0, 8];
+        tiff.extend_from_slice(&[0, 2]); // 2 entries
+        tiff.extend_from_slice(&[0x01, 0x0E, 0, 2, 0, 0, 0, 4, b'O', b'l', b'd', 0]);
+        tiff.extend_from_slice(&[0x87, 0x69, 0, 4, 0, 0, 0, 1, 0, 0, 0, 38]);
+        tiff.extend_from_slice(&[0, 0, 0, 0]); // no next IFD
+        tiff.extend_from_slice(&[0, 1]); // Exif IFD at 38, 1 entry
+        tiff.extend_from_slice(&[0x90, 0x03, 0, 2, 0, 0, 0, 20, 0, 0, 0, 56]);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        tiff.extend_from_slice(b"2010:10:10 10:10:10\0");
+        let exif = segment(MARKER_APP1, EXIF_HEADER, &tiff).unwrap();
+
+        let data = jpeg(&[JFIF, &exif]);
+        let rewritten = rewrite_jpeg(&data, &metadata(), &[], false).unwrap();
+        assert!(rewritten.ends_with(IMAGE_DATA));
+
+        let payloads = app1_payloads(&rewritten);
+        assert_eq!(payloads.len(), 2);
+        let tiff = &payloads[0][EXIF_HEADER.len()..];
+        let order = ByteOrder::Big;
+        let (ifd0, _) = read_ifd(tiff, order, order.u32(tiff, 4).unwrap() as usize).unwrap();
+        assert_eq!(
+            ascii_value(tiff, order, &ifd0, TAG_IMAGE_DESCRIPTION),
+            Option::Some(String::from("At the beach"))
+        );
+        assert!(ifd0.iter().all(|entry| entry.tag != TAG_XP_KEYWORDS));
+
+        // The camera time wins
+        let exif_ifd_entry = ifd0.iter().find(|entry| entry.tag == TAG_EXIF_IFD).unwrap();
+        assert_eq!(order.u32(&exif_ifd_entry.value, 0), Option::Some(38));
+        let (exif_ifd, _) = read_ifd(tiff, order, 38).unwrap();
+        assert_eq!(
+            ascii_value(tiff, order, &exif_ifd, TAG_DATE_TIME_ORIGINAL),
+            Option::Some(String::from("2010:10:10 10:10:10"))
+        );
+
+        // Rewriting a rewritten file works too
+        assert!(rewrite_jpeg(&rewritten, &metadata(), &albums(), false).is_some());
+    }
+
+    #[test]
+    fn rewrite_jpeg_keeps_existing_xmp() {
+        let xmp = segment(MARKER_APP1, XMP_HEADER, b"<x:xmpmeta/>").unwrap();
+        let data = jpeg(&[&xmp]);
+        let rewritten = rewrite_jpeg(&data, &metadata(), &albums(), false).unwrap();
+
+        let payloads = app1_payloads(&rewritten);
+        assert_eq!(payloads.len(), 2);
+        assert!(payloads[0].starts_with(EXIF_HEADER));
+        assert_eq!(payloads[1], xmp[4..].to_vec());
+    }
+}
@@ -1,86 +1,292 @@
 use std::collections::HashSet;
 use std::convert::From;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::sync::{Arc, Mutex};
 
-use fuse::{self, FileType};
+use fuse::{self, FileAttr, FileType};
+use libc;
 use time::Timespec;
 
 use crate::rust_filesystem::{
     FileAttrResponse, FileEntryResponse, FuseError, FuseResult, OpenResponse, ReadDirEntry,
-    ReadDirResponse, ReadResponse,
+    ReadDirResponse, ReadResponse, ReadlinkResponse, XattrResponse,
 };
 
-use crate::db::{Filter, PhotoDbRo};
-use crate::domain::{Inode, MediaTypes, PhotoDbAlbum};
+use crate::db::{CategoryDb, DbError, Filter, MetadataDb, PhotoDbRo, SharedAlbumDb};
+use crate::domain::{
+    ContentCategory, Inode, MediaTypes, PhotoDbAlbum, PhotoDbMediaItem, SharedAlbumInfo,
+};
 use crate::photolib::*;
 use crate::rust_filesystem::{RustFilesystem, UniqRequest};
 
+mod config;
+pub use self::config::PhotoFsConfig;
+
+mod content_cache;
+
 mod error;
 pub use self::error::PhotoFsError;
 
+mod fetcher;
+use self::fetcher::ContentFetcher;
+
+mod jpeg;
+
+mod prefetch;
+use self::prefetch::Prefetcher;
+
+mod sidecar;
+use self::sidecar::{
+    json_sidecar, json_sidecar_name, media_item_name_for_json_sidecar,
+    media_item_name_for_xmp_sidecar, xmp_sidecar_name,
+};
+
+mod size_map;
+use self::size_map::SizeMap;
+
 mod utils;
-use self::utils::{make_atr, OpenFileHandles};
+use self::utils::{make_atr, supplementary_groups, OpenFileHandles};
+
+mod view;
+use self::view::{split_inode, view_inode, InodeView};
+
+mod xmp;
+use self::xmp::xmp_sidecar;
 
 const FIXED_INODE_ROOT: u64 = fuse::FUSE_ROOT_ID;
 const FIXED_INODE_ALBUMS: u64 = 2;
 const FIXED_INODE_MEDIA: u64 = 3;
 const FIXED_INODE_HELLO_WORLD: u64 = 4;
-
-const TTL: Timespec = Timespec { sec: 120, nsec: 0 }; // 2 minutes
+const FIXED_INODE_CATEGORIES: u64 = 5;
+const FIXED_INODE_SHARED_ALBUMS: u64 = 6;
+const FIXED_INODE_THUMBNAILS: u64 = 7;
+// One fixed inode per known content category, starting at this base
+const FIXED_INODE_CATEGORY_BASE: u64 = 50;
+// One fixed inode per configured thumbnail size, starting at this base
+const FIXED_INODE_THUMBNAIL_SIZE_BASE: u64 = 80;
+const MAX_THUMBNAIL_SIZES: usize = 20;
+// albums, media, categories, shared-albums and thumbnails
+const ROOT_SUBDIRECTORIES: usize = 5;
 
 const HELLO_TXT_CONTENT: &[u8] = b"Hello World!\n";
 
 const GENERATION: u64 = 0;
 
 const DEFAULT_MEDIA_ITEM_SIZE: usize = 1024;
+// Files whose reported size was evicted are opened with direct IO, as when never reported
+const MAX_REPORTED_SIZES: usize = 100_000;
+
+// Media items per readdir, more than the kernel takes in one go
+const READDIR_PAGE_SIZE: usize = 256;
+// Offsets of media directory entries are made of the DB inode of the media item and the
+// position of the entry among the ones of the item. Unlike indexes these stay valid when
+// the background update changes the directory while it is read.
+const MEDIA_ENTRIES_PER_ITEM: u64 = 4;
+const FIRST_DB_INODE: u64 = 101;
+
+const XATTR_SHARED_IS_OWNED: &str = "user.photooxide.shared.is_owned";
+const XATTR_SHARED_IS_JOINED: &str = "user.photooxide.shared.is_joined";
+const XATTR_SHARED_IS_COLLABORATIVE: &str = "user.photooxide.shared.is_collaborative";
+
+fn category_inode(category: ContentCategory) -> Inode {
+    let index = ContentCategory::all()
+        .iter()
+        .position(|known| *known == category)
+        .unwrap();
+    FIXED_INODE_CATEGORY_BASE + index as u64
+}
+
+fn category_for_inode(inode: Inode) -> Option<ContentCategory> {
+    if inode < FIXED_INODE_CATEGORY_BASE {
+        return Option::None;
+    }
+    ContentCategory::all()
+        .get((inode - FIXED_INODE_CATEGORY_BASE) as usize)
+        .cloned()
+}
 
+fn ttl(seconds: u64) -> Timespec {
+    Timespec {
+        sec: seconds as i64,
+        nsec: 0,
+    }
+}
+
+// Album directories are two levels below the root, next to /media
+fn link_target(media_item: &PhotoDbMediaItem, view: InodeView) -> Option<String> {
+    let name = match view {
+        InodeView::OriginalLink => media_item.name.clone(),
+        InodeView::JsonSidecarLink => json_sidecar_name(&media_item.name),
+        InodeView::XmpSidecarLink => xmp_sidecar_name(&media_item.name),
+        _ => return Option::None,
+    };
+    Option::Some(format!("../../media/{}", name))
+}
+
+fn media_entry_offset(inode: Inode, position: usize) -> i64 {
+    (inode * MEDIA_ENTRIES_PER_ITEM + position as u64) as i64
+}
+
+// None for offsets before the first media item, i.e. of "." and ".."
+fn split_media_entry_offset(offset: i64) -> Option<(Inode, usize)> {
+    let offset = offset as u64;
+    if offset < FIRST_DB_INODE * MEDIA_ENTRIES_PER_ITEM {
+        return Option::None;
+    }
+    Option::Some((
+        offset / MEDIA_ENTRIES_PER_ITEM,
+        (offset % MEDIA_ENTRIES_PER_ITEM) as usize,
+    ))
+}
+
+// The data is shared with the content cache and other handles of the same inode
 #[derive(Debug, new)]
 struct ReadFhEntry {
     inode: Inode,
-    data: Vec<u8>,
+    data: Arc<Vec<u8>>,
+}
+
+// Media directories can hold the whole library, so are read from the DB page by page
+#[derive(Clone, Debug)]
+enum MediaListing {
+    All,
+    Album(String),
+    Category(ContentCategory),
+}
+
+impl MediaListing {
+    fn filter(&self) -> Filter<'_> {
+        match self {
+            MediaListing::All => Filter::NoFilter,
+            MediaListing::Album(album_id) => Filter::ByAlbum(album_id),
+            MediaListing::Category(category) => Filter::ByCategory(*category),
+        }
+    }
 }
 
 #[derive(Debug, new)]
 struct ReadDirFhEntry {
     inode: Inode,
+    // Listed on opendir, only "." and ".." for media directories
     entries: Vec<(u64, fuse::FileType, String)>,
+    media: Option<(MediaListing, InodeView)>,
 }
 
 pub struct PhotoFs<X, Y>
 where
     X: RemotePhotoLibData,
-    Y: PhotoDbRo,
+    Y: PhotoDbRo + CategoryDb + SharedAlbumDb + MetadataDb,
 {
-    photo_lib: Arc<Mutex<X>>,
     photo_db: Arc<Y>,
-    open_files: OpenFileHandles<ReadFhEntry>,
-    open_dirs: OpenFileHandles<ReadDirFhEntry>,
+    config: PhotoFsConfig,
+    fetcher: Arc<ContentFetcher<X, Y>>,
+    prefetcher: Prefetcher,
+    // Sizes last handed to the kernel by lookup or getattr, it never reads past these
+    reported_sizes: SizeMap,
+    open_files: Mutex<OpenFileHandles<ReadFhEntry>>,
+    open_dirs: Mutex<OpenFileHandles<ReadDirFhEntry>>,
 }
 
 impl<X, Y> PhotoFs<X, Y>
 where
     X: RemotePhotoLibData,
-    Y: PhotoDbRo,
+    Y: PhotoDbRo + CategoryDb + SharedAlbumDb + MetadataDb,
 {
-    pub fn new(photo_lib: Arc<Mutex<X>>, photo_db: Arc<Y>) -> PhotoFs<X, Y> {
+    pub fn new(photo_lib: Arc<X>, photo_db: Arc<Y>) -> PhotoFs<X, Y> {
+        PhotoFs::with_config(photo_lib, photo_db, PhotoFsConfig::default())
+    }
+
+    pub fn with_config(
+        photo_lib: Arc<X>,
+        photo_db: Arc<Y>,
+        mut config: PhotoFsConfig,
+    ) -> PhotoFs<X, Y> {
+        config.thumbnail_sizes.retain(|size| *size > 0);
+        config.thumbnail_sizes.sort();
+        config.thumbnail_sizes.dedup();
+        if config.thumbnail_sizes.len() > MAX_THUMBNAIL_SIZES {
+            warn!(
+                "Only the first {} thumbnail sizes are used, ignoring {:?}",
+                MAX_THUMBNAIL_SIZES,
+                &config.thumbnail_sizes[MAX_THUMBNAIL_SIZES..]
+            );
+            config.thumbnail_sizes.truncate(MAX_THUMBNAIL_SIZES);
+        }
+
         PhotoFs {
-            photo_lib,
+            fetcher: Arc::new(ContentFetcher::new(
+                photo_lib,
+                photo_db.clone(),
+                config.jpeg_metadata,
+                config.content_cache_bytes,
+            )),
+            prefetcher: Prefetcher::new(config.prefetch_depth, config.prefetch_bytes_per_second),
             photo_db,
-            open_files: OpenFileHandles::new(),
-            open_dirs: OpenFileHandles::new(),
+            config,
+            reported_sizes: SizeMap::new(MAX_REPORTED_SIZES),
+            open_files: Mutex::new(OpenFileHandles::new()),
+            open_dirs: Mutex::new(OpenFileHandles::new()),
         }
     }
 
-    fn lookup_root(
-        &mut self,
-        _req: &dyn UniqRequest,
-        name: &OsStr,
-    ) -> FuseResult<FileEntryResponse<'_>> {
+    fn thumbnail_size_inode(&self, index: usize) -> Inode {
+        FIXED_INODE_THUMBNAIL_SIZE_BASE + index as u64
+    }
+
+    fn thumbnail_index_for_inode(&self, inode: Inode) -> Option<usize> {
+        if inode < FIXED_INODE_THUMBNAIL_SIZE_BASE {
+            return Option::None;
+        }
+        let index = (inode - FIXED_INODE_THUMBNAIL_SIZE_BASE) as usize;
+        if index < self.config.thumbnail_sizes.len() {
+            Option::Some(index)
+        } else {
+            Option::None
+        }
+    }
+
+    fn is_known_view(&self, view: InodeView) -> bool {
+        match view {
+            InodeView::Original | InodeView::JsonSidecar => true,
+            InodeView::XmpSidecar => self.config.xmp_sidecars,
+            InodeView::Thumbnail(index) => index < self.config.thumbnail_sizes.len(),
+            InodeView::OriginalLink | InodeView::JsonSidecarLink | InodeView::XmpSidecarLink => {
+                self.config.album_symlinks && self.is_known_view(view.link_target().unwrap())
+            }
+        }
+    }
+
+    // The view of the media items listed in album directories
+    fn album_view(&self) -> InodeView {
+        if self.config.album_symlinks {
+            InodeView::OriginalLink
+        } else {
+            InodeView::Original
+        }
+    }
+
+    // None if the view is not downloaded from the remote
+    fn media_variant(&self, view: InodeView) -> Option<MediaVariant> {
+        match view {
+            InodeView::Original => Option::Some(MediaVariant::Original),
+            InodeView::Thumbnail(index) => {
+                self.config
+                    .thumbnail_sizes
+                    .get(index)
+                    .map(|size| MediaVariant::Resized {
+                        width: *size,
+                        height: *size,
+                    })
+            }
+            _ => Option::None,
+        }
+    }
+
+    fn lookup_root(&self, _req: &dyn UniqRequest, name: &OsStr) -> FuseResult<FileEntryResponse> {
         match name.to_str().unwrap() {
             "hello.txt" => Result::Ok(FileEntryResponse {
-                ttl: &TTL,
-                attr: make_atr(
+                ttl: self.fixed_ttl(),
+                attr: self.attr(
                     FIXED_INODE_HELLO_WORLD,
                     HELLO_TXT_CONTENT.len(),
                     FileType::RegularFile,
@@ -88,13 +294,28 @@ where
                 generation: GENERATION,
             }),
             "albums" => Result::Ok(FileEntryResponse {
-                ttl: &TTL,
-                attr: make_atr(FIXED_INODE_ALBUMS, 0, FileType::Directory),
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(FIXED_INODE_ALBUMS, 0)?,
                 generation: GENERATION,
             }),
             "media" => Result::Ok(FileEntryResponse {
-                ttl: &TTL,
-                attr: make_atr(FIXED_INODE_MEDIA, 0, FileType::Directory),
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(FIXED_INODE_MEDIA, 0)?,
+                generation: GENERATION,
+            }),
+            "categories" => Result::Ok(FileEntryResponse {
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(FIXED_INODE_CATEGORIES, 0)?,
+                generation: GENERATION,
+            }),
+            "shared-albums" => Result::Ok(FileEntryResponse {
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(FIXED_INODE_SHARED_ALBUMS, 0)?,
+                generation: GENERATION,
+            }),
+            "thumbnails" => Result::Ok(FileEntryResponse {
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(FIXED_INODE_THUMBNAILS, 0)?,
                 generation: GENERATION,
             }),
             _ => {
@@ -102,23 +323,19 @@ where
                     "lookup: Failed to find a FileAttr for name={:?} in root",
                     name
                 );
-                Result::Err(FuseError::FunctionNotImplemented)
+                self.negative_entry()
             }
         }
     }
 
-    fn lookup_albums(
-        &mut self,
-        _req: &dyn UniqRequest,
-        name: &OsStr,
-    ) -> FuseResult<FileEntryResponse<'_>> {
+    fn lookup_albums(&self, _req: &dyn UniqRequest, name: &OsStr) -> FuseResult<FileEntryResponse> {
         let name = name.to_str().unwrap();
         match self.photo_db.album_by_name(&String::from(name)) {
             Ok(Option::Some(album)) => {
                 let size = self.photo_db.media_items_in_album_length(album.inode)?;
                 Result::Ok(FileEntryResponse {
-                    ttl: &TTL,
-                    attr: make_atr(album.inode, size, FileType::Directory),
+                    ttl: self.album_ttl(),
+                    attr: self.dir_attr(album.inode, size)?,
                     generation: GENERATION,
                 })
             }
@@ -127,7 +344,7 @@ where
                     "lookup: Failed to find a FileAttr for name={:?} in albums",
                     name
                 );
-                Result::Err(FuseError::FunctionNotImplemented)
+                self.negative_entry()
             }
             Err(error) => {
                 warn!(
@@ -139,24 +356,132 @@ where
         }
     }
 
+    fn lookup_shared_albums(
+        &self,
+        _req: &dyn UniqRequest,
+        name: &OsStr,
+    ) -> FuseResult<FileEntryResponse> {
+        let name = match name.to_str() {
+            Option::Some(name) => name,
+            Option::None => return self.negative_entry(),
+        };
+        match self.photo_db.shared_album_by_name(&String::from(name)) {
+            Ok(Option::Some(album)) => {
+                let size = self.photo_db.media_items_in_album_length(album.inode)?;
+                Result::Ok(FileEntryResponse {
+                    ttl: self.album_ttl(),
+                    attr: self.dir_attr(album.inode, size)?,
+                    generation: GENERATION,
+                })
+            }
+            Ok(Option::None) => {
+                warn!(
+                    "lookup: Failed to find a FileAttr for name={:?} in shared albums",
+                    name
+                );
+                self.negative_entry()
+            }
+            Err(error) => {
+                warn!(
+                    "lookup: Failed to find a FileAttr for name={:?} in shared albums: {:?}",
+                    name, error
+                );
+                Result::Err(FuseError::IoError)
+            }
+        }
+    }
+
+    fn lookup_categories(
+        &self,
+        _req: &dyn UniqRequest,
+        name: &OsStr,
+    ) -> FuseResult<FileEntryResponse> {
+        let name = match name.to_str() {
+            Option::Some(name) => name,
+            Option::None => return self.negative_entry(),
+        };
+        // Only accept the lower case directory names that opendir hands out
+        match name.parse::<ContentCategory>() {
+            Ok(category) if category.dir_name() == name => {
+                let size = self.photo_db.media_items_in_category_length(category)?;
+                Result::Ok(FileEntryResponse {
+                    ttl: self.album_ttl(),
+                    attr: self.dir_attr(category_inode(category), size)?,
+                    generation: GENERATION,
+                })
+            }
+            _ => {
+                warn!(
+                    "lookup: Failed to find a FileAttr for name={:?} in categories",
+                    name
+                );
+                self.negative_entry()
+            }
+        }
+    }
+
+    fn lookup_thumbnails(
+        &self,
+        _req: &dyn UniqRequest,
+        name: &OsStr,
+    ) -> FuseResult<FileEntryResponse> {
+        let name = match name.to_str() {
+            Option::Some(name) => name,
+            Option::None => return self.negative_entry(),
+        };
+        let index = self
+            .config
+            .thumbnail_sizes
+            .iter()
+            .position(|size| format!("{}", size) == name);
+        match index {
+            Option::Some(index) => Result::Ok(FileEntryResponse {
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(self.thumbnail_size_inode(index), 0)?,
+                generation: GENERATION,
+            }),
+            Option::None => {
+                warn!(
+                    "lookup: Failed to find a FileAttr for name={:?} in thumbnails",
+                    name
+                );
+                self.negative_entry()
+            }
+        }
+    }
+
     fn lookup_media(
-        &mut self,
+        &self,
         _req: &dyn UniqRequest,
         name: &OsStr,
         filter: Filter,
-    ) -> FuseResult<FileEntryResponse<'_>> {
+        view: InodeView,
+    ) -> FuseResult<FileEntryResponse> {
         let name = name.to_str().unwrap();
+        // Sidecars only sit next to the originals, or next to the links to them
+        let target = view.link_target().unwrap_or(view);
+        let xmp_sidecar_name = media_item_name_for_xmp_sidecar(name)
+            .filter(|_| self.config.xmp_sidecars && target == InodeView::Original);
+        let (name, target) = match media_item_name_for_json_sidecar(name) {
+            Some(media_item_name) if target == InodeView::Original => {
+                (media_item_name, InodeView::JsonSidecar)
+            }
+            _ => match xmp_sidecar_name {
+                Some(media_item_name) => (media_item_name, InodeView::XmpSidecar),
+                None => (name, target),
+            },
+        };
+        let view = match view.link_target() {
+            Some(_) => target.link().unwrap(),
+            None => target,
+        };
         match self
             .photo_db
             .media_item_by_name(&String::from(name), filter)
         {
             Ok(Option::Some(media_item)) => Result::Ok(FileEntryResponse {
-                ttl: &TTL,
-                attr: make_atr(
-                    media_item.inode,
-                    DEFAULT_MEDIA_ITEM_SIZE,
-                    FileType::RegularFile,
-                ),
+                ttl: self.media_ttl(),
+                attr: self.media_item_attr(&media_item, view)?,
                 generation: GENERATION,
             }),
             Ok(Option::None) => {
@@ -164,7 +489,7 @@ where
                     "lookup: Failed to find a FileAttr for name={:?} in media",
                     name
                 );
-                Result::Err(FuseError::FunctionNotImplemented)
+                self.negative_entry()
             }
             Err(error) => {
                 error!(
@@ -176,12 +501,214 @@ where
         }
     }
 
-    fn opendir_entries(
-        &mut self,
-        ino: u64,
-        album_for_inode: &Option<PhotoDbAlbum>,
-    ) -> Vec<(u64, fuse::FileType, String)> {
+    // Albums and shared albums share the same directory layout
+    fn any_album_by_inode(&self, inode: Inode) -> Result<Option<PhotoDbAlbum>, DbError> {
+        match self.photo_db.item_by_inode(inode)? {
+            Option::Some(item) => match item.media_type {
+                MediaTypes::Album | MediaTypes::SharedAlbum => Result::Ok(Option::Some(item)),
+                MediaTypes::MediaItem => Result::Ok(Option::None),
+            },
+            Option::None => Result::Ok(Option::None),
+        }
+    }
+
+    // Resolves an inode that may point at another view (thumbnail, sidecar, ...)
+    // of a media item. Only media items have views, and only the known ones.
+    fn item_by_view_inode(
+        &self,
+        inode: Inode,
+    ) -> Result<Option<(PhotoDbMediaItem, InodeView)>, DbError> {
+        let (inode, view) = match split_inode(inode) {
+            Option::Some((inode, view)) if self.is_known_view(view) => (inode, view),
+            _ => return Result::Ok(Option::None),
+        };
+        let item = match view {
+            InodeView::Original => self.photo_db.item_by_inode(inode)?,
+            _ => self.photo_db.media_item_by_inode(inode)?,
+        };
+        Result::Ok(item.map(|item| (item, view)))
+    }
+
+    fn media_item_by_view_inode(
+        &self,
+        inode: Inode,
+    ) -> Result<Option<(PhotoDbMediaItem, InodeView)>, DbError> {
+        match self.item_by_view_inode(inode)? {
+            Option::Some((item, view)) => match item.media_type {
+                MediaTypes::MediaItem => Result::Ok(Option::Some((item, view))),
+                MediaTypes::Album | MediaTypes::SharedAlbum => Result::Ok(Option::None),
+            },
+            Option::None => Result::Ok(Option::None),
+        }
+    }
+
+    // Generated from the local DB only, so cheap enough to do on every lookup.
+    // None for views that are downloaded instead.
+    fn sidecar_data(
+        &self,
+        media_item: &PhotoDbMediaItem,
+        view: InodeView,
+    ) -> Result<Option<Vec<u8>>, DbError> {
+        if !view.is_sidecar() {
+            return Result::Ok(Option::None);
+        }
+        let id = media_item.google_id();
+        let metadata = self.photo_db.media_item_metadata(id)?.unwrap_or_default();
+        let albums = self.photo_db.albums_for_media_item(id)?;
+        match view {
+            InodeView::XmpSidecar => Result::Ok(Option::Some(xmp_sidecar(
+                &metadata,
+                &albums,
+                self.photo_db.is_favourite(id)?,
+            ))),
+            _ => Result::Ok(Option::Some(json_sidecar(media_item, &metadata, &albums))),
+        }
+    }
+
+    fn media_item_size(
+        &self,
+        media_item: &PhotoDbMediaItem,
+        view: InodeView,
+    ) -> Result<usize, DbError> {
+        let inode = view_inode(media_item.inode, view);
+        let size = match self.sidecar_data(media_item, view)? {
+            Option::Some(data) => data.len(),
+            Option::None => self
+                .fetcher
+                .known_size(inode)
+                .unwrap_or(DEFAULT_MEDIA_ITEM_SIZE),
+        };
+        self.reported_sizes.insert(inode, size);
+        Result::Ok(size)
+    }
+
+    // Without default_permissions the kernel leaves all checks to the filesystem, so
+    // other users of an allow_other mount are turned away here. Only the mounting user
+    // is let in when no other users or groups are listed.
+    fn check_access(&self, req: &dyn UniqRequest) -> FuseResult<()> {
+        if req.uid() == users::get_current_uid()
+            || self.config.allowed_uids.contains(&req.uid())
+            || self.in_allowed_group(req)
+        {
+            Result::Ok(())
+        } else {
+            warn!(
+                "Denied access to uid={} gid={} pid={}",
+                req.uid(),
+                req.gid(),
+                req.pid()
+            );
+            Result::Err(FuseError::PermissionDenied)
+        }
+    }
+
+    // The supplementary groups are only read when the primary one is not listed
+    fn in_allowed_group(&self, req: &dyn UniqRequest) -> bool {
+        let allowed_gids = &self.config.allowed_gids;
+        !allowed_gids.is_empty()
+            && (allowed_gids.contains(&req.gid())
+                || supplementary_groups(req.pid())
+                    .iter()
+                    .any(|gid| allowed_gids.contains(gid)))
+    }
+
+    // make_atr with the configured owner and permission bits
+    fn attr(&self, inode: Inode, size: usize, file_type: FileType) -> FileAttr {
+        let mut attr = make_atr(inode, size, file_type);
+        match file_type {
+            FileType::Directory => attr.perm = self.config.dir_mode,
+            FileType::Symlink => {}
+            _ => attr.perm = self.config.file_mode,
+        }
+        if let Some(uid) = self.config.uid {
+            attr.uid = uid;
+        }
+        if let Some(gid) = self.config.gid {
+            attr.gid = gid;
+        }
+        attr
+    }
+
+    // Each subdirectory links back to its parent with "..", so it adds to its nlink
+    fn dir_attr(&self, inode: Inode, size: usize) -> Result<FileAttr, DbError> {
+        let subdirectories = match inode {
+            FIXED_INODE_ROOT => ROOT_SUBDIRECTORIES,
+            FIXED_INODE_ALBUMS => self.photo_db.albums()?.len(),
+            FIXED_INODE_SHARED_ALBUMS => self.photo_db.shared_albums()?.len(),
+            FIXED_INODE_CATEGORIES => self.photo_db.categories()?.len(),
+            FIXED_INODE_THUMBNAILS => self.config.thumbnail_sizes.len(),
+            _ => 0,
+        };
+        let mut attr = self.attr(inode, size, FileType::Directory);
+        attr.nlink += subdirectories as u32;
+        Result::Ok(attr)
+    }
+
+    // Symlinks report the length of their target as their size
+    fn media_item_attr(
+        &self,
+        media_item: &PhotoDbMediaItem,
+        view: InodeView,
+    ) -> Result<FileAttr, DbError> {
+        let inode = view_inode(media_item.inode, view);
+        match link_target(media_item, view) {
+            Option::Some(target) => Result::Ok(self.attr(inode, target.len(), FileType::Symlink)),
+            Option::None => Result::Ok(self.attr(
+                inode,
+                self.media_item_size(media_item, view)?,
+                FileType::RegularFile,
+            )),
+        }
+    }
+
+    // The page cache can only be used when the kernel knows the real size, otherwise
+    // reads are cut off at the placeholder size or padded with zeros up to it
+    fn open_flags(&self, ino: Inode, size: usize, keep_cache: bool) -> u32 {
+        let reported_size = self.reported_sizes.get(ino);
+        if reported_size != Option::Some(size) {
+            fuse::consts::FOPEN_DIRECT_IO
+        } else if keep_cache {
+            fuse::consts::FOPEN_KEEP_CACHE
+        } else {
+            0
+        }
+    }
+
+    fn fixed_ttl(&self) -> Timespec {
+        ttl(self.config.fixed_ttl_seconds)
+    }
+
+    fn album_ttl(&self) -> Timespec {
+        ttl(self.config.album_ttl_seconds)
+    }
+
+    fn media_ttl(&self) -> Timespec {
+        ttl(self.config.media_ttl_seconds)
+    }
+
+    // Inode 0 tells the kernel the name does not exist, it then answers lookups of
+    // the name with ENOENT itself until the TTL runs out
+    fn negative_entry(&self) -> FuseResult<FileEntryResponse> {
+        if self.config.negative_ttl_seconds == 0 {
+            return Result::Err(FuseError::FunctionNotImplemented);
+        }
+        Result::Ok(FileEntryResponse {
+            ttl: ttl(self.config.negative_ttl_seconds),
+            attr: make_atr(0, 0, FileType::RegularFile),
+            generation: GENERATION,
+        })
+    }
+
+    fn shared_album_xattrs(&self, inode: Inode) -> FuseResult<Option<SharedAlbumInfo>> {
+        match self.photo_db.shared_album_by_inode(inode)? {
+            Option::Some(album) => Result::Ok(self.photo_db.shared_album_info(album.google_id())?),
+            Option::None => Result::Ok(Option::None),
+        }
+    }
+
+    fn opendir_entries(&self, ino: u64, album_for_inode: &Option<PhotoDbAlbum>) -> ReadDirFhEntry {
         let mut entries: Vec<(u64, fuse::FileType, String)> = Vec::new();
+        let mut media = Option::None;
         entries.push((ino, FileType::Directory, String::from(".")));
 
         if ino == FIXED_INODE_ROOT {
@@ -196,6 +723,21 @@ where
                 FileType::Directory,
                 String::from("media"),
             ));
+            entries.push((
+                FIXED_INODE_CATEGORIES,
+                FileType::Directory,
+                String::from("categories"),
+            ));
+            entries.push((
+                FIXED_INODE_SHARED_ALBUMS,
+                FileType::Directory,
+                String::from("shared-albums"),
+            ));
+            entries.push((
+                FIXED_INODE_THUMBNAILS,
+                FileType::Directory,
+                String::from("thumbnails"),
+            ));
             entries.push((
                 FIXED_INODE_HELLO_WORLD,
                 FileType::RegularFile,
@@ -223,68 +765,270 @@ where
                     warn!("Failed backend listing albums: {:?}", error);
                 }
             }
-        } else if ino == FIXED_INODE_MEDIA || album_for_inode.is_some() {
-            let media_items = if ino == FIXED_INODE_MEDIA {
-                debug!("FS opendir: is for media");
-                entries.push((FIXED_INODE_ROOT, FileType::Directory, String::from("..")));
-                self.photo_db.media_items()
-            } else {
-                debug!("FS opendir: is for media in album");
-                entries.push((FIXED_INODE_ALBUMS, FileType::Directory, String::from("..")));
-                self.photo_db.media_items_in_album(ino)
-            };
-            let mut media_items_dedupe = HashSet::new();
-            match media_items {
-                Ok(media_items) => {
-                    debug!(
-                        "FS opendir: Success listing media len={}",
-                        media_items.len()
-                    );
-                    for media_item in media_items {
-                        debug!("media_item: {:?}", media_item);
-                        if media_items_dedupe.insert(media_item.name.clone()) {
-                            let entry = (
-                                media_item.inode,
-                                FileType::RegularFile,
-                                media_item.name.clone(),
-                            );
+        } else if ino == FIXED_INODE_SHARED_ALBUMS {
+            debug!("FS opendir: is for shared albums");
+            entries.push((FIXED_INODE_ROOT, FileType::Directory, String::from("..")));
+            let albums = self.photo_db.shared_albums();
+            let mut albums_dedupe = HashSet::new();
+            match albums {
+                Ok(albums) => {
+                    debug!("FS opendir: Success: listing shared albums");
+                    for album in albums {
+                        debug!("FS opendir: \tshared album: {:?}", album);
+                        if albums_dedupe.insert(album.name.clone()) {
+                            let entry = (album.inode, FileType::Directory, album.name.clone());
                             entries.push(entry);
                         } else {
-                            warn!("FS opendir: skipping {} as duplicate name", media_item.name);
+                            warn!("FS opendir: skipping {} as duplicate name", album.name);
                         }
                     }
                 }
                 Err(error) => {
-                    warn!("Failed backend listing media: {:?}", error);
+                    warn!("Failed backend listing shared albums: {:?}", error);
+                }
+            }
+        } else if ino == FIXED_INODE_CATEGORIES {
+            debug!("FS opendir: is for categories");
+            entries.push((FIXED_INODE_ROOT, FileType::Directory, String::from("..")));
+            match self.photo_db.categories() {
+                Ok(categories) => {
+                    debug!("FS opendir: Success: listing categories");
+                    for category in categories {
+                        entries.push((
+                            category_inode(category),
+                            FileType::Directory,
+                            category.dir_name(),
+                        ));
+                    }
+                }
+                Err(error) => {
+                    warn!("Failed backend listing categories: {:?}", error);
                 }
             }
+        } else if ino == FIXED_INODE_THUMBNAILS {
+            debug!("FS opendir: is for thumbnails");
+            entries.push((FIXED_INODE_ROOT, FileType::Directory, String::from("..")));
+            for (index, size) in self.config.thumbnail_sizes.iter().enumerate() {
+                entries.push((
+                    self.thumbnail_size_inode(index),
+                    FileType::Directory,
+                    format!("{}", size),
+                ));
+            }
+        } else if ino == FIXED_INODE_MEDIA
+            || album_for_inode.is_some()
+            || category_for_inode(ino).is_some()
+            || self.thumbnail_index_for_inode(ino).is_some()
+        {
+            let listing = if ino == FIXED_INODE_MEDIA {
+                debug!("FS opendir: is for media");
+                entries.push((FIXED_INODE_ROOT, FileType::Directory, String::from("..")));
+                (MediaListing::All, InodeView::Original)
+            } else if let Some(category) = category_for_inode(ino) {
+                debug!("FS opendir: is for media in category");
+                entries.push((
+                    FIXED_INODE_CATEGORIES,
+                    FileType::Directory,
+                    String::from(".."),
+                ));
+                (MediaListing::Category(category), InodeView::Original)
+            } else if let Some(index) = self.thumbnail_index_for_inode(ino) {
+                debug!("FS opendir: is for thumbnails of media");
+                entries.push((
+                    FIXED_INODE_THUMBNAILS,
+                    FileType::Directory,
+                    String::from(".."),
+                ));
+                (MediaListing::All, InodeView::Thumbnail(index))
+            } else {
+                debug!("FS opendir: is for media in album");
+                let album = album_for_inode.as_ref().unwrap();
+                let parent = match album.media_type {
+                    MediaTypes::SharedAlbum => FIXED_INODE_SHARED_ALBUMS,
+                    _ => FIXED_INODE_ALBUMS,
+                };
+                entries.push((parent, FileType::Directory, String::from("..")));
+                (
+                    MediaListing::Album(album.google_id().to_string()),
+                    self.album_view(),
+                )
+            };
+            media = Option::Some(listing);
         } else {
             panic!("Code should never reach this location");
         };
 
+        ReadDirFhEntry::new(ino, entries, media)
+    }
+
+    // The entries of a media item in a media directory, its sidecars follow it
+    fn media_item_entries(
+        &self,
+        media_item: &PhotoDbMediaItem,
+        view: InodeView,
+    ) -> Vec<(u64, fuse::FileType, String)> {
+        // Links are listed in place of the views they point at
+        let linked = view.link_target().is_some();
+        let kind = if linked {
+            FileType::Symlink
+        } else {
+            FileType::RegularFile
+        };
+        let listed = |target: InodeView| {
+            if linked {
+                target.link().unwrap()
+            } else {
+                target
+            }
+        };
+        let mut entries = vec![(
+            view_inode(media_item.inode, view),
+            kind,
+            media_item.name.clone(),
+        )];
+        if listed(InodeView::Original) == view {
+            entries.push((
+                view_inode(media_item.inode, listed(InodeView::JsonSidecar)),
+                kind,
+                json_sidecar_name(&media_item.name),
+            ));
+            if self.config.xmp_sidecars {
+                entries.push((
+                    view_inode(media_item.inode, listed(InodeView::XmpSidecar)),
+                    kind,
+                    xmp_sidecar_name(&media_item.name),
+                ));
+            }
+        }
         entries
     }
+
+    // Reads the page of a media directory following offset
+    fn readdir_media(
+        &self,
+        directory: Inode,
+        listing: &MediaListing,
+        view: InodeView,
+        offset: i64,
+    ) -> Result<Vec<ReadDirEntry>, DbError> {
+        let mut result_entries = Vec::new();
+        let mut add_entries = |media_item: &PhotoDbMediaItem, skip: usize| {
+            for (position, entry) in self
+                .media_item_entries(media_item, view)
+                .into_iter()
+                .enumerate()
+                .skip(skip)
+            {
+                result_entries.push(ReadDirEntry {
+                    ino: entry.0,
+                    offset: media_entry_offset(media_item.inode, position),
+                    kind: entry.1,
+                    name: OsString::from(entry.2),
+                });
+            }
+        };
+
+        let mut after_inode = 0;
+        if let Some((inode, position)) = split_media_entry_offset(offset) {
+            // Finish the media item the last page stopped in
+            if let Some(media_item) = self.photo_db.media_item_by_inode(inode)? {
+                add_entries(&media_item, position + 1);
+            }
+            after_inode = inode;
+        }
+        let media_items =
+            self.photo_db
+                .media_items_page(listing.filter(), after_inode, READDIR_PAGE_SIZE)?;
+        debug!(
+            "FS readdir: Success listing media after={} len={}",
+            after_inode,
+            media_items.len()
+        );
+        for media_item in &media_items {
+            add_entries(media_item, 0);
+        }
+
+        // Files behind links are opened through /media
+        let target = view.link_target().unwrap_or(view);
+        if self.media_variant(target).is_some() {
+            self.prefetcher.listed(
+                directory,
+                after_inode == 0,
+                media_items
+                    .iter()
+                    .map(|media_item| view_inode(media_item.inode, target))
+                    .collect(),
+            );
+        }
+        Result::Ok(result_entries)
+    }
 }
 
-impl<X, Y> RustFilesystem for PhotoFs<X, Y>
+// Prefetching hands the fetcher to another thread
+impl<X, Y> PhotoFs<X, Y>
 where
-    X: RemotePhotoLibData,
-    Y: PhotoDbRo,
+    X: RemotePhotoLibData + 'static,
+    Y: PhotoDbRo + CategoryDb + SharedAlbumDb + MetadataDb + Send + Sync + 'static,
 {
-    fn lookup(
-        &mut self,
-        req: &dyn UniqRequest,
-        parent: u64,
+    // Queues downloads of the files following ino, if the files of its directory are
+    // being opened in order
+    fn prefetch_after(&self, ino: Inode) {
+        for next in self.prefetcher.opened(ino) {
+            let (media_item, variant) = match self.media_item_by_view_inode(next) {
+                Ok(Option::Some((media_item, view))) => match self.media_variant(view) {
+                    Option::Some(variant) => (media_item, variant),
+                    Option::None => continue,
+                },
+                Ok(Option::None) => continue,
+                Err(error) => {
+                    warn!("Failed to lookup media item to prefetch: {:?}", error);
+                    continue;
+                }
+            };
+            debug!("Prefetching {:?} (ino={})", media_item.name, next);
+            let fetcher = self.fetcher.clone();
+            self.prefetcher
+                .execute(next, move || fetcher.prefetch(next, &media_item, variant));
+        }
+    }
+}
+
+impl<X, Y> RustFilesystem for PhotoFs<X, Y>
+where
+    X: RemotePhotoLibData + 'static,
+    Y: PhotoDbRo + CategoryDb + SharedAlbumDb + MetadataDb + Send + Sync + 'static,
+{
+    fn lookup(
+        &self,
+        req: &dyn UniqRequest,
+        parent: u64,
         name: &OsStr,
-    ) -> FuseResult<FileEntryResponse<'_>> {
+    ) -> FuseResult<FileEntryResponse> {
+        self.check_access(req)?;
         match parent {
             FIXED_INODE_ROOT => self.lookup_root(req, name),
             FIXED_INODE_ALBUMS => self.lookup_albums(req, name),
-            FIXED_INODE_MEDIA => self.lookup_media(req, name, Filter::NoFilter),
-            _ => match self.photo_db.album_by_inode(parent) {
-                Ok(Option::Some(album)) => {
-                    self.lookup_media(req, name, Filter::ByAlbum(album.google_id()))
-                }
+            FIXED_INODE_MEDIA => {
+                self.lookup_media(req, name, Filter::NoFilter, InodeView::Original)
+            }
+            FIXED_INODE_CATEGORIES => self.lookup_categories(req, name),
+            FIXED_INODE_SHARED_ALBUMS => self.lookup_shared_albums(req, name),
+            FIXED_INODE_THUMBNAILS => self.lookup_thumbnails(req, name),
+            _ if category_for_inode(parent).is_some() => {
+                let category = category_for_inode(parent).unwrap();
+                self.lookup_media(req, name, Filter::ByCategory(category), InodeView::Original)
+            }
+            _ if self.thumbnail_index_for_inode(parent).is_some() => {
+                let index = self.thumbnail_index_for_inode(parent).unwrap();
+                self.lookup_media(req, name, Filter::NoFilter, InodeView::Thumbnail(index))
+            }
+            _ => match self.any_album_by_inode(parent) {
+                Ok(Option::Some(album)) => self.lookup_media(
+                    req,
+                    name,
+                    Filter::ByAlbum(album.google_id()),
+                    self.album_view(),
+                ),
                 Ok(Option::None) => {
                     warn!(
                         "FS lookup: Failed to find a FileAttr for inode={} (name={:?})",
@@ -303,30 +1047,55 @@ where
         }
     }
 
-    fn getattr(&mut self, _req: &dyn UniqRequest, ino: u64) -> FuseResult<FileAttrResponse<'_>> {
+    fn getattr(&self, req: &dyn UniqRequest, ino: u64) -> FuseResult<FileAttrResponse> {
         debug!("FS getattr: ino={}", ino);
+        self.check_access(req)?;
         match ino {
             FIXED_INODE_ROOT => Result::Ok(FileAttrResponse {
-                ttl: &TTL,
-                attr: make_atr(FIXED_INODE_ROOT, 4, FileType::Directory),
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(FIXED_INODE_ROOT, 4)?,
             }),
             FIXED_INODE_ALBUMS => Result::Ok(FileAttrResponse {
-                ttl: &TTL,
-                attr: make_atr(FIXED_INODE_ALBUMS, 0, FileType::Directory),
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(FIXED_INODE_ALBUMS, 0)?,
             }),
             FIXED_INODE_MEDIA => Result::Ok(FileAttrResponse {
-                ttl: &TTL,
-                attr: make_atr(FIXED_INODE_MEDIA, 0, FileType::Directory),
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(FIXED_INODE_MEDIA, 0)?,
             }),
             FIXED_INODE_HELLO_WORLD => Result::Ok(FileAttrResponse {
-                ttl: &TTL,
-                attr: make_atr(
+                ttl: self.fixed_ttl(),
+                attr: self.attr(
                     FIXED_INODE_HELLO_WORLD,
                     HELLO_TXT_CONTENT.len(),
                     FileType::RegularFile,
                 ),
             }),
-            _ => match self.photo_db.item_by_inode(ino) {
+            FIXED_INODE_CATEGORIES => Result::Ok(FileAttrResponse {
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(FIXED_INODE_CATEGORIES, 0)?,
+            }),
+            FIXED_INODE_SHARED_ALBUMS => Result::Ok(FileAttrResponse {
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(FIXED_INODE_SHARED_ALBUMS, 0)?,
+            }),
+            FIXED_INODE_THUMBNAILS => Result::Ok(FileAttrResponse {
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(FIXED_INODE_THUMBNAILS, 0)?,
+            }),
+            _ if self.thumbnail_index_for_inode(ino).is_some() => Result::Ok(FileAttrResponse {
+                ttl: self.fixed_ttl(),
+                attr: self.dir_attr(ino, 0)?,
+            }),
+            _ if category_for_inode(ino).is_some() => {
+                let category = category_for_inode(ino).unwrap();
+                let size = self.photo_db.media_items_in_category_length(category)?;
+                Result::Ok(FileAttrResponse {
+                    ttl: self.album_ttl(),
+                    attr: self.dir_attr(ino, size)?,
+                })
+            }
+            _ => match self.item_by_view_inode(ino) {
                 Err(error) => {
                     error!("FS getattr: Failed to lookup item in local db: {:?}", error);
                     Result::Err(FuseError::FunctionNotImplemented)
@@ -335,35 +1104,41 @@ where
                     warn!("FS getattr: No item found in local DB: {:?}", ino);
                     Result::Err(FuseError::FunctionNotImplemented)
                 }
-                Ok(Option::Some(item)) => {
-                    let file_type = match item.media_type {
-                        MediaTypes::Album => FileType::Directory,
-                        MediaTypes::MediaItem => FileType::RegularFile,
-                    };
-                    let size = match item.media_type {
-                        MediaTypes::Album => {
-                            self.photo_db.media_items_in_album_length(item.inode)?
-                        }
-                        MediaTypes::MediaItem => DEFAULT_MEDIA_ITEM_SIZE,
-                    };
-
-                    Result::Ok(FileAttrResponse {
-                        ttl: &TTL,
-                        attr: make_atr(item.inode, size, file_type),
-                    })
-                }
+                Ok(Option::Some((item, view))) => match item.media_type {
+                    MediaTypes::Album | MediaTypes::SharedAlbum => {
+                        let size = self.photo_db.media_items_in_album_length(item.inode)?;
+                        Result::Ok(FileAttrResponse {
+                            ttl: self.album_ttl(),
+                            attr: self.dir_attr(ino, size)?,
+                        })
+                    }
+                    MediaTypes::MediaItem => Result::Ok(FileAttrResponse {
+                        ttl: self.media_ttl(),
+                        attr: self.media_item_attr(&item, view)?,
+                    }),
+                },
             },
         }
     }
 
-    fn open(&mut self, _req: &dyn UniqRequest, ino: u64, _flags: u32) -> FuseResult<OpenResponse> {
+    fn open(&self, req: &dyn UniqRequest, ino: u64, flags: u32) -> FuseResult<OpenResponse> {
         debug!("FS open: ino={}", ino);
+        self.check_access(req)?;
+        // Media items can not be changed through the API, even with write scopes
+        let flags = flags as i32;
+        if flags & libc::O_ACCMODE != libc::O_RDONLY || flags & libc::O_TRUNC != 0 {
+            self.check_writable(req)?;
+            return Result::Err(FuseError::PermissionDenied);
+        }
 
-        let file_data: Vec<u8>;
+        let file_data: Arc<Vec<u8>>;
+        let flags: u32;
         if ino == FIXED_INODE_HELLO_WORLD {
-            file_data = HELLO_TXT_CONTENT.to_vec();
+            file_data = Arc::new(HELLO_TXT_CONTENT.to_vec());
+            // Reported with its real size from the start
+            flags = fuse::consts::FOPEN_KEEP_CACHE;
         } else {
-            match self.photo_db.media_item_by_inode(ino) {
+            match self.media_item_by_view_inode(ino) {
                 Err(error) => {
                     error!(
                         "FS open: Failed to lookup media item in local db: {:?}",
@@ -375,48 +1150,47 @@ where
                     warn!("FS open: No media items found in local DB: {:?}", ino);
                     return Result::Err(FuseError::FunctionNotImplemented);
                 }
-                Ok(Option::Some(media_item)) => {
-                    let photo_lib = self.photo_lib.lock().unwrap();
-                    let filename_lowercase = media_item.name.to_lowercase();
-                    let is_video = filename_lowercase.ends_with(".mp4")
-                        || filename_lowercase.ends_with(".mts")
-                        || filename_lowercase.ends_with(".avi"); // TODO: Use MIME Type
-                    match photo_lib.media_item(media_item.google_id(), is_video) {
-                        Err(error) => {
-                            error!(
-                                "FS open: Failed to fetch media item from remote: {:?}",
-                                error
-                            );
-                            return Result::Err(FuseError::FunctionNotImplemented);
-                        }
-                        Ok(data) => {
-                            file_data = data;
-                        }
-                    }
+                Ok(Option::Some((_, view))) if view.link_target().is_some() => {
+                    // The kernel follows symlinks instead of opening them
+                    warn!("FS open: Can not open symlink: {:?}", ino);
+                    return Result::Err(FuseError::FunctionNotImplemented);
+                }
+                Ok(Option::Some((media_item, view))) if view.is_sidecar() => {
+                    file_data = Arc::new(self.sidecar_data(&media_item, view)?.unwrap_or_default());
+                    // Sidecars are regenerated on every open, drop pages of earlier ones
+                    flags = self.open_flags(ino, file_data.len(), false);
+                }
+                Ok(Option::Some((media_item, view))) => {
+                    self.prefetch_after(ino);
+                    file_data =
+                        self.fetcher
+                            .fetch(ino, &media_item, self.media_variant(view).unwrap())?;
+                    flags = self.open_flags(ino, file_data.len(), true);
                 }
             }
         }
 
-        let fh = self.open_files.open(ReadFhEntry::new(ino, file_data));
+        let fh = self
+            .open_files
+            .lock()
+            .unwrap()
+            .open(ReadFhEntry::new(ino, file_data));
 
-        Result::Ok(OpenResponse {
-            fh,
-            flags: fuse::consts::FOPEN_DIRECT_IO,
-        })
+        Result::Ok(OpenResponse { fh, flags })
     }
 
     fn read(
-        &mut self,
+        &self,
         _req: &dyn UniqRequest,
         ino: u64,
         fh: u64,
         offset: i64,
         size: u32,
-    ) -> FuseResult<ReadResponse<'_>> {
+    ) -> FuseResult<ReadResponse> {
         let offset = offset as usize;
         debug!("FS read: ino={}, offset={} size={}", ino, offset, size);
 
-        match self.open_files.get(fh) {
+        match self.open_files.lock().unwrap().get(fh) {
             None => Result::Err(FuseError::FunctionNotImplemented),
             Some(entry) => {
                 if entry.inode != ino {
@@ -430,18 +1204,18 @@ where
                         "Attempt to read past end of file: file_size={} offset={}",
                         data_len, offset
                     );
-                    return Result::Ok(ReadResponse { data: &[] });
+                    return Result::Ok(ReadResponse { data: Vec::new() });
                 }
                 let slice_end: usize = usize::min(offset as usize + size as usize, data_len);
                 Result::Ok(ReadResponse {
-                    data: &entry.data[offset as usize..slice_end],
+                    data: entry.data[offset as usize..slice_end].to_vec(),
                 })
             }
         }
     }
 
     fn release(
-        &mut self,
+        &self,
         _req: &dyn UniqRequest,
         ino: u64,
         fh: u64,
@@ -451,21 +1225,30 @@ where
     ) -> FuseResult<()> {
         debug!("FS release: ino={}, fh={}", ino, fh);
 
-        match self.open_files.remove(fh) {
+        let entry = self.open_files.lock().unwrap().remove(fh);
+        match entry {
             None => Result::Err(FuseError::FunctionNotImplemented),
-            Some(_) => Result::Ok(()),
+            Some(entry) => {
+                // The content may be evicted once no handle uses it anymore
+                drop(entry);
+                self.fetcher.trim();
+                Result::Ok(())
+            }
         }
     }
 
-    fn opendir(
-        &mut self,
-        _req: &dyn UniqRequest,
-        ino: u64,
-        _flags: u32,
-    ) -> FuseResult<OpenResponse> {
+    fn opendir(&self, req: &dyn UniqRequest, ino: u64, _flags: u32) -> FuseResult<OpenResponse> {
+        self.check_access(req)?;
         let album_for_inode: Option<PhotoDbAlbum> = match ino {
-            FIXED_INODE_ROOT | FIXED_INODE_MEDIA | FIXED_INODE_ALBUMS => Result::Ok(Option::None),
-            _ => match self.photo_db.album_by_inode(ino) {
+            FIXED_INODE_ROOT
+            | FIXED_INODE_MEDIA
+            | FIXED_INODE_ALBUMS
+            | FIXED_INODE_CATEGORIES
+            | FIXED_INODE_SHARED_ALBUMS
+            | FIXED_INODE_THUMBNAILS => Result::Ok(Option::None),
+            _ if category_for_inode(ino).is_some() => Result::Ok(Option::None),
+            _ if self.thumbnail_index_for_inode(ino).is_some() => Result::Ok(Option::None),
+            _ => match self.any_album_by_inode(ino) {
                 Err(error) => {
                     error!(
                         "FS opendir: Error checking inode is a album (ino={}): {:?}",
@@ -487,79 +1270,152 @@ where
             },
         }?;
 
-        let entries = self.opendir_entries(ino, &album_for_inode);
-        let fh = self.open_dirs.open(ReadDirFhEntry::new(ino, entries));
+        let fh_entry = self.opendir_entries(ino, &album_for_inode);
+        let fh = self.open_dirs.lock().unwrap().open(fh_entry);
 
         Result::Ok(OpenResponse { fh, flags: 0 }) // TODO: Flags
     }
 
     fn readdir(
-        &mut self,
+        &self,
         _req: &dyn UniqRequest,
         ino: u64,
         fh: u64,
         offset: i64,
-    ) -> FuseResult<ReadDirResponse<'_>> {
+    ) -> FuseResult<ReadDirResponse> {
         debug!("FS readdir: ino={}, offset={}", ino, offset);
 
-        let fh_entry = match self.open_dirs.get(fh) {
-            None => return Result::Err(FuseError::FunctionNotImplemented),
-            Some(entry) => entry,
-        };
+        let mut result_entries: Vec<ReadDirEntry>;
+        let media;
+        {
+            let open_dirs = self.open_dirs.lock().unwrap();
+            let fh_entry = match open_dirs.get(fh) {
+                None => return Result::Err(FuseError::FunctionNotImplemented),
+                Some(entry) => entry,
+            };
 
-        if fh_entry.inode != ino {
-            error!("Read dir handle found entry for a different inode");
-            return Result::Err(FuseError::FunctionNotImplemented);
-        }
+            if fh_entry.inode != ino {
+                error!("Read dir handle found entry for a different inode");
+                return Result::Err(FuseError::FunctionNotImplemented);
+            }
 
-        // TODO: Error when not known inode
-        // reply.error(ENOENT);
+            // TODO: Error when not known inode
+            // reply.error(ENOENT);
+
+            // The offset of an entry is where the next readdir continues after it
+            result_entries = (&fh_entry.entries)
+                .iter()
+                .enumerate()
+                .skip(offset as usize)
+                .map(|(index, entry)| ReadDirEntry {
+                    ino: entry.0,
+                    offset: (index + 1) as i64,
+                    kind: entry.1,
+                    name: OsString::from(&entry.2),
+                })
+                .collect();
+            media = fh_entry.media.clone();
+        }
 
-        let to_skip = if offset == 0 { offset } else { offset + 1 } as usize;
-        let result_entries: Vec<ReadDirEntry<'_>> = (&fh_entry.entries)
-            .iter()
-            .enumerate()
-            .skip(to_skip)
-            .map(|(offset, entry)| {
-                let ino = entry.0;
-                let kind = entry.1;
-                let name = OsStr::new(&entry.2);
-                ReadDirEntry {
-                    ino,
-                    offset: (offset + 1) as i64,
-                    kind,
-                    name,
-                }
-            })
-            .collect();
+        // Not holding the lock, the DB is slow for large directories
+        if let Some((listing, view)) = media {
+            result_entries.append(&mut self.readdir_media(ino, &listing, view, offset)?);
+        }
         Result::Ok(ReadDirResponse {
             entries: result_entries,
         })
     }
 
-    fn releasedir(
-        &mut self,
-        _req: &dyn UniqRequest,
-        ino: u64,
-        fh: u64,
-        _flags: u32,
-    ) -> FuseResult<()> {
+    fn releasedir(&self, _req: &dyn UniqRequest, ino: u64, fh: u64, _flags: u32) -> FuseResult<()> {
         debug!("FS releasedir: ino={}, fh={}", ino, fh);
 
-        match self.open_dirs.remove(fh) {
+        match self.open_dirs.lock().unwrap().remove(fh) {
             None => Result::Err(FuseError::FunctionNotImplemented),
             Some(_) => Result::Ok(()),
         }
     }
 
-    fn destroy(&mut self, _req: &dyn UniqRequest) {
-        if !self.open_files.is_empty() {
+    fn readlink(&self, req: &dyn UniqRequest, ino: u64) -> FuseResult<ReadlinkResponse> {
+        debug!("FS readlink: ino={}", ino);
+        self.check_access(req)?;
+        let target = match self.media_item_by_view_inode(ino)? {
+            Option::Some((media_item, view)) => link_target(&media_item, view),
+            Option::None => Option::None,
+        };
+        match target {
+            Option::Some(target) => Result::Ok(ReadlinkResponse {
+                target: OsString::from(target),
+            }),
+            Option::None => {
+                warn!("FS readlink: Not a symlink: {:?}", ino);
+                Result::Err(FuseError::FunctionNotImplemented)
+            }
+        }
+    }
+
+    fn access(&self, req: &dyn UniqRequest, ino: u64, mask: u32) -> FuseResult<()> {
+        debug!("FS access: ino={}", ino);
+        self.check_access(req)?;
+        if mask as i32 & libc::W_OK != 0 {
+            self.check_writable(req)?;
+        }
+        Result::Ok(())
+    }
+
+    fn getxattr(&self, req: &dyn UniqRequest, ino: u64, name: &OsStr) -> FuseResult<XattrResponse> {
+        debug!("FS getxattr: ino={}, name={:?}", ino, name);
+        self.check_access(req)?;
+
+        let share_info = match self.shared_album_xattrs(ino)? {
+            Option::Some(share_info) => share_info,
+            Option::None => return Result::Err(FuseError::NoAttribute),
+        };
+        let value = match name.to_str() {
+            Some(XATTR_SHARED_IS_OWNED) => share_info.is_owned,
+            Some(XATTR_SHARED_IS_JOINED) => share_info.is_joined,
+            Some(XATTR_SHARED_IS_COLLABORATIVE) => share_info.is_collaborative,
+            _ => return Result::Err(FuseError::NoAttribute),
+        };
+        Result::Ok(XattrResponse {
+            data: format!("{}", value).into_bytes(),
+        })
+    }
+
+    fn listxattr(&self, req: &dyn UniqRequest, ino: u64) -> FuseResult<XattrResponse> {
+        debug!("FS listxattr: ino={}", ino);
+        self.check_access(req)?;
+
+        let mut data: Vec<u8> = Vec::new();
+        if self.shared_album_xattrs(ino)?.is_some() {
+            for name in &[
+                XATTR_SHARED_IS_OWNED,
+                XATTR_SHARED_IS_JOINED,
+                XATTR_SHARED_IS_COLLABORATIVE,
+            ] {
+                data.extend_from_slice(name.as_bytes());
+                data.push(0);
+            }
+        }
+        Result::Ok(XattrResponse { data })
+    }
+
+    fn destroy(&self, _req: &dyn UniqRequest) {
+        if !self.open_files.lock().unwrap().is_empty() {
             warn!("FS destroy: destroying a filesytem with open files");
         }
-        if !self.open_dirs.is_empty() {
+        if !self.open_dirs.lock().unwrap().is_empty() {
             warn!("FS destroy: destroying a filesytem with open dirs");
         }
     }
+
+    fn check_writable(&self, req: &dyn UniqRequest) -> FuseResult<()> {
+        self.check_access(req)?;
+        if self.config.writable {
+            Result::Ok(())
+        } else {
+            Result::Err(FuseError::ReadOnlyFilesystem)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -567,30 +1423,33 @@ mod test {
     use super::*;
 
     use std::collections::HashMap;
+    use std::os::unix::ffi::OsStrExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
 
     use hyper;
 
     use chrono::{TimeZone, Utc};
 
-    use crate::domain::{GoogleId, Inode};
+    use crate::domain::{GoogleId, Inode, MediaItemMetadata};
 
-    use crate::db::{PhotoDb, SqliteDb};
+    use crate::db::{CategoryDb, MetadataDb, PhotoDb, SharedAlbumDb, SqliteDb};
+    use crate::rust_filesystem::RequestInfo;
 
     #[test]
     fn lookup_root() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
         {
-            assert!(fs
-                .lookup(
-                    &TestUniqRequest {},
-                    FIXED_INODE_ROOT,
-                    OsStr::new("not_in_root")
-                )
-                .is_err());
+            assert!(is_negative(fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_ROOT,
+                OsStr::new("not_in_root")
+            )));
         }
 
         {
@@ -619,14 +1478,90 @@ mod test {
             assert_eq!(response.attr.kind, FileType::RegularFile);
         }
 
+        {
+            let response = fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_ROOT,
+                OsStr::new("categories"),
+            )?;
+
+            assert_eq!(response.attr.ino, FIXED_INODE_CATEGORIES);
+            assert_eq!(response.attr.kind, FileType::Directory);
+        }
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn lookup_categories() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        {
+            assert!(is_negative(fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_CATEGORIES,
+                OsStr::new("not_a_category")
+            )));
+            assert!(is_negative(fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_CATEGORIES,
+                OsStr::new("RECEIPTS")
+            )));
+            assert!(is_negative(fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_CATEGORIES,
+                OsStr::from_bytes(b"receipts\xff")
+            )));
+        }
+
+        let receipts_inode = category_inode(ContentCategory::Receipts);
+        {
+            let response = fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_CATEGORIES,
+                OsStr::new("receipts"),
+            )?;
+
+            assert_eq!(response.attr.ino, receipts_inode);
+            assert_eq!(response.attr.kind, FileType::Directory);
+            assert_eq!(response.attr.size, 0);
+        }
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let media_item_inode = photo_db
+            .upsert_media_item("GoogleId1", "Receipt1.jpg", &now)
+            .unwrap();
+        photo_db
+            .set_media_items_in_category(ContentCategory::Receipts, &["GoogleId1"])
+            .unwrap();
+        {
+            let response = fs.lookup(
+                &TestUniqRequest {},
+                receipts_inode,
+                OsStr::new("Receipt1.jpg"),
+            )?;
+
+            assert_eq!(response.attr.ino, media_item_inode);
+            assert_eq!(response.attr.kind, FileType::RegularFile);
+        }
+        {
+            assert!(is_negative(fs.lookup(
+                &TestUniqRequest {},
+                category_inode(ContentCategory::Pets),
+                OsStr::new("Receipt1.jpg")
+            )));
+        }
+
         Result::Ok(())
     }
 
     #[test]
     fn lookup_albums() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
         {
             let response = fs
@@ -638,13 +1573,11 @@ mod test {
         }
 
         {
-            assert!(fs
-                .lookup(
-                    &TestUniqRequest {},
-                    FIXED_INODE_ALBUMS,
-                    OsStr::new("not_a_album")
-                )
-                .is_err());
+            assert!(is_negative(fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_ALBUMS,
+                OsStr::new("not_a_album")
+            )));
         }
 
         let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
@@ -665,9 +1598,9 @@ mod test {
 
     #[test]
     fn lookup_media_items() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
         // Empty
         {
@@ -677,7 +1610,7 @@ mod test {
                 OsStr::new("Photo1.jpg"),
             );
 
-            assert!(response.is_err());
+            assert!(is_negative(response));
         }
 
         let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
@@ -705,7 +1638,7 @@ mod test {
                 OsStr::new("Photo2.jpg"),
             );
 
-            assert!(response.is_err());
+            assert!(is_negative(response));
         }
 
         Result::Ok(())
@@ -713,9 +1646,9 @@ mod test {
 
     #[test]
     fn lookup_media_item_in_album() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
         let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
         let media_item_inode = photo_db
@@ -727,7 +1660,7 @@ mod test {
         {
             let response = fs.lookup(&TestUniqRequest {}, album_inode, OsStr::new("Photo1.jpg"));
 
-            assert!(response.is_err());
+            assert!(is_negative(response));
         }
 
         // Correct lookup
@@ -745,17 +1678,92 @@ mod test {
         {
             let response = fs.lookup(&TestUniqRequest {}, album_inode, OsStr::new("Photo2.jpg"));
 
-            assert!(response.is_err());
+            assert!(is_negative(response));
         }
 
         Result::Ok(())
     }
 
+    #[test]
+    fn lookup_ttls() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let config = PhotoFsConfig {
+            fixed_ttl_seconds: 1000,
+            album_ttl_seconds: 100,
+            media_ttl_seconds: 10,
+            negative_ttl_seconds: 1,
+            ..PhotoFsConfig::default()
+        };
+        let fs = PhotoFs::with_config(photo_lib.clone(), photo_db.clone(), config);
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let media_item_inode = photo_db.upsert_media_item("GoogleId1", "Photo1.jpg", &now)?;
+        let album_inode = photo_db.upsert_album("GoogleId2", "Album1", &now)?;
+
+        let response = fs.lookup(&TestUniqRequest {}, FIXED_INODE_ROOT, OsStr::new("albums"))?;
+        assert_eq!(response.ttl, ttl(1000));
+        let response = fs.lookup(
+            &TestUniqRequest {},
+            FIXED_INODE_ALBUMS,
+            OsStr::new("Album1"),
+        )?;
+        assert_eq!(response.ttl, ttl(100));
+        let response = fs.lookup(
+            &TestUniqRequest {},
+            FIXED_INODE_MEDIA,
+            OsStr::new("Photo1.jpg"),
+        )?;
+        assert_eq!(response.ttl, ttl(10));
+        let response = fs.lookup(
+            &TestUniqRequest {},
+            FIXED_INODE_MEDIA,
+            OsStr::new(".DS_Store"),
+        )?;
+        assert_eq!(response.attr.ino, 0);
+        assert_eq!(response.ttl, ttl(1));
+
+        assert_eq!(
+            fs.getattr(&TestUniqRequest {}, FIXED_INODE_MEDIA)?.ttl,
+            ttl(1000)
+        );
+        assert_eq!(fs.getattr(&TestUniqRequest {}, album_inode)?.ttl, ttl(100));
+        assert_eq!(
+            fs.getattr(&TestUniqRequest {}, media_item_inode)?.ttl,
+            ttl(10)
+        );
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn lookup_negative_disabled() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let config = PhotoFsConfig {
+            negative_ttl_seconds: 0,
+            ..PhotoFsConfig::default()
+        };
+        let fs = PhotoFs::with_config(photo_lib.clone(), photo_db.clone(), config);
+
+        assert_eq!(
+            fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_ROOT,
+                OsStr::new("Thumbs.db")
+            )
+            .unwrap_err(),
+            FuseError::FunctionNotImplemented
+        );
+
+        Result::Ok(())
+    }
+
     #[test]
     fn getattr_static() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
         {
             let response = fs.getattr(&TestUniqRequest {}, FIXED_INODE_ROOT)?;
@@ -793,320 +1801,1224 @@ mod test {
     }
 
     #[test]
-    fn getattr_dynamic() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+    fn access_allowed_users() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let config = PhotoFsConfig {
+            allowed_uids: vec![2001],
+            allowed_gids: vec![3001],
+            ..PhotoFsConfig::default()
+        };
+        let fs = PhotoFs::with_config(photo_lib.clone(), photo_db.clone(), config);
+
+        let owner = RequestInfo::new(0, users::get_current_uid(), 0, 0);
+        let allowed_user = RequestInfo::new(0, 2001, 2001, 0);
+        let allowed_group = RequestInfo::new(0, 2003, 3001, 0);
+        let other = RequestInfo::new(0, 2002, 2002, 0);
+
+        for req in &[owner, allowed_user, allowed_group] {
+            assert_eq!(fs.access(req, FIXED_INODE_ROOT, 0), Ok(()));
+            let fh = fs.opendir(req, FIXED_INODE_ROOT, 0)?.fh;
+            fs.releasedir(req, FIXED_INODE_ROOT, fh, 0)?;
+            let fh = fs.open(req, FIXED_INODE_HELLO_WORLD, 0)?.fh;
+            fs.release(req, FIXED_INODE_HELLO_WORLD, fh, 0, 0, false)?;
+        }
+
+        let denied = FuseError::PermissionDenied;
+        assert_eq!(fs.access(&other, FIXED_INODE_ROOT, 0), Err(denied));
+        assert_eq!(fs.opendir(&other, FIXED_INODE_ROOT, 0), Err(denied));
+        assert_eq!(fs.open(&other, FIXED_INODE_HELLO_WORLD, 0), Err(denied));
+
+        // Names, attributes and xattrs are not given away either
+        let name = OsStr::new("albums");
+        let denied_some = Option::Some(denied);
+        assert_eq!(fs.lookup(&other, FIXED_INODE_ROOT, name).err(), denied_some);
+        assert_eq!(fs.getattr(&other, FIXED_INODE_ROOT).err(), denied_some);
+        assert_eq!(fs.readlink(&other, FIXED_INODE_ROOT), Err(denied));
+        assert_eq!(fs.listxattr(&other, FIXED_INODE_ROOT), Err(denied));
+        let xattr = OsStr::new(XATTR_SHARED_IS_OWNED);
+        assert_eq!(fs.getxattr(&other, FIXED_INODE_ROOT, xattr), Err(denied));
+
+        // Supplementary groups count as well
+        if let Option::Some(gid) = supplementary_groups(std::process::id()).first() {
+            let config = PhotoFsConfig {
+                allowed_gids: vec![*gid],
+                ..PhotoFsConfig::default()
+            };
+            let fs = PhotoFs::with_config(photo_lib.clone(), photo_db.clone(), config);
+            let member = RequestInfo::new(0, 2003, 2003, std::process::id());
+            assert_eq!(fs.access(&member, FIXED_INODE_ROOT, 0), Ok(()));
+        }
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn access_without_allowed_users() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        // Only the mounting user, even on an allow_other mount
+        let owner = RequestInfo::new(0, users::get_current_uid(), 0, 0);
+        assert_eq!(fs.access(&owner, FIXED_INODE_ROOT, 0), Ok(()));
+        assert!(fs.opendir(&owner, FIXED_INODE_ROOT, 0).is_ok());
+
+        let other = RequestInfo::new(0, 2002, 2002, std::process::id());
+        let denied = FuseError::PermissionDenied;
+        assert_eq!(fs.access(&other, FIXED_INODE_ROOT, 0), Err(denied));
+        assert_eq!(fs.opendir(&other, FIXED_INODE_ROOT, 0), Err(denied));
+        let denied_some = Option::Some(denied);
+        assert_eq!(fs.getattr(&other, FIXED_INODE_ROOT).err(), denied_some);
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn write_access() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let req = TestUniqRequest {};
+
+        // Without write scopes everything that would change something is refused
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let read_only = Err(FuseError::ReadOnlyFilesystem);
+        assert_eq!(fs.check_writable(&req), read_only);
+        assert_eq!(
+            fs.access(&req, FIXED_INODE_ROOT, libc::W_OK as u32),
+            read_only
+        );
+        for flags in &[libc::O_WRONLY, libc::O_RDWR, libc::O_RDONLY | libc::O_TRUNC] {
+            assert_eq!(
+                fs.open(&req, FIXED_INODE_HELLO_WORLD, *flags as u32),
+                Err(FuseError::ReadOnlyFilesystem)
+            );
+        }
+        assert_eq!(fs.access(&req, FIXED_INODE_ROOT, libc::R_OK as u32), Ok(()));
+        let fh = fs
+            .open(&req, FIXED_INODE_HELLO_WORLD, libc::O_RDONLY as u32)?
+            .fh;
+        fs.release(&req, FIXED_INODE_HELLO_WORLD, fh, 0, 0, false)?;
+
+        // With them existing files still can not be changed
+        let config = PhotoFsConfig {
+            writable: true,
+            ..PhotoFsConfig::default()
+        };
+        let fs = PhotoFs::with_config(photo_lib.clone(), photo_db.clone(), config);
+        assert_eq!(fs.check_writable(&req), Ok(()));
+        assert_eq!(fs.access(&req, FIXED_INODE_ROOT, libc::W_OK as u32), Ok(()));
+        assert_eq!(
+            fs.open(&req, FIXED_INODE_HELLO_WORLD, libc::O_WRONLY as u32),
+            Err(FuseError::PermissionDenied)
+        );
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn getattr_links_and_permissions() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let config = PhotoFsConfig {
+            thumbnail_sizes: vec![256],
+            uid: Option::Some(1234),
+            gid: Option::Some(5678),
+            file_mode: 0o640,
+            dir_mode: 0o750,
+            ..PhotoFsConfig::default()
+        };
+        let fs = PhotoFs::with_config(photo_lib.clone(), photo_db.clone(), config);
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let album_inode = photo_db.upsert_album("GoogleId1", "Album1", &now)?;
+        photo_db.upsert_album("GoogleId2", "Album2", &now)?;
+        let media_item_inode = photo_db.upsert_media_item("GoogleId3", "Photo1.jpg", &now)?;
+
+        let root = fs.getattr(&TestUniqRequest {}, FIXED_INODE_ROOT)?.attr;
+        assert_eq!((root.nlink, root.perm), (7, 0o750));
+        assert_eq!((root.uid, root.gid), (1234, 5678));
+        assert_eq!(
+            fs.getattr(&TestUniqRequest {}, FIXED_INODE_ALBUMS)?
+                .attr
+                .nlink,
+            4
+        );
+        assert_eq!(
+            fs.getattr(&TestUniqRequest {}, FIXED_INODE_THUMBNAILS)?
+                .attr
+                .nlink,
+            3
+        );
+        assert_eq!(fs.getattr(&TestUniqRequest {}, album_inode)?.attr.nlink, 2);
+        let lookup = fs.lookup(&TestUniqRequest {}, FIXED_INODE_ROOT, OsStr::new("albums"))?;
+        assert_eq!(lookup.attr.nlink, 4);
+
+        let media_item = fs.getattr(&TestUniqRequest {}, media_item_inode)?.attr;
+        assert_eq!((media_item.nlink, media_item.perm), (1, 0o640));
+        assert_eq!((media_item.uid, media_item.gid), (1234, 5678));
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn getattr_dynamic() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let media_item_inode = photo_db
+            .upsert_media_item("GoogleId1", "Photo1.jpg", &now)
+            .unwrap();
+        let album_inode = photo_db.upsert_album("GoogleId2", "Album1", &now).unwrap();
+
+        {
+            let response = fs.getattr(&TestUniqRequest {}, media_item_inode)?;
+
+            assert_eq!(response.attr.ino, media_item_inode);
+            assert_eq!(response.attr.kind, FileType::RegularFile);
+            assert_eq!(response.attr.size, 1024);
+        }
+
+        {
+            let response = fs.getattr(&TestUniqRequest {}, album_inode)?;
+
+            assert_eq!(response.attr.ino, album_inode);
+            assert_eq!(response.attr.kind, FileType::Directory);
+            assert_eq!(response.attr.size, 0);
+        }
+
+        {
+            assert!(fs.getattr(&TestUniqRequest {}, album_inode + 1).is_err());
+        }
+
+        photo_db
+            .upsert_media_item_in_album("GoogleId2", "GoogleId1")
+            .unwrap();
+
+        {
+            let response = fs.getattr(&TestUniqRequest {}, album_inode)?;
+
+            assert_eq!(response.attr.size, 1);
+        }
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn open_read_release_hello_txt() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let fh = fs.open(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, 0)?.fh;
+
+        {
+            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, fh, 0, 13)?;
+
+            assert_eq!(response.data, b"Hello World!\n");
+        }
+
+        assert!(fs
+            .release(
+                &TestUniqRequest {},
+                FIXED_INODE_HELLO_WORLD,
+                fh,
+                0,
+                0,
+                false
+            )
+            .is_ok());
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn read_offset() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let fh = fs.open(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, 0)?.fh;
+
+        {
+            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, fh, 0, 13)?;
+            assert_eq!(response.data, b"Hello World!\n");
+        }
+
+        {
+            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, fh, 1, 12)?;
+            assert_eq!(response.data, b"ello World!\n");
+        }
+
+        {
+            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, fh, 12, 1)?;
+            assert_eq!(response.data, b"\n");
+        }
+
+        // Offset past the end of the file
+        {
+            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, fh, 13, 1)?;
+            assert_eq!(response.data, b"");
+        }
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn read_size() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let open = fs.open(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, 0)?;
+
+        {
+            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, open.fh, 0, 13)?;
+            assert_eq!(response.data, b"Hello World!\n");
+        }
+
+        {
+            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, open.fh, 0, 5)?;
+            assert_eq!(response.data, b"Hello");
+        }
+
+        {
+            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, open.fh, 0, 15)?;
+            assert_eq!(response.data, b"Hello World!\n");
+            // The kernel serves repeated reads from its page cache, the size is known
+            assert_eq!(open.flags, fuse::consts::FOPEN_KEEP_CACHE);
+        }
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn read_media_item() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let inode: Inode;
+        {
+            photo_lib
+                .test_data
+                .lock()
+                .unwrap()
+                .insert("GoogleId1", vec![65, 66, 67]);
+
+            let now_unix = Utc::now().timestamp();
+            let now = Utc::timestamp(&Utc, now_unix, 0);
+            inode = photo_db
+                .upsert_media_item(&String::from("GoogleId1"), &String::from("Photo 1"), &now)
+                .unwrap();
+        }
+
+        // read real file
+        {
+            let open = fs.open(&TestUniqRequest {}, inode, 0)?;
+            let response = fs.read(&TestUniqRequest {}, inode, open.fh, 0, 5)?;
+            assert_eq!(response.data, b"ABC");
+        }
+
+        // read unknown inode or fh
+        {
+            let open = fs.open(&TestUniqRequest {}, inode, 0)?;
+            assert!(fs
+                .read(&TestUniqRequest {}, inode + 1, open.fh, 0, 5)
+                .is_err());
+            assert!(fs
+                .read(&TestUniqRequest {}, inode, open.fh + 1, 0, 5)
+                .is_err());
+        }
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn open_shares_downloaded_content() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        photo_lib
+            .test_data
+            .lock()
+            .unwrap()
+            .insert("GoogleId1", vec![65, 66, 67]);
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let inode = photo_db.upsert_media_item("GoogleId1", "Photo1.jpg", &now)?;
+
+        let first = fs.open(&TestUniqRequest {}, inode, 0)?;
+        let second = fs.open(&TestUniqRequest {}, inode, 0)?;
+        assert_ne!(first.fh, second.fh);
+        assert_eq!(photo_lib.downloads.load(Ordering::SeqCst), 1);
+
+        fs.release(&TestUniqRequest {}, inode, first.fh, 0, 0, false)?;
+        let response = fs.read(&TestUniqRequest {}, inode, second.fh, 0, 5)?;
+        assert_eq!(response.data, b"ABC");
+
+        // Still cached after all handles are closed
+        fs.release(&TestUniqRequest {}, inode, second.fh, 0, 0, false)?;
+        fs.open(&TestUniqRequest {}, inode, 0)?;
+        assert_eq!(photo_lib.downloads.load(Ordering::SeqCst), 1);
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn open_prefetches_next_media_items() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let mut inodes = Vec::new();
+        for &(google_id, name) in &[
+            ("GoogleId1", "Photo1.jpg"),
+            ("GoogleId2", "Photo2.jpg"),
+            ("GoogleId3", "Photo3.jpg"),
+        ] {
+            photo_lib
+                .test_data
+                .lock()
+                .unwrap()
+                .insert(google_id, vec![65, 66, 67]);
+            inodes.push(photo_db.upsert_media_item(google_id, name, &now)?);
+        }
+
+        let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_MEDIA, 0)?.fh;
+        fs.readdir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
+        fs.releasedir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
+
+        fs.open(&TestUniqRequest {}, inodes[0], 0)?;
+        assert_eq!(fs.fetcher.known_size(inodes[2]), Option::None);
+        fs.open(&TestUniqRequest {}, inodes[1], 0)?;
+
+        // Downloaded in the background
+        for _ in 0..100 {
+            if fs.fetcher.known_size(inodes[2]).is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(fs.fetcher.known_size(inodes[2]), Option::Some(3));
+        assert_eq!(photo_lib.downloads.load(Ordering::SeqCst), 3);
+
+        fs.open(&TestUniqRequest {}, inodes[2], 0)?;
+        assert_eq!(photo_lib.downloads.load(Ordering::SeqCst), 3);
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn open_uses_page_cache_once_size_is_reported() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        photo_lib
+            .test_data
+            .lock()
+            .unwrap()
+            .insert("GoogleId1", vec![65, 66, 67]);
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let inode = photo_db.upsert_media_item("GoogleId1", "Photo1.jpg", &now)?;
+
+        // Only the placeholder size was reported so far
+        assert_eq!(
+            fs.getattr(&TestUniqRequest {}, inode)?.attr.size,
+            DEFAULT_MEDIA_ITEM_SIZE as u64
+        );
+        let open = fs.open(&TestUniqRequest {}, inode, 0)?;
+        assert_eq!(open.flags, fuse::consts::FOPEN_DIRECT_IO);
+
+        assert_eq!(fs.getattr(&TestUniqRequest {}, inode)?.attr.size, 3);
+        let open = fs.open(&TestUniqRequest {}, inode, 0)?;
+        assert_eq!(open.flags, fuse::consts::FOPEN_KEEP_CACHE);
+
+        // Sidecars use the page cache but do not keep it between opens
+        let sidecar = view_inode(inode, InodeView::JsonSidecar);
+        let size = fs.getattr(&TestUniqRequest {}, sidecar)?.attr.size;
+        let open = fs.open(&TestUniqRequest {}, sidecar, 0)?;
+        assert_eq!(open.flags, 0);
+        let response = fs.read(&TestUniqRequest {}, sidecar, open.fh, 0, size as u32)?;
+        assert_eq!(response.data.len() as u64, size);
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn opendir_multiple_calls() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let response1 = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ROOT, 0)?;
+        let response2 = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ROOT, 0)?;
+
+        assert_eq!(response1.fh, 0);
+        assert_eq!(response2.fh, 1);
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn readdir_root() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ROOT, 0)?.fh;
+
+        let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_ROOT, fh, 0)?;
+
+        assert_eq!(response.entries.len(), 7);
+        assert_eq!(response.entries[0].ino, FIXED_INODE_ROOT);
+        assert_eq!(response.entries[1].ino, FIXED_INODE_ALBUMS);
+        assert_eq!(response.entries[2].ino, FIXED_INODE_MEDIA);
+        assert_eq!(response.entries[3].ino, FIXED_INODE_CATEGORIES);
+        assert_eq!(response.entries[4].ino, FIXED_INODE_SHARED_ALBUMS);
+        assert_eq!(response.entries[5].ino, FIXED_INODE_THUMBNAILS);
+        assert_eq!(response.entries[6].ino, FIXED_INODE_HELLO_WORLD);
+
+        // Continues after the entry of the given offset
+        let offset = response.entries[3].offset;
+        let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_ROOT, fh, offset)?;
+        assert_eq!(response.entries.len(), 3);
+        assert_eq!(response.entries[0].ino, FIXED_INODE_SHARED_ALBUMS);
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn readdir_albums() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+
+        {
+            let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_MEDIA, 0)?.fh;
+
+            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
+            assert_eq!(response.entries.len(), 2);
+            assert_eq!(response.entries[0].ino, FIXED_INODE_MEDIA);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
+
+            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
+        }
+
+        let media_item_inode = photo_db
+            .upsert_media_item(&String::from("GoogleId1"), &String::from("Photo 1"), &now)
+            .unwrap();
+
+        {
+            let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_MEDIA, 0)?.fh;
+
+            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
+            assert_eq!(response.entries.len(), 4);
+            assert_eq!(response.entries[0].ino, FIXED_INODE_MEDIA);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
+            assert_eq!(response.entries[2].ino, media_item_inode);
+            assert_eq!(response.entries[3].name, OsStr::new(".Photo 1.json"));
+
+            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
+        }
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn readdir_media_items() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+
+        {
+            let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, 0)?.fh;
+
+            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
+            assert_eq!(response.entries.len(), 2);
+            assert_eq!(response.entries[0].ino, FIXED_INODE_ALBUMS);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
+
+            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
+        }
+
+        let album_inode = photo_db.upsert_album("GoogleId2", "Album1", &now).unwrap();
+
+        {
+            let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, 0)?.fh;
+
+            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
+            assert_eq!(response.entries.len(), 3);
+            assert_eq!(response.entries[0].ino, FIXED_INODE_ALBUMS);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
+            assert_eq!(response.entries[2].ino, album_inode);
+
+            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
+        }
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn readdir_media_items_in_albums() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+
+        {
+            let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, 0)?.fh;
+
+            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
+            assert_eq!(response.entries.len(), 2);
+            assert_eq!(response.entries[0].ino, FIXED_INODE_ALBUMS);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
+
+            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
+        }
+
+        let album_inode = photo_db.upsert_album("GoogleId1", "Album1", &now).unwrap();
+        let media_item_inode = photo_db
+            .upsert_media_item(&String::from("GoogleId2"), &String::from("Photo 1"), &now)
+            .unwrap();
+        photo_db.upsert_media_item(&String::from("GoogleId3"), &String::from("Photo 2"), &now)?;
+        photo_db.upsert_media_item_in_album("GoogleId1", "GoogleId2")?;
+
+        {
+            let fh = fs.opendir(&TestUniqRequest {}, album_inode, 0)?.fh;
+
+            let response = fs.readdir(&TestUniqRequest {}, album_inode, fh, 0)?;
+            assert_eq!(response.entries.len(), 4);
+            assert_eq!(response.entries[0].ino, album_inode);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_ALBUMS);
+            assert_eq!(response.entries[2].ino, media_item_inode);
+
+            fs.releasedir(&TestUniqRequest {}, album_inode, fh, 0)?;
+        }
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn album_symlinks() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let config = PhotoFsConfig {
+            album_symlinks: true,
+            ..PhotoFsConfig::default()
+        };
+        let fs = PhotoFs::with_config(photo_lib.clone(), photo_db.clone(), config);
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let album_inode = photo_db.upsert_album("GoogleId1", "Album1", &now)?;
+        let media_item_inode = photo_db.upsert_media_item("GoogleId2", "Photo1.jpg", &now)?;
+        photo_db.upsert_media_item_in_album("GoogleId1", "GoogleId2")?;
+
+        {
+            let fh = fs.opendir(&TestUniqRequest {}, album_inode, 0)?.fh;
+
+            let response = fs.readdir(&TestUniqRequest {}, album_inode, fh, 0)?;
+            assert_eq!(response.entries.len(), 4);
+            assert_eq!(response.entries[2].kind, FileType::Symlink);
+            assert_eq!(response.entries[2].name, OsStr::new("Photo1.jpg"));
+            assert_ne!(response.entries[2].ino, media_item_inode);
+            assert_eq!(response.entries[3].kind, FileType::Symlink);
+            assert_eq!(response.entries[3].name, OsStr::new(".Photo1.jpg.json"));
+
+            fs.releasedir(&TestUniqRequest {}, album_inode, fh, 0)?;
+        }
+
+        let link = fs.lookup(&TestUniqRequest {}, album_inode, OsStr::new("Photo1.jpg"))?;
+        assert_eq!(link.attr.kind, FileType::Symlink);
+        assert_eq!(link.attr.size, "../../media/Photo1.jpg".len() as u64);
+        assert_eq!(
+            fs.getattr(&TestUniqRequest {}, link.attr.ino)?.attr.kind,
+            FileType::Symlink
+        );
+        assert_eq!(
+            fs.readlink(&TestUniqRequest {}, link.attr.ino)?.target,
+            OsString::from("../../media/Photo1.jpg")
+        );
+        assert!(fs.open(&TestUniqRequest {}, link.attr.ino, 0).is_err());
+
+        let sidecar = fs.lookup(
+            &TestUniqRequest {},
+            album_inode,
+            OsStr::new(".Photo1.jpg.json"),
+        )?;
+        assert_eq!(
+            fs.readlink(&TestUniqRequest {}, sidecar.attr.ino)?.target,
+            OsString::from("../../media/.Photo1.jpg.json")
+        );
+
+        // /media still holds the files themselves
+        let media = fs.lookup(
+            &TestUniqRequest {},
+            FIXED_INODE_MEDIA,
+            OsStr::new("Photo1.jpg"),
+        )?;
+        assert_eq!(media.attr.ino, media_item_inode);
+        assert_eq!(media.attr.kind, FileType::RegularFile);
+        assert!(fs.readlink(&TestUniqRequest {}, media_item_inode).is_err());
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn album_symlinks_disabled() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let media_item_inode = photo_db.upsert_media_item("GoogleId2", "Photo1.jpg", &now)?;
+        let link_inode = view_inode(media_item_inode, InodeView::OriginalLink);
+        assert!(fs.getattr(&TestUniqRequest {}, link_inode).is_err());
+        assert!(fs.readlink(&TestUniqRequest {}, link_inode).is_err());
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn readdir_media_items_in_pages() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let media_items = READDIR_PAGE_SIZE + 10;
+        for index in 0..media_items {
+            photo_db.upsert_media_item(
+                &format!("GoogleId{}", index),
+                &format!("Photo{}.jpg", index),
+                &now,
+            )?;
+        }
+
+        let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_MEDIA, 0)?.fh;
+
+        let first_page = fs.readdir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
+        assert_eq!(first_page.entries.len(), 2 + READDIR_PAGE_SIZE * 2);
+        assert_eq!(first_page.entries[2].name, OsStr::new("Photo0.jpg"));
+        assert_eq!(first_page.entries[3].name, OsStr::new(".Photo0.jpg.json"));
+
+        // Continue after a media item, whose sidecar did not fit anymore
+        let resume = &first_page.entries[4];
+        assert_eq!(resume.name, OsStr::new("Photo1.jpg"));
+        let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, resume.offset)?;
+        assert_eq!(response.entries[0].name, OsStr::new(".Photo1.jpg.json"));
+        assert_eq!(response.entries[1].name, OsStr::new("Photo2.jpg"));
+
+        // Items changed by the background update do not shift the offsets
+        let last = first_page.entries.last().unwrap();
+        photo_db.upsert_media_item("GoogleId0", "Photo0 new name.jpg", &now)?;
+        let second_page = fs.readdir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, last.offset)?;
+        assert_eq!(second_page.entries.len(), 10 * 2);
+        assert_eq!(
+            second_page.entries[0].name,
+            OsString::from(format!("Photo{}.jpg", READDIR_PAGE_SIZE))
+        );
+
+        let last = second_page.entries.last().unwrap();
+        let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, last.offset)?;
+        assert!(response.entries.is_empty());
+
+        fs.releasedir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn readdir_shared_albums() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
+        let photo_db = Arc::new(SqliteDb::in_memory()?);
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let share_info = SharedAlbumInfo::new(false, true, true);
+
+        photo_db.upsert_album("GoogleId1", "Album1", &now)?;
+        let shared_album_inode =
+            photo_db.upsert_shared_album("GoogleId2", "Shared1", &share_info, &now)?;
+        let media_item_inode = photo_db.upsert_media_item("GoogleId3", "Photo1.jpg", &now)?;
+        photo_db.upsert_media_item_in_album("GoogleId2", "GoogleId3")?;
+
+        {
+            let fh = fs
+                .opendir(&TestUniqRequest {}, FIXED_INODE_SHARED_ALBUMS, 0)?
+                .fh;
+
+            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_SHARED_ALBUMS, fh, 0)?;
+            assert_eq!(response.entries.len(), 3);
+            assert_eq!(response.entries[0].ino, FIXED_INODE_SHARED_ALBUMS);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
+            assert_eq!(response.entries[2].ino, shared_album_inode);
+
+            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_SHARED_ALBUMS, fh, 0)?;
+        }
+
+        {
+            let fh = fs.opendir(&TestUniqRequest {}, shared_album_inode, 0)?.fh;
+
+            let response = fs.readdir(&TestUniqRequest {}, shared_album_inode, fh, 0)?;
+            assert_eq!(response.entries.len(), 4);
+            assert_eq!(response.entries[0].ino, shared_album_inode);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_SHARED_ALBUMS);
+            assert_eq!(response.entries[2].ino, media_item_inode);
+
+            fs.releasedir(&TestUniqRequest {}, shared_album_inode, fh, 0)?;
+        }
+
+        {
+            let response = fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_SHARED_ALBUMS,
+                OsStr::new("Shared1"),
+            )?;
+            assert_eq!(response.attr.ino, shared_album_inode);
+            assert_eq!(response.attr.kind, FileType::Directory);
+            assert_eq!(response.attr.size, 1);
+
+            assert!(is_negative(fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_SHARED_ALBUMS,
+                OsStr::new("Album1")
+            )));
+            assert!(is_negative(fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_SHARED_ALBUMS,
+                OsStr::from_bytes(b"Shared1\xff")
+            )));
+
+            let response = fs.lookup(
+                &TestUniqRequest {},
+                shared_album_inode,
+                OsStr::new("Photo1.jpg"),
+            )?;
+            assert_eq!(response.attr.ino, media_item_inode);
+        }
+
+        // A failing DB is an I/O error, not a missing name
+        photo_db.close()?;
+        let response = fs.lookup(
+            &TestUniqRequest {},
+            FIXED_INODE_SHARED_ALBUMS,
+            OsStr::new("Shared1"),
+        );
+        assert_eq!(response.err(), Option::Some(FuseError::IoError));
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn xattr_shared_albums() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
         let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
-        let media_item_inode = photo_db
-            .upsert_media_item("GoogleId1", "Photo1.jpg", &now)
-            .unwrap();
-        let album_inode = photo_db.upsert_album("GoogleId2", "Album1", &now).unwrap();
-
-        {
-            let response = fs.getattr(&TestUniqRequest {}, media_item_inode)?;
+        let share_info = SharedAlbumInfo::new(false, true, true);
 
-            assert_eq!(response.attr.ino, media_item_inode);
-            assert_eq!(response.attr.kind, FileType::RegularFile);
-            assert_eq!(response.attr.size, 1024);
-        }
+        let album_inode = photo_db.upsert_album("GoogleId1", "Album1", &now)?;
+        let shared_album_inode =
+            photo_db.upsert_shared_album("GoogleId2", "Shared1", &share_info, &now)?;
 
         {
-            let response = fs.getattr(&TestUniqRequest {}, album_inode)?;
+            let response = fs.listxattr(&TestUniqRequest {}, shared_album_inode)?;
+            assert_eq!(
+                response.data,
+                b"user.photooxide.shared.is_owned\0user.photooxide.shared.is_joined\0user.photooxide.shared.is_collaborative\0".to_vec()
+            );
 
-            assert_eq!(response.attr.ino, album_inode);
-            assert_eq!(response.attr.kind, FileType::Directory);
-            assert_eq!(response.attr.size, 0);
-        }
+            let response = fs.getxattr(
+                &TestUniqRequest {},
+                shared_album_inode,
+                OsStr::new(XATTR_SHARED_IS_OWNED),
+            )?;
+            assert_eq!(response.data, b"false".to_vec());
 
-        {
-            assert!(fs.getattr(&TestUniqRequest {}, album_inode + 1).is_err());
-        }
+            let response = fs.getxattr(
+                &TestUniqRequest {},
+                shared_album_inode,
+                OsStr::new(XATTR_SHARED_IS_COLLABORATIVE),
+            )?;
+            assert_eq!(response.data, b"true".to_vec());
 
-        photo_db
-            .upsert_media_item_in_album("GoogleId2", "GoogleId1")
-            .unwrap();
+            assert_eq!(
+                fs.getxattr(
+                    &TestUniqRequest {},
+                    shared_album_inode,
+                    OsStr::new("user.unknown")
+                ),
+                Result::Err(FuseError::NoAttribute)
+            );
+        }
 
         {
-            let response = fs.getattr(&TestUniqRequest {}, album_inode)?;
+            let response = fs.listxattr(&TestUniqRequest {}, album_inode)?;
+            assert!(response.data.is_empty());
 
-            assert_eq!(response.attr.size, 1);
+            assert_eq!(
+                fs.getxattr(
+                    &TestUniqRequest {},
+                    album_inode,
+                    OsStr::new(XATTR_SHARED_IS_OWNED)
+                ),
+                Result::Err(FuseError::NoAttribute)
+            );
         }
 
         Result::Ok(())
     }
 
     #[test]
-    fn open_read_release_hello_txt() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+    fn readdir_categories() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
-        let fh = fs.open(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, 0)?.fh;
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
 
         {
-            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, fh, 0, 13)?;
+            let fh = fs
+                .opendir(&TestUniqRequest {}, FIXED_INODE_CATEGORIES, 0)?
+                .fh;
 
-            assert_eq!(response.data, b"Hello World!\n");
-        }
+            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_CATEGORIES, fh, 0)?;
+            assert_eq!(response.entries.len(), 2);
+            assert_eq!(response.entries[0].ino, FIXED_INODE_CATEGORIES);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
 
-        assert!(fs
-            .release(
-                &TestUniqRequest {},
-                FIXED_INODE_HELLO_WORLD,
-                fh,
-                0,
-                0,
-                false
-            )
-            .is_ok());
+            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_CATEGORIES, fh, 0)?;
+        }
 
-        Result::Ok(())
-    }
+        let media_item_inode = photo_db.upsert_media_item("GoogleId1", "Receipt1.jpg", &now)?;
+        photo_db.upsert_media_item("GoogleId2", "Photo1.jpg", &now)?;
+        photo_db.set_media_items_in_category(ContentCategory::Receipts, &["GoogleId1"])?;
+        let receipts_inode = category_inode(ContentCategory::Receipts);
 
-    #[test]
-    fn read_offset() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
-        let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        {
+            let fh = fs
+                .opendir(&TestUniqRequest {}, FIXED_INODE_CATEGORIES, 0)?
+                .fh;
 
-        let fh = fs.open(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, 0)?.fh;
+            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_CATEGORIES, fh, 0)?;
+            assert_eq!(response.entries.len(), 3);
+            assert_eq!(response.entries[2].ino, receipts_inode);
+            assert_eq!(response.entries[2].name, OsStr::new("receipts"));
 
-        {
-            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, fh, 0, 13)?;
-            assert_eq!(response.data, b"Hello World!\n");
+            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_CATEGORIES, fh, 0)?;
         }
 
         {
-            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, fh, 1, 12)?;
-            assert_eq!(response.data, b"ello World!\n");
-        }
+            let fh = fs.opendir(&TestUniqRequest {}, receipts_inode, 0)?.fh;
 
-        {
-            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, fh, 12, 1)?;
-            assert_eq!(response.data, b"\n");
-        }
+            let response = fs.readdir(&TestUniqRequest {}, receipts_inode, fh, 0)?;
+            assert_eq!(response.entries.len(), 4);
+            assert_eq!(response.entries[0].ino, receipts_inode);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_CATEGORIES);
+            assert_eq!(response.entries[2].ino, media_item_inode);
 
-        // Offset past the end of the file
-        {
-            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, fh, 13, 1)?;
-            assert_eq!(response.data, b"");
+            fs.releasedir(&TestUniqRequest {}, receipts_inode, fh, 0)?;
         }
 
         Result::Ok(())
     }
 
     #[test]
-    fn read_size() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+    fn readdir_thumbnails() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let config = PhotoFsConfig {
+            thumbnail_sizes: vec![1024, 256],
+            ..PhotoFsConfig::default()
+        };
+        let fs = PhotoFs::with_config(photo_lib.clone(), photo_db.clone(), config);
 
-        let open = fs.open(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, 0)?;
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let media_item_inode = photo_db.upsert_media_item("GoogleId1", "Photo1.jpg", &now)?;
+        photo_lib
+            .test_data
+            .lock()
+            .unwrap()
+            .insert("GoogleId1", vec![65, 66, 67]);
 
         {
-            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, open.fh, 0, 13)?;
-            assert_eq!(response.data, b"Hello World!\n");
-        }
+            let fh = fs
+                .opendir(&TestUniqRequest {}, FIXED_INODE_THUMBNAILS, 0)?
+                .fh;
 
-        {
-            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, open.fh, 0, 5)?;
-            assert_eq!(response.data, b"Hello");
+            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_THUMBNAILS, fh, 0)?;
+            assert_eq!(response.entries.len(), 4);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
+            assert_eq!(response.entries[2].name, OsStr::new("256"));
+            assert_eq!(response.entries[3].name, OsStr::new("1024"));
+
+            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_THUMBNAILS, fh, 0)?;
         }
 
+        let size_inode = fs
+            .lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_THUMBNAILS,
+                OsStr::new("256"),
+            )?
+            .attr
+            .ino;
+        assert!(is_negative(fs.lookup(
+            &TestUniqRequest {},
+            FIXED_INODE_THUMBNAILS,
+            OsStr::new("512")
+        )));
+        assert!(is_negative(fs.lookup(
+            &TestUniqRequest {},
+            FIXED_INODE_THUMBNAILS,
+            OsStr::from_bytes(b"256\xff")
+        )));
+
+        let thumbnail_inode;
         {
-            let response = fs.read(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, open.fh, 0, 15)?;
-            assert_eq!(response.data, b"Hello World!\n");
-            assert_eq!(open.flags, 1); // assert direct IO or the response should be zero padded
-        }
+            let fh = fs.opendir(&TestUniqRequest {}, size_inode, 0)?.fh;
 
-        Result::Ok(())
-    }
+            let response = fs.readdir(&TestUniqRequest {}, size_inode, fh, 0)?;
+            assert_eq!(response.entries.len(), 3);
+            assert_eq!(response.entries[1].ino, FIXED_INODE_THUMBNAILS);
+            assert_eq!(response.entries[2].name, OsStr::new("Photo1.jpg"));
+            thumbnail_inode = response.entries[2].ino;
+            assert_ne!(thumbnail_inode, media_item_inode);
 
-    #[test]
-    fn read_media_item() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
-        let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+            fs.releasedir(&TestUniqRequest {}, size_inode, fh, 0)?;
+        }
 
-        let inode: Inode;
         {
-            let mut lib = photo_lib.lock().unwrap();
-            lib.test_data.insert("GoogleId1", vec![65, 66, 67]);
+            let response = fs.lookup(&TestUniqRequest {}, size_inode, OsStr::new("Photo1.jpg"))?;
+            assert_eq!(response.attr.ino, thumbnail_inode);
+            assert_eq!(response.attr.kind, FileType::RegularFile);
 
-            let now_unix = Utc::now().timestamp();
-            let now = Utc::timestamp(&Utc, now_unix, 0);
-            inode = photo_db
-                .upsert_media_item(&String::from("GoogleId1"), &String::from("Photo 1"), &now)
-                .unwrap();
+            let response = fs.getattr(&TestUniqRequest {}, thumbnail_inode)?;
+            assert_eq!(response.attr.ino, thumbnail_inode);
         }
 
-        // read real file
+        // The thumbnail and the original are fetched as different variants
         {
-            let open = fs.open(&TestUniqRequest {}, inode, 0)?;
-            let response = fs.read(&TestUniqRequest {}, inode, open.fh, 0, 5)?;
+            let open = fs.open(&TestUniqRequest {}, thumbnail_inode, 0)?;
+            let response = fs.read(&TestUniqRequest {}, thumbnail_inode, open.fh, 0, 10)?;
+            assert_eq!(response.data, b"256x256");
+
+            let open = fs.open(&TestUniqRequest {}, media_item_inode, 0)?;
+            let response = fs.read(&TestUniqRequest {}, media_item_inode, open.fh, 0, 10)?;
             assert_eq!(response.data, b"ABC");
         }
 
-        // read unknown inode or fh
+        // Views of sizes that are not configured do not exist
         {
-            let open = fs.open(&TestUniqRequest {}, inode, 0)?;
-            assert!(fs
-                .read(&TestUniqRequest {}, inode + 1, open.fh, 0, 5)
-                .is_err());
-            assert!(fs
-                .read(&TestUniqRequest {}, inode, open.fh + 1, 0, 5)
-                .is_err());
+            let unknown_inode = view_inode(media_item_inode, InodeView::Thumbnail(2));
+            assert!(fs.getattr(&TestUniqRequest {}, unknown_inode).is_err());
+            assert!(fs.open(&TestUniqRequest {}, unknown_inode, 0).is_err());
         }
 
         Result::Ok(())
     }
 
     #[test]
-    fn opendir_multiple_calls() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+    fn read_json_sidecar() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
-        let response1 = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ROOT, 0)?;
-        let response2 = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ROOT, 0)?;
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let album_inode = photo_db.upsert_album("GoogleId1", "Album1", &now)?;
+        let media_item_inode = photo_db.upsert_media_item("GoogleId2", "Photo1.jpg", &now)?;
+        photo_db.upsert_media_item_in_album("GoogleId1", "GoogleId2")?;
+        photo_db.upsert_media_item_metadata(
+            "GoogleId2",
+            &MediaItemMetadata {
+                description: Option::Some(String::from("At the beach")),
+                ..MediaItemMetadata::default()
+            },
+        )?;
 
-        assert_eq!(response1.fh, 0);
-        assert_eq!(response2.fh, 1);
+        let sidecar_inode;
+        {
+            let fh = fs.opendir(&TestUniqRequest {}, album_inode, 0)?.fh;
 
-        Result::Ok(())
-    }
+            let response = fs.readdir(&TestUniqRequest {}, album_inode, fh, 0)?;
+            assert_eq!(response.entries.len(), 4);
+            assert_eq!(response.entries[2].ino, media_item_inode);
+            assert_eq!(response.entries[3].name, OsStr::new(".Photo1.jpg.json"));
+            assert_eq!(response.entries[3].kind, FileType::RegularFile);
+            sidecar_inode = response.entries[3].ino;
 
-    #[test]
-    fn readdir_root() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
-        let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+            fs.releasedir(&TestUniqRequest {}, album_inode, fh, 0)?;
+        }
 
-        let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ROOT, 0)?.fh;
+        let size;
+        {
+            let response = fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_MEDIA,
+                OsStr::new(".Photo1.jpg.json"),
+            )?;
+            assert_eq!(response.attr.ino, sidecar_inode);
+            size = response.attr.size;
 
-        let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_ROOT, fh, 0)?;
+            let response = fs.getattr(&TestUniqRequest {}, sidecar_inode)?;
+            assert_eq!(response.attr.size, size);
 
-        assert_eq!(response.entries.len(), 4);
-        assert_eq!(response.entries[0].ino, FIXED_INODE_ROOT);
-        assert_eq!(response.entries[1].ino, FIXED_INODE_ALBUMS);
-        assert_eq!(response.entries[2].ino, FIXED_INODE_MEDIA);
-        assert_eq!(response.entries[3].ino, FIXED_INODE_HELLO_WORLD);
+            assert!(is_negative(fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_MEDIA,
+                OsStr::new(".Photo2.jpg.json")
+            )));
+        }
+
+        // Served without touching the remote
+        {
+            let open = fs.open(&TestUniqRequest {}, sidecar_inode, 0)?;
+            let response = fs.read(&TestUniqRequest {}, sidecar_inode, open.fh, 0, 4096)?;
+            assert_eq!(response.data.len() as u64, size);
+
+            let sidecar: serde_json::Value = serde_json::from_slice(&response.data).unwrap();
+            assert_eq!(sidecar["title"], "Photo1.jpg");
+            assert_eq!(sidecar["description"], "At the beach");
+            assert_eq!(sidecar["albums"], serde_json::json!(["Album1"]));
+        }
 
         Result::Ok(())
     }
 
     #[test]
-    fn readdir_albums() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+    fn read_xmp_sidecar() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
         let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let album_inode = photo_db.upsert_album("GoogleId1", "Album1", &now)?;
+        photo_db.upsert_media_item("GoogleId2", "Photo1.jpg", &now)?;
+        photo_db.upsert_media_item_in_album("GoogleId1", "GoogleId2")?;
+        photo_db.set_favourite_media_items(&["GoogleId2"])?;
 
+        // Off by default
         {
-            let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_MEDIA, 0)?.fh;
-
-            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
-            assert_eq!(response.entries.len(), 2);
-            assert_eq!(response.entries[0].ino, FIXED_INODE_MEDIA);
-            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
+            let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+            let fh = fs.opendir(&TestUniqRequest {}, album_inode, 0)?.fh;
+            let response = fs.readdir(&TestUniqRequest {}, album_inode, fh, 0)?;
+            assert_eq!(response.entries.len(), 4);
 
-            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
+            assert!(is_negative(fs.lookup(
+                &TestUniqRequest {},
+                album_inode,
+                OsStr::new("Photo1.jpg.xmp")
+            )));
         }
 
-        let media_item_inode = photo_db
-            .upsert_media_item(&String::from("GoogleId1"), &String::from("Photo 1"), &now)
-            .unwrap();
+        let config = PhotoFsConfig {
+            xmp_sidecars: true,
+            ..PhotoFsConfig::default()
+        };
+        let fs = PhotoFs::with_config(photo_lib.clone(), photo_db.clone(), config);
 
+        let sidecar_inode;
         {
-            let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_MEDIA, 0)?.fh;
+            let fh = fs.opendir(&TestUniqRequest {}, album_inode, 0)?.fh;
 
-            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
-            assert_eq!(response.entries.len(), 3);
-            assert_eq!(response.entries[0].ino, FIXED_INODE_MEDIA);
-            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
-            assert_eq!(response.entries[2].ino, media_item_inode);
+            let response = fs.readdir(&TestUniqRequest {}, album_inode, fh, 0)?;
+            assert_eq!(response.entries.len(), 5);
+            assert_eq!(response.entries[4].name, OsStr::new("Photo1.jpg.xmp"));
+            sidecar_inode = response.entries[4].ino;
 
-            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_MEDIA, fh, 0)?;
+            fs.releasedir(&TestUniqRequest {}, album_inode, fh, 0)?;
         }
 
-        Result::Ok(())
-    }
-
-    #[test]
-    fn readdir_media_items() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
-        let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
-
-        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
-
+        let size;
         {
-            let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, 0)?.fh;
-
-            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
-            assert_eq!(response.entries.len(), 2);
-            assert_eq!(response.entries[0].ino, FIXED_INODE_ALBUMS);
-            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
-
-            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
+            let response = fs.lookup(
+                &TestUniqRequest {},
+                album_inode,
+                OsStr::new("Photo1.jpg.xmp"),
+            )?;
+            assert_eq!(response.attr.ino, sidecar_inode);
+            size = response.attr.size;
         }
 
-        let album_inode = photo_db.upsert_album("GoogleId2", "Album1", &now).unwrap();
-
         {
-            let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, 0)?.fh;
-
-            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
-            assert_eq!(response.entries.len(), 3);
-            assert_eq!(response.entries[0].ino, FIXED_INODE_ALBUMS);
-            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
-            assert_eq!(response.entries[2].ino, album_inode);
+            let open = fs.open(&TestUniqRequest {}, sidecar_inode, 0)?;
+            let response = fs.read(&TestUniqRequest {}, sidecar_inode, open.fh, 0, 4096)?;
+            assert_eq!(response.data.len() as u64, size);
 
-            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
+            let sidecar = String::from_utf8(response.data).unwrap();
+            assert!(sidecar.contains("xmp:Rating=\"5\""));
+            assert!(sidecar.contains("<rdf:li>Album1</rdf:li>"));
         }
 
         Result::Ok(())
     }
 
     #[test]
-    fn readdir_media_items_in_albums() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+    fn read_media_item_with_jpeg_metadata() -> Result<(), FuseError> {
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
+        let jpeg = vec![0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x02, 0xFF, 0xD9];
         let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
-
+        let inode = photo_db.upsert_media_item("GoogleId1", "Photo1.jpg", &now)?;
+        photo_db.upsert_media_item_metadata(
+            "GoogleId1",
+            &MediaItemMetadata {
+                description: Option::Some(String::from("At the beach")),
+                ..MediaItemMetadata::default()
+            },
+        )?;
+        photo_lib
+            .test_data
+            .lock()
+            .unwrap()
+            .insert("GoogleId1", jpeg.clone());
+
+        // Off by default
         {
-            let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, 0)?.fh;
-
-            let response = fs.readdir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
-            assert_eq!(response.entries.len(), 2);
-            assert_eq!(response.entries[0].ino, FIXED_INODE_ALBUMS);
-            assert_eq!(response.entries[1].ino, FIXED_INODE_ROOT);
-
-            fs.releasedir(&TestUniqRequest {}, FIXED_INODE_ALBUMS, fh, 0)?;
+            let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+            let open = fs.open(&TestUniqRequest {}, inode, 0)?;
+            let response = fs.read(&TestUniqRequest {}, inode, open.fh, 0, 4096)?;
+            assert_eq!(response.data, &jpeg[..]);
         }
 
-        let album_inode = photo_db.upsert_album("GoogleId1", "Album1", &now).unwrap();
-        let media_item_inode = photo_db
-            .upsert_media_item(&String::from("GoogleId2"), &String::from("Photo 1"), &now)
-            .unwrap();
-        photo_db.upsert_media_item(&String::from("GoogleId3"), &String::from("Photo 2"), &now)?;
-        photo_db.upsert_media_item_in_album("GoogleId1", "GoogleId2")?;
+        let config = PhotoFsConfig {
+            jpeg_metadata: true,
+            ..PhotoFsConfig::default()
+        };
+        let fs = PhotoFs::with_config(photo_lib.clone(), photo_db.clone(), config);
 
+        let data;
         {
-            let fh = fs.opendir(&TestUniqRequest {}, album_inode, 0)?.fh;
+            let open = fs.open(&TestUniqRequest {}, inode, 0)?;
+            let response = fs.read(&TestUniqRequest {}, inode, open.fh, 0, 4096)?;
+            data = response.data;
+        }
+        assert!(data.len() > jpeg.len());
+        assert!(data.ends_with(&jpeg[2..]));
+        assert!(data.windows(12).any(|window| window == b"At the beach"));
 
-            let response = fs.readdir(&TestUniqRequest {}, album_inode, fh, 0)?;
-            assert_eq!(response.entries.len(), 3);
-            assert_eq!(response.entries[0].ino, album_inode);
-            assert_eq!(response.entries[1].ino, FIXED_INODE_ALBUMS);
-            assert_eq!(response.entries[2].ino, media_item_inode);
+        // Once opened the size matches the rewritten data
+        {
+            let response = fs.getattr(&TestUniqRequest {}, inode)?;
+            assert_eq!(response.attr.size, data.len() as u64);
 
-            fs.releasedir(&TestUniqRequest {}, album_inode, fh, 0)?;
+            let response = fs.lookup(
+                &TestUniqRequest {},
+                FIXED_INODE_MEDIA,
+                OsStr::new("Photo1.jpg"),
+            )?;
+            assert_eq!(response.attr.size, data.len() as u64);
         }
 
         Result::Ok(())
@@ -1114,9 +3026,9 @@ mod test {
 
     #[test]
     fn readdir_invalid_inode_or_fh() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
         let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ROOT, 0)?.fh;
 
@@ -1136,9 +3048,9 @@ mod test {
 
     #[test]
     fn releasedir_no_previous_opendir() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
         assert!(fs.releasedir(&TestUniqRequest {}, 1, 0, 0).is_err());
 
@@ -1147,9 +3059,9 @@ mod test {
 
     #[test]
     fn releasedir_from_previous_opendir() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
         let fh = fs.opendir(&TestUniqRequest {}, FIXED_INODE_ROOT, 0)?.fh;
 
@@ -1162,10 +3074,10 @@ mod test {
 
     #[test]
     fn destroy_ok_if_open_files() -> Result<(), FuseError> {
-        let photo_lib = Arc::new(Mutex::new(TestRemotePhotoLib::new()));
+        let photo_lib = Arc::new(TestRemotePhotoLib::new());
         let photo_db = Arc::new(SqliteDb::in_memory()?);
 
-        let mut fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
+        let fs = PhotoFs::new(photo_lib.clone(), photo_db.clone());
 
         fs.opendir(&TestUniqRequest {}, FIXED_INODE_ROOT, 0)?;
         fs.open(&TestUniqRequest {}, FIXED_INODE_HELLO_WORLD, 0)?;
@@ -1175,6 +3087,14 @@ mod test {
         Result::Ok(())
     }
 
+    // Lookups of missing names are answered with an entry the kernel caches as missing
+    fn is_negative(response: FuseResult<FileEntryResponse>) -> bool {
+        match response {
+            Ok(response) => response.attr.ino == 0,
+            Err(_) => false,
+        }
+    }
+
     #[derive(Debug)]
     struct TestUniqRequest {}
 
@@ -1183,7 +3103,7 @@ mod test {
             0
         }
         fn uid(&self) -> u32 {
-            0
+            users::get_current_uid()
         }
         fn gid(&self) -> u32 {
             0
@@ -1195,13 +3115,15 @@ mod test {
 
     #[derive(Debug)]
     struct TestRemotePhotoLib<'a> {
-        test_data: HashMap<&'a GoogleId, Vec<u8>>,
+        test_data: Mutex<HashMap<&'a GoogleId, Vec<u8>>>,
+        downloads: AtomicUsize,
     }
 
     impl<'a> TestRemotePhotoLib<'a> {
         fn new() -> TestRemotePhotoLib<'a> {
             TestRemotePhotoLib {
-                test_data: HashMap::new(),
+                test_data: Mutex::new(HashMap::new()),
+                downloads: AtomicUsize::new(0),
             }
         }
     }
@@ -1211,9 +3133,16 @@ mod test {
             &self,
             google_id: &GoogleId,
             _is_video: bool,
+            variant: MediaVariant,
         ) -> Result<Vec<u8>, RemotePhotoLibError> {
-            match self.test_data.get(google_id) {
-                Some(data) => Result::Ok(data.clone()),
+            self.downloads.fetch_add(1, Ordering::SeqCst);
+            match self.test_data.lock().unwrap().get(google_id) {
+                Some(data) => match variant {
+                    MediaVariant::Original => Result::Ok(data.clone()),
+                    MediaVariant::Resized { width, height } => {
+                        Result::Ok(format!("{}x{}", width, height).into_bytes())
+                    }
+                },
                 None => Result::Err(RemotePhotoLibError::HttpApiError(
                     hyper::status::StatusCode::NotFound,
                 )),
@@ -0,0 +1,249 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::domain::Inode;
+use crate::rust_filesystem::WorkerPool;
+
+// Listings of the last few read directories are searched for the opened files
+const MAX_LISTINGS: usize = 4;
+// Entries the kernel did not take from the last page are read again, so a new page
+// may repeat up to this many of the last listed files
+const MAX_PAGE_OVERLAP: usize = 1024;
+
+struct PrefetchState {
+    // Downloadable files of recently read directories, newest first
+    listings: VecDeque<(Inode, Vec<Inode>)>,
+    last_opened: Option<Inode>,
+}
+
+// Recognizes files being opened in directory order, e.g. by a slideshow, and
+// downloads the next ones in the background before they are asked for.
+pub struct Prefetcher {
+    depth: usize,
+    max_bytes_per_second: usize,
+    state: Mutex<PrefetchState>,
+    // Waiting for the worker, removed once started or when dropped
+    queued: Arc<Mutex<HashSet<Inode>>>,
+    // A single worker, so the bandwidth cap holds for all prefetches together
+    worker: WorkerPool,
+}
+
+impl Prefetcher {
+    // A depth of 0 disables prefetching, a max_bytes_per_second of 0 disables the cap
+    pub fn new(depth: usize, max_bytes_per_second: usize) -> Prefetcher {
+        Prefetcher {
+            depth,
+            max_bytes_per_second,
+            state: Mutex::new(PrefetchState {
+                listings: VecDeque::new(),
+                last_opened: Option::None,
+            }),
+            queued: Arc::new(Mutex::new(HashSet::new())),
+            worker: WorkerPool::named("prefetch", 1),
+        }
+    }
+
+    // Directories are read page by page, from_start is set for the first page
+    pub fn listed(&self, directory: Inode, from_start: bool, inodes: Vec<Inode>) {
+        if self.depth == 0 || inodes.is_empty() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let known = state
+            .listings
+            .iter()
+            .position(|(listed, _)| *listed == directory);
+        match known {
+            Some(index) if !from_start => {
+                let listing = &mut state.listings[index].1;
+                let overlap_start = listing.len().saturating_sub(MAX_PAGE_OVERLAP);
+                if let Some(position) = listing[overlap_start..]
+                    .iter()
+                    .position(|inode| *inode == inodes[0])
+                {
+                    listing.truncate(overlap_start + position);
+                }
+                listing.extend(inodes);
+            }
+            _ => {
+                if let Some(index) = known {
+                    state.listings.remove(index);
+                }
+                state.listings.push_front((directory, inodes));
+                state.listings.truncate(MAX_LISTINGS);
+            }
+        }
+    }
+
+    // The files following inode in its listing, if the file before it was the last
+    // one opened. Files already waiting to be prefetched are left out.
+    pub fn opened(&self, inode: Inode) -> Vec<Inode> {
+        if self.depth == 0 {
+            return Vec::new();
+        }
+        let mut state = self.state.lock().unwrap();
+        let last_opened = state.last_opened.replace(inode);
+        if last_opened == Option::Some(inode) {
+            return Vec::new();
+        }
+
+        let next: Vec<Inode> = state
+            .listings
+            .iter()
+            .filter_map(|(_, listing)| {
+                let position = listing.iter().position(|entry| *entry == inode)?;
+                if position > 0 && Option::Some(listing[position - 1]) == last_opened {
+                    Option::Some(listing[position + 1..].iter().take(self.depth).cloned())
+                } else {
+                    Option::None
+                }
+            })
+            .next()
+            .map(|next| next.collect())
+            .unwrap_or_default();
+
+        let mut queued = self.queued.lock().unwrap();
+        next.into_iter()
+            .filter(|next| queued.insert(*next))
+            .collect()
+    }
+
+    // Runs fetch for an inode returned by opened on the prefetch worker. It returns the
+    // number of bytes downloaded, the worker then pauses to stay below the bandwidth cap.
+    pub fn execute<F>(&self, inode: Inode, fetch: F)
+    where
+        F: FnOnce() -> usize + Send + 'static,
+    {
+        let queued = self.queued.clone();
+        let max_bytes_per_second = self.max_bytes_per_second;
+        self.worker.execute(move || {
+            if !queued.lock().unwrap().remove(&inode) {
+                return; // Prefetcher dropped
+            }
+            let started = Instant::now();
+            let downloaded = fetch();
+            if let Some(pause) = throttle(downloaded, max_bytes_per_second, started.elapsed()) {
+                debug!("Prefetched {} bytes, pausing for {:?}", downloaded, pause);
+                thread::sleep(pause);
+            }
+        });
+    }
+}
+
+// Skips whatever is still queued, instead of downloading it while shutting down
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        if let Ok(mut queued) = self.queued.lock() {
+            queued.clear();
+        }
+    }
+}
+
+fn throttle(bytes: usize, max_bytes_per_second: usize, elapsed: Duration) -> Option<Duration> {
+    if max_bytes_per_second == 0 {
+        return Option::None;
+    }
+    let millis = bytes as u64 * 1000 / max_bytes_per_second as u64;
+    let allowed = Duration::from_millis(millis);
+    if allowed > elapsed {
+        Option::Some(allowed - elapsed)
+    } else {
+        Option::None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::mpsc;
+
+    #[test]
+    fn prefetch_sequential_opens() {
+        let prefetcher = Prefetcher::new(2, 0);
+        prefetcher.listed(3, true, vec![101, 102, 103, 104, 105]);
+
+        // A single open is not a sequence yet
+        assert_eq!(prefetcher.opened(101), Vec::<Inode>::new());
+        assert_eq!(prefetcher.opened(102), vec![103, 104]);
+        // 104 is still queued
+        assert_eq!(prefetcher.opened(103), vec![105]);
+        // Reopening the same file does nothing
+        assert_eq!(prefetcher.opened(103), Vec::<Inode>::new());
+    }
+
+    #[test]
+    fn prefetch_random_opens() {
+        let prefetcher = Prefetcher::new(2, 0);
+        prefetcher.listed(3, true, vec![101, 102, 103, 104, 105]);
+
+        assert_eq!(prefetcher.opened(104), Vec::<Inode>::new());
+        assert_eq!(prefetcher.opened(102), Vec::<Inode>::new());
+        assert_eq!(prefetcher.opened(201), Vec::<Inode>::new());
+        assert_eq!(prefetcher.opened(202), Vec::<Inode>::new());
+    }
+
+    #[test]
+    fn prefetch_end_of_listing() {
+        let prefetcher = Prefetcher::new(2, 0);
+        prefetcher.listed(3, true, vec![101, 102]);
+        prefetcher.listed(201, true, vec![201, 202, 203]);
+
+        prefetcher.opened(101);
+        assert_eq!(prefetcher.opened(102), Vec::<Inode>::new());
+        prefetcher.opened(202);
+        assert_eq!(prefetcher.opened(203), Vec::<Inode>::new());
+    }
+
+    #[test]
+    fn prefetch_listed_in_pages() {
+        let prefetcher = Prefetcher::new(2, 0);
+        prefetcher.listed(3, true, vec![101, 102, 103]);
+        // The kernel only took up to 102 of the first page
+        prefetcher.listed(3, false, vec![103, 104, 105]);
+
+        prefetcher.opened(102);
+        assert_eq!(prefetcher.opened(103), vec![104, 105]);
+
+        // Reading from the start again replaces the listing
+        prefetcher.listed(3, true, vec![101, 105]);
+        prefetcher.opened(101);
+        assert_eq!(prefetcher.opened(105), Vec::<Inode>::new());
+    }
+
+    #[test]
+    fn prefetch_disabled() {
+        let prefetcher = Prefetcher::new(0, 0);
+        prefetcher.listed(3, true, vec![101, 102, 103]);
+
+        prefetcher.opened(101);
+        assert_eq!(prefetcher.opened(102), Vec::<Inode>::new());
+    }
+
+    #[test]
+    fn prefetch_execute() {
+        let prefetcher = Prefetcher::new(1, 0);
+        prefetcher.listed(3, true, vec![101, 102, 103]);
+        prefetcher.opened(101);
+        assert_eq!(prefetcher.opened(102), vec![103]);
+
+        let (sender, receiver) = mpsc::channel();
+        prefetcher.execute(103, move || {
+            sender.send(103).unwrap();
+            3
+        });
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(103));
+    }
+
+    #[test]
+    fn prefetch_throttle() {
+        assert_eq!(throttle(1000, 0, Duration::from_millis(0)), Option::None);
+        assert_eq!(
+            throttle(1000, 500, Duration::from_millis(500)),
+            Option::Some(Duration::from_millis(1500))
+        );
+        assert_eq!(throttle(1000, 500, Duration::from_secs(3)), Option::None);
+    }
+}
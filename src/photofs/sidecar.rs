@@ -0,0 +1,160 @@
+use serde_json;
+
+use crate::domain::{MediaItemMetadata, PhotoDbAlbum, PhotoDbMediaItem, UtcDateTime};
+
+const JSON_SIDECAR_PREFIX: &str = ".";
+const JSON_SIDECAR_SUFFIX: &str = ".json";
+const XMP_SIDECAR_SUFFIX: &str = ".xmp";
+
+// IMG_1234.jpg => .IMG_1234.jpg.json
+pub fn json_sidecar_name(media_item_name: &str) -> String {
+    format!(
+        "{}{}{}",
+        JSON_SIDECAR_PREFIX, media_item_name, JSON_SIDECAR_SUFFIX
+    )
+}
+
+// .IMG_1234.jpg.json => IMG_1234.jpg
+pub fn media_item_name_for_json_sidecar(name: &str) -> Option<&str> {
+    if name.len() > JSON_SIDECAR_PREFIX.len() + JSON_SIDECAR_SUFFIX.len()
+        && name.starts_with(JSON_SIDECAR_PREFIX)
+        && name.ends_with(JSON_SIDECAR_SUFFIX)
+    {
+        Option::Some(&name[JSON_SIDECAR_PREFIX.len()..name.len() - JSON_SIDECAR_SUFFIX.len()])
+    } else {
+        Option::None
+    }
+}
+
+// IMG_1234.jpg => IMG_1234.jpg.xmp, the naming darktable and digiKam look for
+pub fn xmp_sidecar_name(media_item_name: &str) -> String {
+    format!("{}{}", media_item_name, XMP_SIDECAR_SUFFIX)
+}
+
+// IMG_1234.jpg.xmp => IMG_1234.jpg
+pub fn media_item_name_for_xmp_sidecar(name: &str) -> Option<&str> {
+    if name.len() > XMP_SIDECAR_SUFFIX.len() && name.ends_with(XMP_SIDECAR_SUFFIX) {
+        Option::Some(&name[..name.len() - XMP_SIDECAR_SUFFIX.len()])
+    } else {
+        Option::None
+    }
+}
+
+fn takeout_time(time: &UtcDateTime) -> serde_json::Value {
+    serde_json::json!({
+        "timestamp": format!("{}", time.timestamp()),
+        "formatted": time.format("%-d %b %Y, %H:%M:%S UTC").to_string(),
+    })
+}
+
+// Laid out like the per photo JSON files of Google Takeout, plus the fields
+// Takeout does not have (id, album names, camera, ...).
+pub fn json_sidecar(
+    media_item: &PhotoDbMediaItem,
+    metadata: &MediaItemMetadata,
+    albums: &[PhotoDbAlbum],
+) -> Vec<u8> {
+    let mut sidecar = serde_json::json!({
+        "title": media_item.name,
+        "description": metadata.description.clone().unwrap_or_default(),
+        "id": media_item.google_id(),
+        "albums": albums.iter().map(|album| album.name.clone()).collect::<Vec<String>>(),
+        "mediaMetadata": {
+            "width": metadata.width.map(|width| format!("{}", width)),
+            "height": metadata.height.map(|height| format!("{}", height)),
+            "cameraMake": metadata.camera_make,
+            "cameraModel": metadata.camera_model,
+        },
+    });
+    if let Some(creation_time) = &metadata.creation_time {
+        sidecar["photoTakenTime"] = takeout_time(creation_time);
+    }
+    if let Some(mime_type) = &metadata.mime_type {
+        sidecar["mimeType"] = serde_json::Value::from(mime_type.clone());
+    }
+    if let Some(product_url) = &metadata.product_url {
+        sidecar["url"] = serde_json::Value::from(product_url.clone());
+    }
+
+    let mut data = serde_json::to_vec_pretty(&sidecar).unwrap();
+    data.push(b'\n');
+    data
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::domain::MediaTypes;
+
+    #[test]
+    fn json_sidecar_names() {
+        assert_eq!(json_sidecar_name("IMG_1234.jpg"), ".IMG_1234.jpg.json");
+        assert_eq!(
+            media_item_name_for_json_sidecar(".IMG_1234.jpg.json"),
+            Option::Some("IMG_1234.jpg")
+        );
+        assert_eq!(
+            media_item_name_for_json_sidecar("IMG_1234.jpg"),
+            Option::None
+        );
+        assert_eq!(media_item_name_for_json_sidecar(".json"), Option::None);
+        assert_eq!(media_item_name_for_json_sidecar("..json"), Option::None);
+    }
+
+    #[test]
+    fn xmp_sidecar_names() {
+        assert_eq!(xmp_sidecar_name("IMG_1234.jpg"), "IMG_1234.jpg.xmp");
+        assert_eq!(
+            media_item_name_for_xmp_sidecar("IMG_1234.jpg.xmp"),
+            Option::Some("IMG_1234.jpg")
+        );
+        assert_eq!(
+            media_item_name_for_xmp_sidecar("IMG_1234.jpg"),
+            Option::None
+        );
+        assert_eq!(media_item_name_for_xmp_sidecar(".xmp"), Option::None);
+    }
+
+    #[test]
+    fn json_sidecar_content() {
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let media_item = PhotoDbMediaItem::new(
+            String::from("GoogleId1"),
+            String::from("IMG_1234.jpg"),
+            MediaTypes::MediaItem,
+            now,
+            101,
+        );
+        let album = PhotoDbAlbum::new(
+            String::from("GoogleId2"),
+            String::from("Holiday"),
+            MediaTypes::Album,
+            now,
+            102,
+        );
+        let metadata = MediaItemMetadata {
+            description: Option::Some(String::from("At the beach")),
+            creation_time: Option::Some(Utc::timestamp(&Utc, 1_546_300_800, 0)),
+            width: Option::Some(4032),
+            ..MediaItemMetadata::default()
+        };
+
+        let data = json_sidecar(&media_item, &metadata, &[album]);
+        let sidecar: serde_json::Value = serde_json::from_slice(&data).unwrap();
+        assert_eq!(sidecar["title"], "IMG_1234.jpg");
+        assert_eq!(sidecar["description"], "At the beach");
+        assert_eq!(sidecar["id"], "GoogleId1");
+        assert_eq!(sidecar["albums"], serde_json::json!(["Holiday"]));
+        assert_eq!(sidecar["photoTakenTime"]["timestamp"], "1546300800");
+        assert_eq!(
+            sidecar["photoTakenTime"]["formatted"],
+            "1 Jan 2019, 00:00:00 UTC"
+        );
+        assert_eq!(sidecar["mediaMetadata"]["width"], "4032");
+        assert!(sidecar["mediaMetadata"]["height"].is_null());
+        assert!(sidecar.get("url").is_none());
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::domain::Inode;
+
+struct SizeMapState {
+    sizes: HashMap<Inode, usize>,
+    // Oldest first
+    inserted: VecDeque<Inode>,
+}
+
+// Sizes of at most max_entries inodes, the oldest gives way to a new one. An evicted
+// size is only missed once that inode is used again, so a long mount stays bounded.
+pub struct SizeMap {
+    state: Mutex<SizeMapState>,
+    max_entries: usize,
+}
+
+impl SizeMap {
+    pub fn new(max_entries: usize) -> SizeMap {
+        SizeMap {
+            state: Mutex::new(SizeMapState {
+                sizes: HashMap::new(),
+                inserted: VecDeque::new(),
+            }),
+            max_entries,
+        }
+    }
+
+    pub fn get(&self, inode: Inode) -> Option<usize> {
+        self.state.lock().unwrap().sizes.get(&inode).cloned()
+    }
+
+    pub fn insert(&self, inode: Inode, size: usize) {
+        let mut state = self.state.lock().unwrap();
+        if state.sizes.insert(inode, size).is_none() {
+            state.inserted.push_back(inode);
+            if state.inserted.len() > self.max_entries {
+                if let Some(oldest) = state.inserted.pop_front() {
+                    state.sizes.remove(&oldest);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn size_map_evicts_oldest() {
+        let sizes = SizeMap::new(2);
+        sizes.insert(1, 10);
+        sizes.insert(2, 20);
+        assert_eq!(sizes.get(1), Option::Some(10));
+
+        // Updates keep their place
+        sizes.insert(1, 11);
+        assert_eq!(sizes.get(1), Option::Some(11));
+        assert_eq!(sizes.get(2), Option::Some(20));
+
+        sizes.insert(3, 30);
+        assert_eq!(sizes.get(1), Option::None);
+        assert_eq!(sizes.get(2), Option::Some(20));
+        assert_eq!(sizes.get(3), Option::Some(30));
+        assert_eq!(sizes.state.lock().unwrap().inserted.len(), 2);
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::fs;
 
 use users;
 
@@ -12,25 +13,64 @@ const CREATE_TIME: Timespec = Timespec {
     nsec: 0,
 }; // 2013-10-08 08:56
 
+// st_blocks is always counted in 512 byte units
+const BLOCK_SIZE: u64 = 512;
+
+pub const DEFAULT_FILE_MODE: u16 = 0o644;
+pub const DEFAULT_DIR_MODE: u16 = 0o755;
+const SYMLINK_MODE: u16 = 0o777;
+
+// Owned by the mounting user and its primary group. Directories are linked from their
+// parent and from their own "." entry, subdirectories add their ".." on top.
 pub fn make_atr(inode: Inode, size: usize, file_type: FileType) -> FileAttr {
+    let (perm, nlink) = match file_type {
+        FileType::Directory => (DEFAULT_DIR_MODE, 2),
+        FileType::Symlink => (SYMLINK_MODE, 1),
+        _ => (DEFAULT_FILE_MODE, 1),
+    };
     FileAttr {
         ino: inode,
         size: size as u64,
-        blocks: 1,
+        blocks: (size as u64 + BLOCK_SIZE - 1) / BLOCK_SIZE,
         atime: CREATE_TIME,
         mtime: CREATE_TIME,
         ctime: CREATE_TIME,
         crtime: CREATE_TIME,
         kind: file_type,
-        perm: 0o644,
-        nlink: 1,
+        perm,
+        nlink,
         uid: users::get_current_uid(),
-        gid: 20,
+        gid: users::get_current_gid(),
         rdev: 0,
         flags: 0,
     }
 }
 
+// The supplementary groups of the requesting process, as the kernel would check them.
+// Empty when the process is gone or did not come from user space (pid 0).
+pub fn supplementary_groups(pid: u32) -> Vec<u32> {
+    match fs::read_to_string(format!("/proc/{}/status", pid)) {
+        Ok(status) => status_groups(&status),
+        Err(error) => {
+            debug!("Failed to read the groups of pid={}: {}", pid, error);
+            Vec::new()
+        }
+    }
+}
+
+fn status_groups(status: &str) -> Vec<u32> {
+    status
+        .lines()
+        .find(|line| line.starts_with("Groups:"))
+        .map(|line| {
+            line["Groups:".len()..]
+                .split_whitespace()
+                .filter_map(|gid| gid.parse::<u32>().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 // TODO: Global fh
 #[derive(Debug)]
 pub struct OpenFileHandles<X> {
@@ -92,6 +132,38 @@ mod test {
             make_atr(100, 1, FileType::Directory).kind,
             FileType::Directory
         );
+
+        // Blocks
+        assert_eq!(make_atr(100, 0, FileType::RegularFile).blocks, 0);
+        assert_eq!(make_atr(100, 1, FileType::RegularFile).blocks, 1);
+        assert_eq!(make_atr(100, 512, FileType::RegularFile).blocks, 1);
+        assert_eq!(make_atr(100, 513, FileType::RegularFile).blocks, 2);
+
+        // Permissions and links
+        let file = make_atr(100, 1, FileType::RegularFile);
+        assert_eq!((file.perm, file.nlink), (0o644, 1));
+        let directory = make_atr(100, 1, FileType::Directory);
+        assert_eq!((directory.perm, directory.nlink), (0o755, 2));
+        let symlink = make_atr(100, 1, FileType::Symlink);
+        assert_eq!((symlink.perm, symlink.nlink), (0o777, 1));
+
+        // Owner
+        assert_eq!(file.uid, users::get_current_uid());
+        assert_eq!(file.gid, users::get_current_gid());
+    }
+
+    #[test]
+    fn status_groups_test() {
+        let status = "Name:\tcat\nGid:\t100\t100\t100\t100\nGroups:\t10 27 1001 \nNSpid:\t1\n";
+        assert_eq!(status_groups(status), vec![10, 27, 1001]);
+        assert_eq!(status_groups("Groups:\t\n"), Vec::<u32>::new());
+        assert_eq!(status_groups("Name:\tcat\n"), Vec::<u32>::new());
+
+        // Only the own process is known to exist
+        assert_eq!(
+            supplementary_groups(std::process::id()),
+            status_groups(&fs::read_to_string("/proc/self/status").unwrap())
+        );
     }
 
     #[test]
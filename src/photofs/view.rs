@@ -0,0 +1,162 @@
+use crate::domain::Inode;
+
+// The DB hands out inodes counting up from 100, they will never reach the upper
+// 16 bits. Those bits select which view of a media item an inode refers to, so
+// every view gets its own inode (and therefore its own open file data).
+const VIEW_SHIFT: u32 = 48;
+const VIEW_MASK: u64 = 0xFFFF << VIEW_SHIFT;
+
+const VIEW_ID_THUMBNAIL_BASE: u64 = 0x100;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum InodeView {
+    Original,
+    JsonSidecar,
+    XmpSidecar,
+    // Index into the configured thumbnail sizes
+    Thumbnail(usize),
+    // Symlinks in album directories to the views above in /media
+    OriginalLink,
+    JsonSidecarLink,
+    XmpSidecarLink,
+}
+
+impl InodeView {
+    fn id(self) -> u64 {
+        match self {
+            InodeView::Original => 0,
+            InodeView::JsonSidecar => 1,
+            InodeView::XmpSidecar => 2,
+            InodeView::OriginalLink => 3,
+            InodeView::JsonSidecarLink => 4,
+            InodeView::XmpSidecarLink => 5,
+            InodeView::Thumbnail(index) => VIEW_ID_THUMBNAIL_BASE + index as u64,
+        }
+    }
+
+    // The view a symlink view points at
+    pub fn link_target(self) -> Option<InodeView> {
+        match self {
+            InodeView::OriginalLink => Option::Some(InodeView::Original),
+            InodeView::JsonSidecarLink => Option::Some(InodeView::JsonSidecar),
+            InodeView::XmpSidecarLink => Option::Some(InodeView::XmpSidecar),
+            _ => Option::None,
+        }
+    }
+
+    // The symlink view pointing at this view, only originals and their sidecars have one
+    pub fn link(self) -> Option<InodeView> {
+        match self {
+            InodeView::Original => Option::Some(InodeView::OriginalLink),
+            InodeView::JsonSidecar => Option::Some(InodeView::JsonSidecarLink),
+            InodeView::XmpSidecar => Option::Some(InodeView::XmpSidecarLink),
+            _ => Option::None,
+        }
+    }
+
+    // Sidecars are generated locally instead of downloaded
+    pub fn is_sidecar(self) -> bool {
+        match self {
+            InodeView::JsonSidecar | InodeView::XmpSidecar => true,
+            _ => false,
+        }
+    }
+
+    fn from_id(id: u64) -> Option<InodeView> {
+        match id {
+            0 => Option::Some(InodeView::Original),
+            1 => Option::Some(InodeView::JsonSidecar),
+            2 => Option::Some(InodeView::XmpSidecar),
+            3 => Option::Some(InodeView::OriginalLink),
+            4 => Option::Some(InodeView::JsonSidecarLink),
+            5 => Option::Some(InodeView::XmpSidecarLink),
+            id if id >= VIEW_ID_THUMBNAIL_BASE => {
+                Option::Some(InodeView::Thumbnail((id - VIEW_ID_THUMBNAIL_BASE) as usize))
+            }
+            _ => Option::None,
+        }
+    }
+}
+
+pub fn view_inode(inode: Inode, view: InodeView) -> Inode {
+    (inode & !VIEW_MASK) | (view.id() << VIEW_SHIFT)
+}
+
+// Splits an inode into the inode stored in the DB and the view of it
+pub fn split_inode(inode: Inode) -> Option<(Inode, InodeView)> {
+    InodeView::from_id((inode & VIEW_MASK) >> VIEW_SHIFT).map(|view| (inode & !VIEW_MASK, view))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn view_inode_original() {
+        assert_eq!(view_inode(101, InodeView::Original), 101);
+        assert_eq!(split_inode(101), Option::Some((101, InodeView::Original)));
+    }
+
+    #[test]
+    fn view_inode_thumbnail() {
+        let inode = view_inode(101, InodeView::Thumbnail(2));
+        assert_ne!(inode, 101);
+        assert_ne!(inode, view_inode(101, InodeView::Thumbnail(1)));
+        assert_eq!(
+            split_inode(inode),
+            Option::Some((101, InodeView::Thumbnail(2)))
+        );
+    }
+
+    #[test]
+    fn view_inode_json_sidecar() {
+        let inode = view_inode(101, InodeView::JsonSidecar);
+        assert_ne!(inode, 101);
+        assert_eq!(
+            split_inode(inode),
+            Option::Some((101, InodeView::JsonSidecar))
+        );
+    }
+
+    #[test]
+    fn view_inode_xmp_sidecar() {
+        let inode = view_inode(101, InodeView::XmpSidecar);
+        assert_ne!(inode, view_inode(101, InodeView::JsonSidecar));
+        assert_eq!(
+            split_inode(inode),
+            Option::Some((101, InodeView::XmpSidecar))
+        );
+        assert!(InodeView::XmpSidecar.is_sidecar());
+        assert!(!InodeView::Thumbnail(0).is_sidecar());
+    }
+
+    #[test]
+    fn view_inode_link() {
+        let inode = view_inode(101, InodeView::OriginalLink);
+        assert_ne!(inode, 101);
+        assert_eq!(
+            split_inode(inode),
+            Option::Some((101, InodeView::OriginalLink))
+        );
+        for view in &[
+            InodeView::Original,
+            InodeView::JsonSidecar,
+            InodeView::XmpSidecar,
+        ] {
+            let link = view.link().unwrap();
+            assert_eq!(link.link_target(), Option::Some(*view));
+            assert_eq!(
+                split_inode(view_inode(101, link)),
+                Option::Some((101, link))
+            );
+        }
+        assert!(!InodeView::JsonSidecarLink.is_sidecar());
+        assert_eq!(InodeView::Thumbnail(0).link(), Option::None);
+        assert_eq!(InodeView::Original.link_target(), Option::None);
+    }
+
+    #[test]
+    fn split_inode_unknown_view() {
+        assert_eq!(split_inode(101 | (0xFF << VIEW_SHIFT)), Option::None);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::domain::{MediaItemMetadata, PhotoDbAlbum, UtcDateTime};
+
+const XMP_RATING_FAVOURITE: u8 = 5;
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn xmp_date(time: &UtcDateTime) -> String {
+    time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+// The x:xmpmeta element, shared by sidecars and XMP embedded into files.
+// Albums become keywords, a favourite becomes a 5 star rating.
+pub fn xmp_meta(
+    metadata: &MediaItemMetadata,
+    albums: &[PhotoDbAlbum],
+    is_favourite: bool,
+) -> String {
+    let mut attributes = String::new();
+    if is_favourite {
+        attributes.push_str(&format!("\n    xmp:Rating=\"{}\"", XMP_RATING_FAVOURITE));
+    }
+    if let Some(creation_time) = &metadata.creation_time {
+        let date = xmp_date(creation_time);
+        attributes.push_str(&format!("\n    xmp:CreateDate=\"{}\"", date));
+        attributes.push_str(&format!("\n    exif:DateTimeOriginal=\"{}\"", date));
+        attributes.push_str(&format!("\n    photoshop:DateCreated=\"{}\"", date));
+    }
+
+    let mut elements = String::new();
+    if let Some(description) = &metadata.description {
+        elements.push_str(&format!(
+            "   <dc:description>\n    <rdf:Alt>\n     <rdf:li xml:lang=\"x-default\">{}</rdf:li>\n    </rdf:Alt>\n   </dc:description>\n",
+            escape(description)
+        ));
+    }
+    if !albums.is_empty() {
+        elements.push_str("   <dc:subject>\n    <rdf:Bag>\n");
+        for album in albums {
+            elements.push_str(&format!("     <rdf:li>{}</rdf:li>\n", escape(&album.name)));
+        }
+        elements.push_str("    </rdf:Bag>\n   </dc:subject>\n");
+    }
+
+    format!(
+        "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\" x:xmptk=\"photooxide\">
+ <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
+  <rdf:Description rdf:about=\"\"
+    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"
+    xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"
+    xmlns:exif=\"http://ns.adobe.com/exif/1.0/\"
+    xmlns:photoshop=\"http://ns.adobe.com/photoshop/1.0/\"{}>
+{}  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+",
+        attributes, elements
+    )
+}
+
+pub fn xmp_sidecar(
+    metadata: &MediaItemMetadata,
+    albums: &[PhotoDbAlbum],
+    is_favourite: bool,
+) -> Vec<u8> {
+    let mut sidecar = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    sidecar.push_str(&xmp_meta(metadata, albums, is_favourite));
+    sidecar.into_bytes()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::domain::MediaTypes;
+
+    #[test]
+    fn escape_xml() {
+        assert_eq!(escape("Tom & Jerry <3"), "Tom &amp; Jerry &lt;3");
+        assert_eq!(
+            escape("\"quoted\" 'text'"),
+            "&quot;quoted&quot; &apos;text&apos;"
+        );
+    }
+
+    #[test]
+    fn xmp_sidecar_empty() {
+        let sidecar =
+            String::from_utf8(xmp_sidecar(&MediaItemMetadata::default(), &[], false)).unwrap();
+        assert!(sidecar.starts_with("<?xml"));
+        assert!(!sidecar.contains("xmp:Rating"));
+        assert!(!sidecar.contains("dc:subject"));
+        assert!(!sidecar.contains("dc:description"));
+    }
+
+    #[test]
+    fn xmp_sidecar_content() {
+        let now = Utc::timestamp(&Utc, Utc::now().timestamp(), 0);
+        let album = PhotoDbAlbum::new(
+            String::from("GoogleId1"),
+            String::from("Fish & Chips"),
+            MediaTypes::Album,
+            now,
+            101,
+        );
+        let metadata = MediaItemMetadata {
+            description: Option::Some(String::from("At the <beach>")),
+            creation_time: Option::Some(Utc::timestamp(&Utc, 1_546_300_800, 0)),
+            ..MediaItemMetadata::default()
+        };
+
+        let sidecar = String::from_utf8(xmp_sidecar(&metadata, &[album], true)).unwrap();
+        assert!(sidecar.contains("xmp:Rating=\"5\""));
+        assert!(sidecar.contains("exif:DateTimeOriginal=\"2019-01-01T00:00:00Z\""));
+        assert!(sidecar.contains("<rdf:li xml:lang=\"x-default\">At the &lt;beach&gt;</rdf:li>"));
+        assert!(sidecar.contains("<rdf:li>Fish &amp; Chips</rdf:li>"));
+    }
+}
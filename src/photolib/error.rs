@@ -12,6 +12,8 @@ pub enum RemotePhotoLibError {
     HttpClientError(hyper::error::Error),
     HttpApiError(hyper::status::StatusCode),
     IoError(std::io::Error),
+    // The listing was given up for a shutdown
+    Stopped,
 }
 
 impl From<std::io::Error> for RemotePhotoLibError {
@@ -39,6 +41,7 @@ impl StdError for RemotePhotoLibError {
             RemotePhotoLibError::HttpClientError(err) => Option::Some(err),
             RemotePhotoLibError::HttpApiError(_err) => Option::None,
             RemotePhotoLibError::IoError(err) => Option::Some(err),
+            RemotePhotoLibError::Stopped => Option::None,
         }
     }
 }
@@ -58,6 +61,7 @@ impl fmt::Display for RemotePhotoLibError {
             RemotePhotoLibError::IoError(err) => {
                 write!(f, "RemotePhotoLibError: IoError({:?})", err)
             }
+            RemotePhotoLibError::Stopped => write!(f, "RemotePhotoLibError: Stopped"),
         }
     }
 }
@@ -113,6 +117,7 @@ mod test {
                 .source()
                 .is_none()
         );
+        assert!(RemotePhotoLibError::Stopped.source().is_none());
         {
             let io_error = std::io::Error::new(std::io::ErrorKind::Other, "I/O Error for test");
             let io_error_str = io_error.to_string();
@@ -149,6 +154,10 @@ mod test {
             ),
             "RemotePhotoLibError: HttpApiError(Ok)"
         );
+        assert_eq!(
+            format!("{}", RemotePhotoLibError::Stopped),
+            "RemotePhotoLibError: Stopped"
+        );
         assert_eq!(
             format!(
                 "{}",
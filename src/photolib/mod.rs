@@ -3,9 +3,14 @@ use std::convert::From;
 use std::io::Read;
 use std::option::Option;
 use std::result::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::oauth2;
-use crate::photoslibrary1::{PhotosLibrary, SearchMediaItemsRequest};
+use crate::photoslibrary1::{
+    ContentFilter, FeatureFilter, Filters, MediaItem, PhotosLibrary, SearchMediaItemsRequest,
+    ShareInfo,
+};
+use chrono::{DateTime, Utc};
 use hyper;
 
 use crate::domain::*;
@@ -13,13 +18,37 @@ use crate::domain::*;
 mod error;
 pub use self::error::RemotePhotoLibError;
 
+mod non_interactive_delegate;
+pub use self::non_interactive_delegate::NonInteractiveDelegate;
+
 mod oauth_token_storage;
 pub use self::oauth_token_storage::{OauthTokenStorage, OauthTokenStorageError};
 
+mod pool;
+use self::pool::Pool;
+
+mod shared_authenticator;
+pub use self::shared_authenticator::SharedAuthenticator;
+
+mod token_cipher;
+pub use self::token_cipher::{random_bytes, TokenCipher, KEY_LEN, SALT_LEN};
+
+mod token_revocation;
+pub use self::token_revocation::revoke_token;
+
+// Read only access is all a read-only mount needs, uploads and sharing need the others
+pub const PHOTOS_READONLY_SCOPE: &str = "https://www.googleapis.com/auth/photoslibrary.readonly";
+pub const PHOTOS_APPENDONLY_SCOPE: &str =
+    "https://www.googleapis.com/auth/photoslibrary.appendonly";
+pub const PHOTOS_SHARING_SCOPE: &str = "https://www.googleapis.com/auth/photoslibrary.sharing";
+
 #[derive(Debug, new)]
 pub struct ItemListing {
     id: String,
     pub name: String,
+    // Only set for media items, and only by listings that return it
+    #[new(default)]
+    pub metadata: Option<MediaItemMetadata>,
 }
 
 impl ItemListing {
@@ -28,18 +57,81 @@ impl ItemListing {
     }
 }
 
+fn media_item_metadata(media_item: &MediaItem) -> MediaItemMetadata {
+    let mut metadata = MediaItemMetadata {
+        description: media_item.description.clone(),
+        mime_type: media_item.mime_type.clone(),
+        product_url: media_item.product_url.clone(),
+        ..MediaItemMetadata::default()
+    };
+    if let Some(media_metadata) = &media_item.media_metadata {
+        metadata.creation_time = media_metadata
+            .creation_time
+            .as_ref()
+            .and_then(|creation_time| DateTime::parse_from_rfc3339(creation_time).ok())
+            .map(|creation_time| creation_time.with_timezone(&Utc));
+        // The API encodes int64 as strings
+        metadata.width = media_metadata
+            .width
+            .as_ref()
+            .and_then(|width| width.to_string().parse().ok());
+        metadata.height = media_metadata
+            .height
+            .as_ref()
+            .and_then(|height| height.to_string().parse().ok());
+        if let Some(photo) = &media_metadata.photo {
+            metadata.camera_make = photo.camera_make.clone();
+            metadata.camera_model = photo.camera_model.clone();
+        } else if let Some(video) = &media_metadata.video {
+            metadata.camera_make = video.camera_make.clone();
+            metadata.camera_model = video.camera_model.clone();
+        }
+    }
+    metadata
+}
+
+// Both listings hold shared albums, but each album is kept once: albums of others the user
+// joined under the shared albums, the users own albums, shared or not, under the albums
+fn is_joined_album_of_others(share_info: Option<&ShareInfo>) -> bool {
+    share_info.map_or(false, |share_info| {
+        share_info.is_joined.unwrap_or(false) && !share_info.is_owned.unwrap_or(false)
+    })
+}
+
 pub trait RemotePhotoLibMetaData: Sized {
     fn media_items(&self) -> Result<Vec<ItemListing>, RemotePhotoLibError>;
 
     fn albums(&self) -> Result<Vec<ItemListing>, RemotePhotoLibError>;
+    fn shared_albums(&self) -> Result<Vec<(ItemListing, SharedAlbumInfo)>, RemotePhotoLibError>;
     fn album(&self, google_id: &GoogleId) -> Result<Vec<ItemListing>, RemotePhotoLibError>;
+
+    fn category(&self, category: ContentCategory) -> Result<Vec<ItemListing>, RemotePhotoLibError>;
+    fn favourites(&self) -> Result<Vec<ItemListing>, RemotePhotoLibError>;
+}
+
+// Which rendition of a media item to download
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum MediaVariant {
+    Original,
+    Resized { width: u32, height: u32 },
 }
 
-pub trait RemotePhotoLibData: Sized {
+impl MediaVariant {
+    fn url_suffix(self, is_video: bool) -> String {
+        match self {
+            MediaVariant::Original if is_video => String::from("=dv"),
+            MediaVariant::Original => String::from("=d"),
+            MediaVariant::Resized { width, height } => format!("=w{}-h{}", width, height),
+        }
+    }
+}
+
+pub trait RemotePhotoLibData: Sized + Send + Sync {
     fn media_item(
         &self,
         google_id: &GoogleId,
         is_video: bool,
+        variant: MediaVariant,
     ) -> Result<Vec<u8>, RemotePhotoLibError>;
 }
 
@@ -48,8 +140,12 @@ where
     C: BorrowMut<hyper::Client>,
     A: oauth2::GetToken,
 {
-    photos_library: PhotosLibrary<C, A>,
-    data_http_client: hyper::Client,
+    // One API client per concurrent metadata call
+    photos_libraries: Pool<PhotosLibrary<C, A>>,
+    // One HTTP client per concurrent download
+    data_http_clients: Pool<hyper::Client>,
+    // Set on shutdown, listings then end before their next page
+    stopped: AtomicBool,
 }
 
 impl<C, A> HttpRemotePhotoLib<C, A>
@@ -58,13 +154,76 @@ where
     A: oauth2::GetToken,
 {
     pub fn new(
-        photos_library: PhotosLibrary<C, A>,
-        data_http_client: hyper::Client,
+        photos_libraries: Vec<PhotosLibrary<C, A>>,
+        data_http_clients: Vec<hyper::Client>,
     ) -> HttpRemotePhotoLib<C, A> {
         HttpRemotePhotoLib {
-            photos_library,
-            data_http_client,
+            photos_libraries: Pool::new(photos_libraries),
+            data_http_clients: Pool::new(data_http_clients),
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    fn check_stopped(&self) -> Result<(), RemotePhotoLibError> {
+        if self.is_stopped() {
+            return Result::Err(RemotePhotoLibError::Stopped);
+        }
+        Result::Ok(())
+    }
+
+    fn search_media_items(
+        &self,
+        filters: Filters,
+        description: &str,
+    ) -> Result<Vec<ItemListing>, RemotePhotoLibError> {
+        let mut all_media_items: Vec<ItemListing> = Vec::new();
+        let mut page_token: Option<String> = Option::None;
+        loop {
+            self.check_stopped()?;
+            let request = SearchMediaItemsRequest {
+                page_token,
+                page_size: Option::Some(50),
+                filters: Option::Some(filters.clone()),
+                album_id: Option::None,
+            };
+            let remote_result = self
+                .photos_libraries
+                .get()
+                .media_items()
+                .search(request)
+                .doit();
+
+            match remote_result {
+                Err(e) => {
+                    error!("{}", e);
+                    return Result::Err(RemotePhotoLibError::from(e));
+                }
+                Ok(res) => {
+                    debug!("Success: listing media_items in {}", description);
+                    // An empty result is returned without a media_items field
+                    for media_item in res.1.media_items.unwrap_or_default() {
+                        all_media_items.push(ItemListing::new(
+                            media_item.id.unwrap(),
+                            media_item.filename.unwrap(),
+                        ));
+                    }
+
+                    page_token = res.1.next_page_token;
+                    if page_token.is_none() {
+                        break;
+                    }
+                }
+            };
         }
+        Result::Ok(all_media_items)
     }
 }
 
@@ -77,7 +236,9 @@ where
         let mut all_media_items: Vec<ItemListing> = Vec::new();
         let mut page_token: Option<String> = Option::None;
         loop {
-            let mut result_builder = self.photos_library.media_items().list().page_size(50);
+            self.check_stopped()?;
+            let photos_library = self.photos_libraries.get();
+            let mut result_builder = photos_library.media_items().list().page_size(50);
             if page_token.is_some() {
                 result_builder = result_builder.page_token(page_token.unwrap().as_str());
             }
@@ -93,10 +254,13 @@ where
                     match res.1.media_items {
                         Some(media_items) => {
                             for media_item in media_items {
-                                all_media_items.push(ItemListing::new(
+                                let metadata = media_item_metadata(&media_item);
+                                let mut item = ItemListing::new(
                                     media_item.id.unwrap(),
                                     media_item.filename.unwrap(),
-                                ))
+                                );
+                                item.metadata = Option::Some(metadata);
+                                all_media_items.push(item)
                             }
                         }
                         None => {
@@ -118,7 +282,9 @@ where
         let mut all_albums: Vec<ItemListing> = Vec::new();
         let mut page_token: Option<String> = Option::None;
         loop {
-            let mut result_builder = self.photos_library.albums().list().page_size(50);
+            self.check_stopped()?;
+            let photos_library = self.photos_libraries.get();
+            let mut result_builder = photos_library.albums().list().page_size(50);
             if page_token.is_some() {
                 result_builder = result_builder.page_token(page_token.unwrap().as_str());
             }
@@ -132,6 +298,9 @@ where
                 Ok(res) => {
                     debug!("Success: listing albums");
                     for album in res.1.albums.unwrap() {
+                        if is_joined_album_of_others(album.share_info.as_ref()) {
+                            continue;
+                        }
                         let album_listing =
                             ItemListing::new(album.id.unwrap(), album.title.unwrap());
                         all_albums.push(album_listing);
@@ -147,17 +316,74 @@ where
         Result::Ok(all_albums)
     }
 
+    fn shared_albums(&self) -> Result<Vec<(ItemListing, SharedAlbumInfo)>, RemotePhotoLibError> {
+        let mut all_shared_albums: Vec<(ItemListing, SharedAlbumInfo)> = Vec::new();
+        let mut page_token: Option<String> = Option::None;
+        loop {
+            self.check_stopped()?;
+            let photos_library = self.photos_libraries.get();
+            let mut result_builder = photos_library.shared_albums().list().page_size(50);
+            if page_token.is_some() {
+                result_builder = result_builder.page_token(page_token.unwrap().as_str());
+            }
+            let remote_result = result_builder.doit();
+
+            match remote_result {
+                Err(e) => {
+                    error!("{}", e);
+                    return Result::Err(RemotePhotoLibError::from(e));
+                }
+                Ok(res) => {
+                    debug!("Success: listing shared albums");
+                    for album in res.1.shared_albums.unwrap_or_default() {
+                        let google_id = album.id.unwrap();
+                        // Shared albums are not required to have a title
+                        let title = album.title.unwrap_or_else(|| google_id.clone());
+                        let share_info = album.share_info.unwrap_or_default();
+                        if share_info.is_owned.unwrap_or(false) {
+                            continue;
+                        }
+                        let is_collaborative = share_info
+                            .shared_album_options
+                            .and_then(|options| options.is_collaborative)
+                            .unwrap_or(false);
+                        all_shared_albums.push((
+                            ItemListing::new(google_id, title),
+                            SharedAlbumInfo::new(
+                                share_info.is_owned.unwrap_or(false),
+                                share_info.is_joined.unwrap_or(false),
+                                is_collaborative,
+                            ),
+                        ));
+                    }
+
+                    page_token = res.1.next_page_token;
+                    if page_token.is_none() {
+                        break;
+                    }
+                }
+            };
+        }
+        Result::Ok(all_shared_albums)
+    }
+
     fn album(&self, google_id: &GoogleId) -> Result<Vec<ItemListing>, RemotePhotoLibError> {
         let mut all_media_items_in_album: Vec<ItemListing> = Vec::new();
         let mut page_token: Option<String> = Option::None;
         loop {
+            self.check_stopped()?;
             let request = SearchMediaItemsRequest {
                 page_token,
                 page_size: Option::Some(50),
                 filters: Option::None,
                 album_id: Option::Some(String::from(google_id)),
             };
-            let remote_result = self.photos_library.media_items().search(request).doit();
+            let remote_result = self
+                .photos_libraries
+                .get()
+                .media_items()
+                .search(request)
+                .doit();
 
             match remote_result {
                 Err(e) => {
@@ -182,28 +408,52 @@ where
         }
         Result::Ok(all_media_items_in_album)
     }
+
+    fn category(&self, category: ContentCategory) -> Result<Vec<ItemListing>, RemotePhotoLibError> {
+        let filters = Filters {
+            content_filter: Option::Some(ContentFilter {
+                included_content_categories: Option::Some(vec![format!("{}", category)]),
+                excluded_content_categories: Option::None,
+            }),
+            ..Filters::default()
+        };
+        self.search_media_items(filters, &format!("category {}", category))
+    }
+
+    fn favourites(&self) -> Result<Vec<ItemListing>, RemotePhotoLibError> {
+        let filters = Filters {
+            feature_filter: Option::Some(FeatureFilter {
+                included_features: Option::Some(vec![String::from("FAVORITES")]),
+            }),
+            ..Filters::default()
+        };
+        self.search_media_items(filters, "favourites")
+    }
 }
 
 impl<C, A> RemotePhotoLibData for HttpRemotePhotoLib<C, A>
 where
-    C: BorrowMut<hyper::Client>,
-    A: oauth2::GetToken,
+    C: BorrowMut<hyper::Client> + Send,
+    A: oauth2::GetToken + Send,
 {
     fn media_item(
         &self,
         google_id: &GoogleId,
         is_video: bool,
+        variant: MediaVariant,
     ) -> Result<Vec<u8>, RemotePhotoLibError> {
-        let media_item = self.photos_library.media_items().get(&google_id).doit()?;
+        let media_item = self
+            .photos_libraries
+            .get()
+            .media_items()
+            .get(&google_id)
+            .doit()?;
         let base_url = media_item.1.base_url.unwrap();
-        let download_url = if is_video {
-            format!("{}=dv", base_url)
-        } else {
-            format!("{}=d", base_url)
-        };
+        let download_url = format!("{}{}", base_url, variant.url_suffix(is_video));
         info!("Have base_url={} download_url={} )", base_url, download_url);
 
-        let mut http_response = self.data_http_client.get(&download_url).send()?;
+        let data_http_client = self.data_http_clients.get();
+        let mut http_response = data_http_client.get(&download_url).send()?;
         match http_response.status {
             hyper::status::StatusCode::Ok => {
                 let mut buffer: Vec<u8> = Vec::new();
@@ -228,4 +478,58 @@ mod test {
             "id"
         );
     }
+
+    #[test]
+    fn item_listing_metadata() {
+        let mut media_item = MediaItem::default();
+        media_item.description = Option::Some(String::from("A description"));
+        assert_eq!(
+            media_item_metadata(&media_item),
+            MediaItemMetadata {
+                description: Option::Some(String::from("A description")),
+                ..MediaItemMetadata::default()
+            }
+        );
+        assert!(ItemListing::new(String::from("id"), String::from("name"))
+            .metadata
+            .is_none());
+    }
+
+    #[test]
+    fn joined_album_of_others() {
+        assert!(!is_joined_album_of_others(Option::None));
+
+        // Owned albums stay with the albums once shared
+        let mut share_info = ShareInfo::default();
+        share_info.is_owned = Option::Some(true);
+        share_info.is_joined = Option::Some(true);
+        assert!(!is_joined_album_of_others(Option::Some(&share_info)));
+
+        share_info.is_owned = Option::Some(false);
+        assert!(is_joined_album_of_others(Option::Some(&share_info)));
+        share_info.is_joined = Option::None;
+        assert!(!is_joined_album_of_others(Option::Some(&share_info)));
+    }
+
+    #[test]
+    fn media_variant_url_suffix() {
+        assert_eq!(MediaVariant::Original.url_suffix(false), "=d");
+        assert_eq!(MediaVariant::Original.url_suffix(true), "=dv");
+        assert_eq!(
+            MediaVariant::Resized {
+                width: 256,
+                height: 256
+            }
+            .url_suffix(false),
+            "=w256-h256"
+        );
+        assert_eq!(
+            MediaVariant::Resized {
+                width: 1024,
+                height: 768
+            }
+            .url_suffix(true),
+            "=w1024-h768"
+        );
+    }
 }
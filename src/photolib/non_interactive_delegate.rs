@@ -0,0 +1,37 @@
+use std::fmt;
+use std::option::Option;
+
+use crate::oauth2::{AuthenticatorDelegate, PollInformation};
+
+// Never asks anyone to authorize, so a mount or sync without a usable token fails
+// instead of waiting on a console nobody is watching
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NonInteractiveDelegate;
+
+impl AuthenticatorDelegate for NonInteractiveDelegate {
+    fn present_user_code(&mut self, _pi: &PollInformation) {
+        error!("Authorization needed, run `photooxide auth` first");
+    }
+
+    fn present_user_url<S: AsRef<str> + fmt::Display>(
+        &mut self,
+        _url: S,
+        _need_code: bool,
+    ) -> Option<String> {
+        error!("Authorization needed, run `photooxide auth` first");
+        Option::None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn non_interactive_delegate_refuses_code() {
+        assert_eq!(
+            NonInteractiveDelegate.present_user_url("https://accounts.google.com", true),
+            Option::None
+        );
+    }
+}
@@ -6,11 +6,13 @@ use crate::oauth2::{Token, TokenStorage};
 use serde_json;
 
 use crate::db;
+use crate::photolib::token_cipher::{TokenCipher, TokenCipherError};
 
 #[derive(Debug)]
 pub enum OauthTokenStorageError {
     DbError(db::DbError),
     SerializingDeserializingError(serde_json::error::Error),
+    CipherError(TokenCipherError),
 }
 
 impl From<db::DbError> for OauthTokenStorageError {
@@ -25,6 +27,12 @@ impl From<serde_json::error::Error> for OauthTokenStorageError {
     }
 }
 
+impl From<TokenCipherError> for OauthTokenStorageError {
+    fn from(error: TokenCipherError) -> Self {
+        OauthTokenStorageError::CipherError(error)
+    }
+}
+
 impl std::error::Error for OauthTokenStorageError {}
 
 impl fmt::Display for OauthTokenStorageError {
@@ -38,16 +46,50 @@ impl fmt::Display for OauthTokenStorageError {
                 "OauthTokenStorageError: SerializingDeserializingError({})",
                 err
             ),
+            OauthTokenStorageError::CipherError(err) => {
+                write!(f, "OauthTokenStorageError: CipherError({})", err)
+            }
         }
     }
 }
 
+// Tokens are stored encrypted, see TokenCipher
 #[derive(Debug, Clone, new)]
 pub struct OauthTokenStorage<A>
 where
     A: db::TokenStorageDb,
 {
     db: Arc<A>,
+    cipher: Arc<TokenCipher>,
+}
+
+impl<A> OauthTokenStorage<A>
+where
+    A: db::TokenStorageDb,
+{
+    // Reads a stored row, still accepting the plain JSON of rows from before encryption
+    pub fn decode(&self, scope_hash: u64, stored: &str) -> Result<Token, OauthTokenStorageError> {
+        let token_as_str = if TokenCipher::is_encrypted(stored) {
+            self.cipher.decrypt(scope_hash, stored)?
+        } else {
+            String::from(stored)
+        };
+        Result::Ok(serde_json::from_str(&token_as_str)?)
+    }
+
+    // Migrates rows from before encryption, returning how many there were
+    pub fn encrypt_plaintext_tokens(&self) -> Result<usize, OauthTokenStorageError> {
+        let mut count = 0;
+        for (scope_hash, stored) in self.db.oath_tokens()? {
+            if !TokenCipher::is_encrypted(&stored) {
+                let encrypted = self.cipher.encrypt(scope_hash, &stored)?;
+                self.db
+                    .set_oath_token(scope_hash, Option::Some(encrypted))?;
+                count += 1;
+            }
+        }
+        Result::Ok(count)
+    }
 }
 
 impl<A> TokenStorage for OauthTokenStorage<A>
@@ -62,7 +104,10 @@ where
         token: Option<Token>,
     ) -> Result<(), Self::Error> {
         let token_as_str = match token {
-            Some(token_value) => Option::Some(serde_json::to_string(&token_value)?),
+            Some(token_value) => Option::Some(
+                self.cipher
+                    .encrypt(scope_hash, &serde_json::to_string(&token_value)?)?,
+            ),
             None => Option::None,
         };
         self.db
@@ -72,10 +117,7 @@ where
 
     fn get(&self, scope_hash: u64, _scopes: &Vec<&str>) -> Result<Option<Token>, Self::Error> {
         let result = match self.db.get_oath_token(scope_hash)? {
-            Option::Some(token_as_str) => {
-                let token: Token = serde_json::from_str(&token_as_str)?;
-                Option::Some(token)
-            }
+            Option::Some(stored) => Option::Some(self.decode(scope_hash, &stored)?),
             Option::None => Option::None,
         };
         Result::Ok(result)
@@ -88,12 +130,17 @@ mod test {
 
     use crate::oauth2::Token;
 
-    use crate::db::SqliteDb;
+    use crate::db::{SqliteDb, TokenStorageDb};
+    use crate::photolib::token_cipher::{random_bytes, KEY_LEN};
+
+    fn cipher() -> Arc<TokenCipher> {
+        Arc::new(TokenCipher::new(&random_bytes(KEY_LEN).unwrap()).unwrap())
+    }
 
     #[test]
     fn oauthtokenstorage_oath_token() -> Result<(), OauthTokenStorageError> {
         let scopes: Vec<&str> = Vec::new();
-        let mut db = OauthTokenStorage::new(Arc::new(SqliteDb::in_memory()?));
+        let mut db = OauthTokenStorage::new(Arc::new(SqliteDb::in_memory()?), cipher());
 
         assert!(db.get(0, &scopes)?.is_none());
 
@@ -150,4 +197,57 @@ mod test {
 
         Result::Ok(())
     }
+
+    #[test]
+    fn oauthtokenstorage_encrypts() -> Result<(), OauthTokenStorageError> {
+        let scopes: Vec<&str> = Vec::new();
+        let sqlite_db = Arc::new(SqliteDb::in_memory()?);
+        let mut db = OauthTokenStorage::new(sqlite_db.clone(), cipher());
+
+        let token = Token {
+            access_token: String::from("access_token"),
+            refresh_token: String::from("refresh_token"),
+            token_type: String::from("token_type"),
+            expires_in: Option::None,
+            expires_in_timestamp: Option::None,
+        };
+        db.set(0, &scopes, Option::Some(token.clone()))?;
+        let stored = sqlite_db.get_oath_token(0)?.unwrap();
+        assert!(!stored.contains("refresh_token"));
+        assert_eq!(db.decode(0, &stored)?, token);
+
+        // Another key can not read it
+        let other_db = OauthTokenStorage::new(sqlite_db.clone(), cipher());
+        assert!(other_db.get(0, &scopes).is_err());
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn oauthtokenstorage_encrypt_plaintext_tokens() -> Result<(), OauthTokenStorageError> {
+        let scopes: Vec<&str> = Vec::new();
+        let sqlite_db = Arc::new(SqliteDb::in_memory()?);
+        let db = OauthTokenStorage::new(sqlite_db.clone(), cipher());
+
+        let token = Token {
+            access_token: String::from("access_token"),
+            refresh_token: String::from("refresh_token"),
+            token_type: String::from("token_type"),
+            expires_in: Option::None,
+            expires_in_timestamp: Option::None,
+        };
+        sqlite_db.set_oath_token(0, Option::Some(serde_json::to_string(&token)?))?;
+
+        // Plain rows are still read, until they are migrated
+        assert_eq!(db.get(0, &scopes)?.unwrap(), token);
+        assert_eq!(db.encrypt_plaintext_tokens()?, 1);
+        assert!(!sqlite_db
+            .get_oath_token(0)?
+            .unwrap()
+            .contains("refresh_token"));
+        assert_eq!(db.get(0, &scopes)?.unwrap(), token);
+        assert_eq!(db.encrypt_plaintext_tokens()?, 0);
+
+        Result::Ok(())
+    }
 }
@@ -0,0 +1,128 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex};
+
+// Hands out each of a fixed set of values to one user at a time, blocking while all
+// are in use. The number of values is therefore also the concurrency limit.
+pub struct Pool<T> {
+    items: Mutex<Vec<T>>,
+    returned: Condvar,
+}
+
+impl<T> Pool<T> {
+    pub fn new(items: Vec<T>) -> Pool<T> {
+        assert!(!items.is_empty(), "A pool needs at least one item");
+        Pool {
+            items: Mutex::new(items),
+            returned: Condvar::new(),
+        }
+    }
+
+    pub fn get(&self) -> PoolGuard<'_, T> {
+        let mut items = self.items.lock().unwrap();
+        loop {
+            match items.pop() {
+                Some(item) => {
+                    return PoolGuard {
+                        pool: self,
+                        item: Option::Some(item),
+                    }
+                }
+                None => items = self.returned.wait(items).unwrap(),
+            }
+        }
+    }
+}
+
+// Returns the value to the pool when dropped
+pub struct PoolGuard<'a, T> {
+    pool: &'a Pool<T>,
+    item: Option<T>,
+}
+
+impl<'a, T> Deref for PoolGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.item.as_ref().unwrap()
+    }
+}
+
+impl<'a, T> DerefMut for PoolGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.item.as_mut().unwrap()
+    }
+}
+
+impl<'a, T> Drop for PoolGuard<'a, T> {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            // Still hand the item back if another user panicked while holding the lock
+            let mut items = match self.pool.items.lock() {
+                Ok(items) => items,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            items.push(item);
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    fn available<T>(pool: &Pool<T>) -> usize {
+        pool.items.lock().unwrap().len()
+    }
+
+    #[test]
+    fn pool_get_and_return() {
+        let pool = Pool::new(vec![1, 2]);
+        assert_eq!(available(&pool), 2);
+        {
+            let first = pool.get();
+            let second = pool.get();
+            assert_eq!(*first + *second, 3);
+            assert_eq!(available(&pool), 0);
+        }
+        assert_eq!(available(&pool), 2);
+    }
+
+    #[test]
+    fn pool_guard_deref_mut() {
+        let pool = Pool::new(vec![1]);
+        *pool.get() += 1;
+        assert_eq!(*pool.get(), 2);
+    }
+
+    #[test]
+    fn pool_blocks_until_returned() {
+        let pool = Arc::new(Pool::new(vec![()]));
+        let guard = pool.get();
+
+        let (sender, receiver) = mpsc::channel();
+        let waiter = {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                let _guard = pool.get();
+                sender.send(()).unwrap();
+            })
+        };
+
+        assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err());
+        drop(guard);
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+        waiter.join().unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn pool_empty() {
+        Pool::<u8>::new(Vec::new());
+    }
+}
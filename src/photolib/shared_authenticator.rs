@@ -0,0 +1,49 @@
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::oauth2::{GetToken, Token};
+
+// Lets several API clients use one authenticator, so a token is only fetched and
+// refreshed once however many requests run in parallel.
+// Tokens are always requested for the given scopes, whatever scopes a call asks for, so
+// one stored token serves all calls and can be fetched ahead by the auth command.
+#[derive(Debug, new)]
+pub struct SharedAuthenticator<A>
+where
+    A: GetToken,
+{
+    authenticator: Arc<Mutex<A>>,
+    scopes: Vec<String>,
+}
+
+impl<A> Clone for SharedAuthenticator<A>
+where
+    A: GetToken,
+{
+    fn clone(&self) -> Self {
+        SharedAuthenticator::new(self.authenticator.clone(), self.scopes.clone())
+    }
+}
+
+impl<A> GetToken for SharedAuthenticator<A>
+where
+    A: GetToken,
+{
+    fn token<'b, I, T>(&mut self, _scopes: I) -> Result<Token, Box<dyn Error>>
+    where
+        T: AsRef<str> + Ord + 'b,
+        I: IntoIterator<Item = &'b T>,
+    {
+        match self.authenticator.lock() {
+            Ok(mut authenticator) => authenticator.token(&self.scopes),
+            Err(_) => Result::Err(Box::from("Shared authenticator lock poisoned")),
+        }
+    }
+
+    fn api_key(&mut self) -> Option<String> {
+        match self.authenticator.lock() {
+            Ok(mut authenticator) => authenticator.api_key(),
+            Err(_) => Option::None,
+        }
+    }
+}
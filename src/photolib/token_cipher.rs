@@ -0,0 +1,203 @@
+use std::fmt;
+use std::result::Result;
+use std::string::FromUtf8Error;
+
+use base64;
+use ring;
+use ring::aead::{self, OpeningKey, SealingKey, CHACHA20_POLY1305};
+use ring::digest::SHA256;
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+
+// Rows written before tokens were encrypted hold the plain token JSON
+const ENCRYPTED_PREFIX: &str = "enc1:";
+const NONCE_LEN: usize = 12;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+pub const KEY_LEN: usize = 32;
+pub const SALT_LEN: usize = 16;
+
+#[derive(Debug)]
+pub enum TokenCipherError {
+    CryptoError,
+    DecodingError(base64::DecodeError),
+    Utf8Error(FromUtf8Error),
+}
+
+impl From<ring::error::Unspecified> for TokenCipherError {
+    fn from(_error: ring::error::Unspecified) -> Self {
+        TokenCipherError::CryptoError
+    }
+}
+
+impl From<base64::DecodeError> for TokenCipherError {
+    fn from(error: base64::DecodeError) -> Self {
+        TokenCipherError::DecodingError(error)
+    }
+}
+
+impl From<FromUtf8Error> for TokenCipherError {
+    fn from(error: FromUtf8Error) -> Self {
+        TokenCipherError::Utf8Error(error)
+    }
+}
+
+impl std::error::Error for TokenCipherError {}
+
+impl fmt::Display for TokenCipherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenCipherError::CryptoError => write!(f, "TokenCipherError: CryptoError"),
+            TokenCipherError::DecodingError(err) => {
+                write!(f, "TokenCipherError: DecodingError({})", err)
+            }
+            TokenCipherError::Utf8Error(err) => write!(f, "TokenCipherError: Utf8Error({})", err),
+        }
+    }
+}
+
+pub fn random_bytes(len: usize) -> Result<Vec<u8>, TokenCipherError> {
+    let mut bytes = vec![0u8; len];
+    SystemRandom::new().fill(&mut bytes)?;
+    Result::Ok(bytes)
+}
+
+// ChaCha20-Poly1305 with a random nonce per token, stored as base64 of nonce, ciphertext
+// and tag. The scope hash is authenticated as well, so a row can not be moved to others.
+pub struct TokenCipher {
+    sealing_key: SealingKey,
+    opening_key: OpeningKey,
+}
+
+impl fmt::Debug for TokenCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TokenCipher")
+    }
+}
+
+impl TokenCipher {
+    pub fn new(key: &[u8]) -> Result<TokenCipher, TokenCipherError> {
+        Result::Ok(TokenCipher {
+            sealing_key: SealingKey::new(&CHACHA20_POLY1305, key)?,
+            opening_key: OpeningKey::new(&CHACHA20_POLY1305, key)?,
+        })
+    }
+
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<TokenCipher, TokenCipherError> {
+        let mut key = [0u8; KEY_LEN];
+        pbkdf2::derive(
+            &SHA256,
+            PBKDF2_ITERATIONS,
+            salt,
+            passphrase.as_bytes(),
+            &mut key,
+        );
+        TokenCipher::new(&key)
+    }
+
+    pub fn is_encrypted(stored: &str) -> bool {
+        stored.starts_with(ENCRYPTED_PREFIX)
+    }
+
+    pub fn encrypt(&self, scope_hash: u64, plaintext: &str) -> Result<String, TokenCipherError> {
+        let mut sealed = random_bytes(NONCE_LEN)?;
+        sealed.extend_from_slice(plaintext.as_bytes());
+        sealed.extend_from_slice(&[0u8; aead::MAX_TAG_LEN]);
+        let (nonce, in_out) = sealed.split_at_mut(NONCE_LEN);
+        let out_len = aead::seal_in_place(
+            &self.sealing_key,
+            nonce,
+            &scope_hash.to_be_bytes(),
+            in_out,
+            aead::MAX_TAG_LEN,
+        )?;
+        sealed.truncate(NONCE_LEN + out_len);
+        Result::Ok(format!("{}{}", ENCRYPTED_PREFIX, base64::encode(&sealed)))
+    }
+
+    pub fn decrypt(&self, scope_hash: u64, stored: &str) -> Result<String, TokenCipherError> {
+        if !TokenCipher::is_encrypted(stored) {
+            return Result::Err(TokenCipherError::CryptoError);
+        }
+        let mut sealed = base64::decode(&stored[ENCRYPTED_PREFIX.len()..])?;
+        if sealed.len() < NONCE_LEN {
+            return Result::Err(TokenCipherError::CryptoError);
+        }
+        let (nonce, in_out) = sealed.split_at_mut(NONCE_LEN);
+        let plaintext = aead::open_in_place(
+            &self.opening_key,
+            nonce,
+            &scope_hash.to_be_bytes(),
+            0,
+            in_out,
+        )?;
+        Result::Ok(String::from_utf8(plaintext.to_vec())?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn token_cipher_round_trip() -> Result<(), TokenCipherError> {
+        let cipher = TokenCipher::new(&random_bytes(KEY_LEN)?)?;
+
+        let encrypted = cipher.encrypt(1, "{\"refresh_token\": \"1//refresh\"}")?;
+        assert!(TokenCipher::is_encrypted(&encrypted));
+        assert!(!encrypted.contains("refresh"));
+        assert_eq!(
+            cipher.decrypt(1, &encrypted)?,
+            "{\"refresh_token\": \"1//refresh\"}"
+        );
+
+        // Every encryption uses a new nonce
+        assert_ne!(cipher.encrypt(1, "token")?, cipher.encrypt(1, "token")?);
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn token_cipher_rejects() -> Result<(), TokenCipherError> {
+        let cipher = TokenCipher::new(&random_bytes(KEY_LEN)?)?;
+        let encrypted = cipher.encrypt(1, "token")?;
+
+        // Other keys, other scopes and tampered or plain rows
+        let other_cipher = TokenCipher::new(&random_bytes(KEY_LEN)?)?;
+        assert!(other_cipher.decrypt(1, &encrypted).is_err());
+        assert!(cipher.decrypt(2, &encrypted).is_err());
+        let mut sealed = base64::decode(&encrypted[ENCRYPTED_PREFIX.len()..])?;
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        let tampered = format!("{}{}", ENCRYPTED_PREFIX, base64::encode(&sealed));
+        assert!(cipher.decrypt(1, &tampered).is_err());
+        assert!(cipher.decrypt(1, "enc1:").is_err());
+        assert!(cipher.decrypt(1, "{\"token\": \"abc\"}").is_err());
+        assert!(!TokenCipher::is_encrypted("{\"token\": \"abc\"}"));
+
+        // Keys have to be 256 bits
+        assert!(TokenCipher::new(&[0u8; 16]).is_err());
+
+        Result::Ok(())
+    }
+
+    #[test]
+    fn token_cipher_from_passphrase() -> Result<(), TokenCipherError> {
+        let salt = random_bytes(SALT_LEN)?;
+        let encrypted = TokenCipher::from_passphrase("passphrase", &salt)?.encrypt(1, "token")?;
+
+        assert_eq!(
+            TokenCipher::from_passphrase("passphrase", &salt)?.decrypt(1, &encrypted)?,
+            "token"
+        );
+        assert!(TokenCipher::from_passphrase("other", &salt)?
+            .decrypt(1, &encrypted)
+            .is_err());
+        assert!(
+            TokenCipher::from_passphrase("passphrase", &random_bytes(SALT_LEN)?)?
+                .decrypt(1, &encrypted)
+                .is_err()
+        );
+
+        Result::Ok(())
+    }
+}
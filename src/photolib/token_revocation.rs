@@ -0,0 +1,59 @@
+use std::result::Result;
+
+use crate::oauth2::Token;
+use hyper;
+use hyper::header::ContentType;
+
+use crate::photolib::RemotePhotoLibError;
+
+const GOOGLE_REVOKE_URL: &str = "https://oauth2.googleapis.com/revoke";
+
+// Revoking the refresh token also revokes the access tokens issued with it
+fn token_to_revoke(token: &Token) -> &str {
+    if token.refresh_token.is_empty() {
+        &token.access_token
+    } else {
+        &token.refresh_token
+    }
+}
+
+pub fn revoke_token(client: &hyper::Client, token: &Token) -> Result<(), RemotePhotoLibError> {
+    let url =
+        hyper::Url::parse_with_params(GOOGLE_REVOKE_URL, &[("token", token_to_revoke(token))])
+            .map_err(hyper::Error::Uri)?;
+    let http_response = client
+        .post(url)
+        .header(ContentType::form_url_encoded())
+        .send()?;
+    match http_response.status {
+        hyper::status::StatusCode::Ok => Result::Ok(()),
+        // Google no longer knows the token, it expired or was revoked already
+        hyper::status::StatusCode::BadRequest => {
+            warn!("Token was already invalid, nothing to revoke");
+            Result::Ok(())
+        }
+        error => Result::Err(RemotePhotoLibError::HttpApiError(error)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json;
+
+    #[test]
+    fn token_to_revoke_prefers_refresh_token() {
+        let token: Token = serde_json::from_str(
+            "{\"access_token\": \"access\", \"refresh_token\": \"1//refresh\", \"token_type\": \"Bearer\", \"expires_in\": null, \"expires_in_timestamp\": 1546300800}",
+        )
+        .unwrap();
+        assert_eq!(token_to_revoke(&token), "1//refresh");
+
+        let token = Token {
+            refresh_token: String::new(),
+            ..token
+        };
+        assert_eq!(token_to_revoke(&token), "access");
+    }
+}
@@ -3,15 +3,32 @@ use std::result;
 
 use libc;
 
+#[cfg(target_os = "linux")]
+const ENOATTR: i32 = libc::ENODATA;
+#[cfg(not(target_os = "linux"))]
+const ENOATTR: i32 = libc::ENOATTR;
+
 #[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
 pub enum FuseError {
     FunctionNotImplemented,
+    NoAttribute,
+    BufferTooSmall,
+    PermissionDenied,
+    ReadOnlyFilesystem,
+    NotSupported,
+    IoError,
 }
 
 impl FuseError {
     pub fn libc_error_code(self) -> i32 {
         match self {
-            _ => libc::ENOENT,
+            FuseError::FunctionNotImplemented => libc::ENOENT,
+            FuseError::NoAttribute => ENOATTR,
+            FuseError::BufferTooSmall => libc::ERANGE,
+            FuseError::PermissionDenied => libc::EACCES,
+            FuseError::ReadOnlyFilesystem => libc::EROFS,
+            FuseError::NotSupported => libc::ENOSYS,
+            FuseError::IoError => libc::EIO,
         }
     }
 }
@@ -20,6 +37,12 @@ impl std::error::Error for FuseError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             FuseError::FunctionNotImplemented => Option::None,
+            FuseError::NoAttribute => Option::None,
+            FuseError::BufferTooSmall => Option::None,
+            FuseError::PermissionDenied => Option::None,
+            FuseError::ReadOnlyFilesystem => Option::None,
+            FuseError::NotSupported => Option::None,
+            FuseError::IoError => Option::None,
         }
     }
 }
@@ -28,6 +51,12 @@ impl fmt::Display for FuseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             FuseError::FunctionNotImplemented => write!(f, "FuseError: FunctionNotImplemented"),
+            FuseError::NoAttribute => write!(f, "FuseError: NoAttribute"),
+            FuseError::BufferTooSmall => write!(f, "FuseError: BufferTooSmall"),
+            FuseError::PermissionDenied => write!(f, "FuseError: PermissionDenied"),
+            FuseError::ReadOnlyFilesystem => write!(f, "FuseError: ReadOnlyFilesystem"),
+            FuseError::NotSupported => write!(f, "FuseError: NotSupported"),
+            FuseError::IoError => write!(f, "FuseError: IoError"),
         }
     }
 }
@@ -41,6 +70,12 @@ mod test {
     #[test]
     fn fuse_error_libc_error_code() {
         assert_eq!(FuseError::FunctionNotImplemented.libc_error_code(), 2);
+        assert_eq!(FuseError::NoAttribute.libc_error_code(), ENOATTR);
+        assert_eq!(FuseError::BufferTooSmall.libc_error_code(), libc::ERANGE);
+        assert_eq!(FuseError::PermissionDenied.libc_error_code(), libc::EACCES);
+        assert_eq!(FuseError::ReadOnlyFilesystem.libc_error_code(), libc::EROFS);
+        assert_eq!(FuseError::NotSupported.libc_error_code(), libc::ENOSYS);
+        assert_eq!(FuseError::IoError.libc_error_code(), libc::EIO);
     }
 
     #[test]
@@ -49,5 +84,26 @@ mod test {
             format!("{}", FuseError::FunctionNotImplemented),
             "FuseError: FunctionNotImplemented"
         );
+        assert_eq!(
+            format!("{}", FuseError::NoAttribute),
+            "FuseError: NoAttribute"
+        );
+        assert_eq!(
+            format!("{}", FuseError::BufferTooSmall),
+            "FuseError: BufferTooSmall"
+        );
+        assert_eq!(
+            format!("{}", FuseError::PermissionDenied),
+            "FuseError: PermissionDenied"
+        );
+        assert_eq!(
+            format!("{}", FuseError::ReadOnlyFilesystem),
+            "FuseError: ReadOnlyFilesystem"
+        );
+        assert_eq!(
+            format!("{}", FuseError::NotSupported),
+            "FuseError: NotSupported"
+        );
+        assert_eq!(format!("{}", FuseError::IoError), "FuseError: IoError");
     }
 }
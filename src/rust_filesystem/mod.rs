@@ -1,8 +1,13 @@
 use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::Arc;
 
 use fuse::{
-    self, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen,
+    self, Filesystem, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry,
+    ReplyOpen, ReplyWrite, ReplyXattr,
 };
+use time::Timespec;
 
 mod error;
 pub use self::error::{FuseError, FuseResult};
@@ -10,30 +15,38 @@ pub use self::error::{FuseError, FuseResult};
 mod response;
 pub use self::response::{
     FileAttrResponse, FileEntryResponse, OpenResponse, ReadDirEntry, ReadDirResponse, ReadResponse,
+    ReadlinkResponse, XattrResponse,
 };
 
 mod request;
-pub use self::request::UniqRequest;
+pub use self::request::{RequestInfo, UniqRequest};
 
-pub trait RustFilesystem {
+mod worker_pool;
+pub use self::worker_pool::WorkerPool;
+
+// Default number of threads handling slow operations (opening, i.e. downloading, files)
+const DEFAULT_WORKERS: usize = 4;
+
+// Called from several threads at once, so implementations synchronize internally
+pub trait RustFilesystem: Send + Sync {
     fn lookup(
-        &mut self,
+        &self,
         req: &dyn UniqRequest,
         parent: u64,
         name: &OsStr,
-    ) -> FuseResult<FileEntryResponse<'_>>;
-    fn getattr(&mut self, req: &dyn UniqRequest, ino: u64) -> FuseResult<FileAttrResponse<'_>>;
-    fn open(&mut self, req: &dyn UniqRequest, ino: u64, flags: u32) -> FuseResult<OpenResponse>;
+    ) -> FuseResult<FileEntryResponse>;
+    fn getattr(&self, req: &dyn UniqRequest, ino: u64) -> FuseResult<FileAttrResponse>;
+    fn open(&self, req: &dyn UniqRequest, ino: u64, flags: u32) -> FuseResult<OpenResponse>;
     fn read(
-        &mut self,
+        &self,
         req: &dyn UniqRequest,
         ino: u64,
         fh: u64,
         offset: i64,
         size: u32,
-    ) -> FuseResult<ReadResponse<'_>>;
+    ) -> FuseResult<ReadResponse>;
     fn release(
-        &mut self,
+        &self,
         req: &dyn UniqRequest,
         ino: u64,
         fh: u64,
@@ -41,56 +54,103 @@ pub trait RustFilesystem {
         lock_owner: u64,
         flush: bool,
     ) -> FuseResult<()>;
-    fn opendir(&mut self, req: &dyn UniqRequest, ino: u64, flags: u32) -> FuseResult<OpenResponse>;
+    fn opendir(&self, req: &dyn UniqRequest, ino: u64, flags: u32) -> FuseResult<OpenResponse>;
     fn readdir(
-        &mut self,
+        &self,
         req: &dyn UniqRequest,
         ino: u64,
         fh: u64,
         offset: i64,
-    ) -> FuseResult<ReadDirResponse<'_>>;
-    fn releasedir(
-        &mut self,
-        req: &dyn UniqRequest,
-        ino: u64,
-        fh: u64,
-        flags: u32,
-    ) -> FuseResult<()>;
-    fn destroy(&mut self, req: &dyn UniqRequest);
+    ) -> FuseResult<ReadDirResponse>;
+    fn releasedir(&self, req: &dyn UniqRequest, ino: u64, fh: u64, flags: u32) -> FuseResult<()>;
+    fn readlink(&self, req: &dyn UniqRequest, ino: u64) -> FuseResult<ReadlinkResponse>;
+    fn access(&self, req: &dyn UniqRequest, ino: u64, mask: u32) -> FuseResult<()>;
+    fn getxattr(&self, req: &dyn UniqRequest, ino: u64, name: &OsStr) -> FuseResult<XattrResponse>;
+    // Attribute names are returned NUL separated
+    fn listxattr(&self, req: &dyn UniqRequest, ino: u64) -> FuseResult<XattrResponse>;
+    fn destroy(&self, req: &dyn UniqRequest);
+    // Asked before anything would be changed, read-only mounts refuse with ReadOnlyFilesystem
+    fn check_writable(&self, req: &dyn UniqRequest) -> FuseResult<()>;
+}
+
+// A size of zero asks for the length of the value, otherwise the value must fit in size
+fn reply_xattr(response: FuseResult<XattrResponse>, size: u32, reply: ReplyXattr) {
+    match response {
+        Ok(response) => {
+            if size == 0 {
+                reply.size(response.data.len() as u32);
+            } else if response.data.len() > size as usize {
+                reply.error(FuseError::BufferTooSmall.libc_error_code());
+            } else {
+                reply.data(&response.data);
+            }
+        }
+        Err(error) => reply.error(error.libc_error_code()),
+    }
+}
+
+// Nothing can be changed yet, but read-only mounts say so with EROFS rather than ENOSYS
+fn write_error<X: RustFilesystem>(fs: &X, req: &dyn UniqRequest) -> i32 {
+    match fs.check_writable(req) {
+        Ok(_) => FuseError::NotSupported.libc_error_code(),
+        Err(error) => error.libc_error_code(),
+    }
 }
 
-#[derive(Debug, new)]
 pub struct RustFilesystemReal<X>
 where
     X: RustFilesystem,
 {
-    fs: X,
+    fs: Arc<X>,
+    workers: WorkerPool,
 }
 
-impl<X> Filesystem for RustFilesystemReal<X>
+impl<X> RustFilesystemReal<X>
 where
     X: RustFilesystem,
+{
+    pub fn new(fs: X) -> RustFilesystemReal<X> {
+        RustFilesystemReal::with_workers(fs, DEFAULT_WORKERS)
+    }
+
+    pub fn with_workers(fs: X, workers: usize) -> RustFilesystemReal<X> {
+        RustFilesystemReal {
+            fs: Arc::new(fs),
+            workers: WorkerPool::new(workers),
+        }
+    }
+}
+
+// Metadata operations are answered from the local DB and replied to directly, anything
+// that may hit the network runs on the worker pool and replies once done.
+impl<X> Filesystem for RustFilesystemReal<X>
+where
+    X: RustFilesystem + 'static,
 {
     fn lookup(&mut self, req: &fuse::Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
         debug!("lookup: {:?}", req);
+        // An entry with inode 0 is a negative entry, cached by the kernel for its TTL
         match self.fs.lookup(req, parent, name) {
-            Ok(response) => reply.entry(response.ttl, &response.attr, response.generation),
+            Ok(response) => reply.entry(&response.ttl, &response.attr, response.generation),
             Err(error) => reply.error(error.libc_error_code()),
         }
     }
 
     fn getattr(&mut self, req: &fuse::Request<'_>, ino: u64, reply: ReplyAttr) {
         match self.fs.getattr(req, ino) {
-            Ok(response) => reply.attr(response.ttl, &response.attr),
+            Ok(response) => reply.attr(&response.ttl, &response.attr),
             Err(error) => reply.error(error.libc_error_code()),
         }
     }
 
     fn open(&mut self, req: &fuse::Request<'_>, ino: u64, flags: u32, reply: ReplyOpen) {
-        match self.fs.open(req, ino, flags) {
-            Ok(response) => reply.opened(response.fh, response.flags),
-            Err(error) => reply.error(error.libc_error_code()),
-        }
+        let fs = self.fs.clone();
+        let req = RequestInfo::from(req as &dyn UniqRequest);
+        self.workers
+            .execute(move || match fs.open(&req, ino, flags) {
+                Ok(response) => reply.opened(response.fh, response.flags),
+                Err(error) => reply.error(error.libc_error_code()),
+            });
     }
 
     fn read(
@@ -103,7 +163,7 @@ where
         reply: ReplyData,
     ) {
         match self.fs.read(req, ino, fh, offset, size) {
-            Ok(response) => reply.data(response.data),
+            Ok(response) => reply.data(&response.data),
             Err(error) => reply.error(error.libc_error_code()),
         }
     }
@@ -171,7 +231,169 @@ where
         }
     }
 
+    fn readlink(&mut self, req: &fuse::Request<'_>, ino: u64, reply: ReplyData) {
+        match self.fs.readlink(req, ino) {
+            Ok(response) => reply.data(response.target.as_bytes()),
+            Err(error) => reply.error(error.libc_error_code()),
+        }
+    }
+
+    fn access(&mut self, req: &fuse::Request<'_>, ino: u64, mask: u32, reply: ReplyEmpty) {
+        match self.fs.access(req, ino, mask) {
+            Ok(_) => reply.ok(),
+            Err(error) => reply.error(error.libc_error_code()),
+        }
+    }
+
+    fn getxattr(
+        &mut self,
+        req: &fuse::Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: ReplyXattr,
+    ) {
+        reply_xattr(self.fs.getxattr(req, ino, name), size, reply);
+    }
+
+    fn listxattr(&mut self, req: &fuse::Request<'_>, ino: u64, size: u32, reply: ReplyXattr) {
+        reply_xattr(self.fs.listxattr(req, ino), size, reply);
+    }
+
     fn destroy(&mut self, req: &fuse::Request<'_>) {
         self.fs.destroy(req);
     }
+
+    fn setattr(
+        &mut self,
+        req: &fuse::Request<'_>,
+        _ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        _size: Option<u64>,
+        _atime: Option<Timespec>,
+        _mtime: Option<Timespec>,
+        _fh: Option<u64>,
+        _crtime: Option<Timespec>,
+        _chgtime: Option<Timespec>,
+        _bkuptime: Option<Timespec>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        reply.error(write_error(&*self.fs, req));
+    }
+
+    fn mknod(
+        &mut self,
+        req: &fuse::Request<'_>,
+        _parent: u64,
+        _name: &OsStr,
+        _mode: u32,
+        _rdev: u32,
+        reply: ReplyEntry,
+    ) {
+        reply.error(write_error(&*self.fs, req));
+    }
+
+    fn mkdir(
+        &mut self,
+        req: &fuse::Request<'_>,
+        _parent: u64,
+        _name: &OsStr,
+        _mode: u32,
+        reply: ReplyEntry,
+    ) {
+        reply.error(write_error(&*self.fs, req));
+    }
+
+    fn unlink(&mut self, req: &fuse::Request<'_>, _parent: u64, _name: &OsStr, reply: ReplyEmpty) {
+        reply.error(write_error(&*self.fs, req));
+    }
+
+    fn rmdir(&mut self, req: &fuse::Request<'_>, _parent: u64, _name: &OsStr, reply: ReplyEmpty) {
+        reply.error(write_error(&*self.fs, req));
+    }
+
+    fn symlink(
+        &mut self,
+        req: &fuse::Request<'_>,
+        _parent: u64,
+        _name: &OsStr,
+        _link: &Path,
+        reply: ReplyEntry,
+    ) {
+        reply.error(write_error(&*self.fs, req));
+    }
+
+    fn rename(
+        &mut self,
+        req: &fuse::Request<'_>,
+        _parent: u64,
+        _name: &OsStr,
+        _newparent: u64,
+        _newname: &OsStr,
+        reply: ReplyEmpty,
+    ) {
+        reply.error(write_error(&*self.fs, req));
+    }
+
+    fn link(
+        &mut self,
+        req: &fuse::Request<'_>,
+        _ino: u64,
+        _newparent: u64,
+        _newname: &OsStr,
+        reply: ReplyEntry,
+    ) {
+        reply.error(write_error(&*self.fs, req));
+    }
+
+    fn write(
+        &mut self,
+        req: &fuse::Request<'_>,
+        _ino: u64,
+        _fh: u64,
+        _offset: i64,
+        _data: &[u8],
+        _flags: u32,
+        reply: ReplyWrite,
+    ) {
+        reply.error(write_error(&*self.fs, req));
+    }
+
+    fn setxattr(
+        &mut self,
+        req: &fuse::Request<'_>,
+        _ino: u64,
+        _name: &OsStr,
+        _value: &[u8],
+        _flags: u32,
+        _position: u32,
+        reply: ReplyEmpty,
+    ) {
+        reply.error(write_error(&*self.fs, req));
+    }
+
+    fn removexattr(
+        &mut self,
+        req: &fuse::Request<'_>,
+        _ino: u64,
+        _name: &OsStr,
+        reply: ReplyEmpty,
+    ) {
+        reply.error(write_error(&*self.fs, req));
+    }
+
+    fn create(
+        &mut self,
+        req: &fuse::Request<'_>,
+        _parent: u64,
+        _name: &OsStr,
+        _mode: u32,
+        _flags: u32,
+        reply: ReplyCreate,
+    ) {
+        reply.error(write_error(&*self.fs, req));
+    }
 }
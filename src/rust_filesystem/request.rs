@@ -21,3 +21,50 @@ impl<'a> UniqRequest for Request<'a> {
         self.pid()
     }
 }
+
+// A copy of a request that can be handed to another thread
+#[derive(Clone, Copy, PartialEq, Eq, Debug, new)]
+pub struct RequestInfo {
+    unique: u64,
+    uid: u32,
+    gid: u32,
+    pid: u32,
+}
+
+impl<'a> From<&'a dyn UniqRequest> for RequestInfo {
+    fn from(req: &'a dyn UniqRequest) -> RequestInfo {
+        RequestInfo::new(req.unique(), req.uid(), req.gid(), req.pid())
+    }
+}
+
+impl UniqRequest for RequestInfo {
+    fn unique(&self) -> u64 {
+        self.unique
+    }
+    fn uid(&self) -> u32 {
+        self.uid
+    }
+    fn gid(&self) -> u32 {
+        self.gid
+    }
+    fn pid(&self) -> u32 {
+        self.pid
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn request_info_from_uniq_request() {
+        let req = RequestInfo::new(1, 2, 3, 4);
+        let copy = RequestInfo::from(&req as &dyn UniqRequest);
+
+        assert_eq!(copy, req);
+        assert_eq!(copy.unique(), 1);
+        assert_eq!(copy.uid(), 2);
+        assert_eq!(copy.gid(), 3);
+        assert_eq!(copy.pid(), 4);
+    }
+}
@@ -1,18 +1,18 @@
-use std::ffi::OsStr;
+use std::ffi::OsString;
 
 use fuse::{FileAttr, FileType};
 use time::Timespec;
 
 #[derive(Clone, Copy, Debug)]
-pub struct FileEntryResponse<'a> {
-    pub ttl: &'a Timespec,
+pub struct FileEntryResponse {
+    pub ttl: Timespec,
     pub attr: FileAttr,
     pub generation: u64,
 }
 
 #[derive(Clone, Copy, Debug)]
-pub struct FileAttrResponse<'a> {
-    pub ttl: &'a Timespec,
+pub struct FileAttrResponse {
+    pub ttl: Timespec,
     pub attr: FileAttr,
 }
 
@@ -23,19 +23,29 @@ pub struct OpenResponse {
 }
 
 #[derive(PartialEq, Eq, Debug)]
-pub struct ReadResponse<'a> {
-    pub data: &'a [u8],
+pub struct ReadResponse {
+    pub data: Vec<u8>,
 }
 
 #[derive(PartialEq, Debug)]
-pub struct ReadDirResponse<'a> {
-    pub entries: Vec<ReadDirEntry<'a>>,
+pub struct ReadDirResponse {
+    pub entries: Vec<ReadDirEntry>,
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub struct ReadDirEntry<'a> {
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReadDirEntry {
     pub ino: u64,
     pub offset: i64,
     pub kind: FileType,
-    pub name: &'a OsStr,
+    pub name: OsString,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct ReadlinkResponse {
+    pub target: OsString,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct XattrResponse {
+    pub data: Vec<u8>,
 }
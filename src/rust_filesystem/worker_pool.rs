@@ -0,0 +1,165 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// Fixed number of threads running jobs in the order they were queued
+pub struct WorkerPool {
+    sender: Mutex<Option<Sender<Job>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(size: usize) -> WorkerPool {
+        WorkerPool::named("fuse-worker", size)
+    }
+
+    pub fn named(name: &str, size: usize) -> WorkerPool {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..usize::max(size, 1))
+            .map(|index| {
+                let receiver = receiver.clone();
+                thread::Builder::new()
+                    .name(format!("{}-{}", name, index))
+                    .spawn(move || WorkerPool::run(&receiver))
+                    .expect("Failed to spawn worker thread")
+            })
+            .collect();
+
+        WorkerPool {
+            sender: Mutex::new(Option::Some(sender)),
+            workers,
+        }
+    }
+
+    fn run(receiver: &Mutex<Receiver<Job>>) {
+        loop {
+            // Only hold the lock while waiting, not while running the job
+            let job = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(_) => return,
+            };
+            match job {
+                // A panicking job must not take its worker with it, once all are gone the
+                // queued jobs are never run. FUSE replies dropped by the job send EIO.
+                Ok(job) => {
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        error!("Worker job panicked");
+                    }
+                }
+                Err(_) => return, // Pool dropped
+            }
+        }
+    }
+
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let sender = self.sender.lock().unwrap();
+        if let Some(sender) = sender.as_ref() {
+            if sender.send(Box::new(job)).is_err() {
+                error!("Worker pool is gone, dropping job");
+            }
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+}
+
+// Finishes the queued jobs before returning
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        if let Ok(mut sender) = self.sender.lock() {
+            sender.take();
+        }
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                error!("Worker thread panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Condvar;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn worker_pool_size() {
+        assert_eq!(WorkerPool::new(3).size(), 3);
+        assert_eq!(WorkerPool::new(0).size(), 1);
+    }
+
+    #[test]
+    fn worker_pool_runs_all_jobs() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        {
+            let pool = WorkerPool::new(2);
+            for _ in 0..10 {
+                let counter = counter.clone();
+                pool.execute(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn worker_pool_runs_jobs_in_parallel() {
+        // Each job waits a while for the other to start, so a failure ends instead of
+        // blocking the pool forever
+        let started = Arc::new((Mutex::new(0), Condvar::new()));
+        let (sender, receiver) = mpsc::channel();
+        let pool = WorkerPool::new(2);
+        for _ in 0..2 {
+            let started = started.clone();
+            let sender = sender.clone();
+            pool.execute(move || {
+                let (count, condvar) = &*started;
+                let mut count = count.lock().unwrap();
+                *count += 1;
+                condvar.notify_all();
+                let deadline = Instant::now() + Duration::from_secs(5);
+                while *count < 2 && Instant::now() < deadline {
+                    count = condvar
+                        .wait_timeout(count, deadline - Instant::now())
+                        .unwrap()
+                        .0;
+                }
+                sender.send(*count == 2).unwrap();
+            });
+        }
+        for _ in 0..2 {
+            assert_eq!(
+                receiver.recv_timeout(Duration::from_secs(10)),
+                Result::Ok(true)
+            );
+        }
+    }
+
+    #[test]
+    fn worker_pool_survives_panicking_jobs() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        {
+            let pool = WorkerPool::new(1);
+            pool.execute(|| panic!("Job panicked on purpose"));
+            let counter = counter.clone();
+            pool.execute(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+}
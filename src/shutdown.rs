@@ -0,0 +1,62 @@
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+
+use signal_hook;
+use signal_hook::iterator::Signals;
+
+// fuse::mount only returns once the mountpoint is unmounted, so SIGINT and SIGTERM are
+// answered by unmounting it. A busy mountpoint is detached lazily on the next signal.
+pub fn unmount_on_signal(mountpoint: &Path) -> io::Result<()> {
+    let signals = Signals::new(&[signal_hook::SIGINT, signal_hook::SIGTERM])?;
+    let mountpoint = mountpoint.to_path_buf();
+    thread::Builder::new()
+        .name(String::from("signals"))
+        .spawn(move || handle_signals(&signals, &mountpoint))?;
+    Result::Ok(())
+}
+
+fn handle_signals(signals: &Signals, mountpoint: &Path) {
+    let mut lazy = false;
+    let mut unmounted = false;
+    for signal in signals.forever() {
+        if unmounted {
+            info!("Received signal {}, already shutting down", signal);
+            continue;
+        }
+        info!(
+            "Received signal {}, unmounting {}",
+            signal,
+            mountpoint.display()
+        );
+        match unmount(mountpoint, lazy) {
+            Ok(()) => unmounted = true,
+            Err(error) => {
+                warn!(
+                    "Failed to unmount {}: {}, signal again to detach it lazily",
+                    mountpoint.display(),
+                    error
+                );
+                lazy = true;
+            }
+        }
+    }
+}
+
+fn unmount(mountpoint: &Path, lazy: bool) -> io::Result<()> {
+    let mut command = Command::new("fusermount");
+    command.arg("-u");
+    if lazy {
+        command.arg("-z");
+    }
+    let status = command.arg(mountpoint).status()?;
+    if status.success() {
+        Result::Ok(())
+    } else {
+        Result::Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("fusermount {}", status),
+        ))
+    }
+}
@@ -0,0 +1,157 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+
+use crate::config::{Config, TokenKeySource};
+use crate::db::TokenStorageDb;
+use crate::error::PhotoOxideError;
+use crate::photolib::{random_bytes, TokenCipher, KEY_LEN, SALT_LEN};
+
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "photooxide";
+
+pub fn token_cipher<D>(config: &Config, db: &D) -> Result<TokenCipher, PhotoOxideError>
+where
+    D: TokenStorageDb,
+{
+    let cipher = match config.token_key.source {
+        TokenKeySource::File => TokenCipher::new(&key_from_file(&config.token_key.file)?),
+        TokenKeySource::Keyring => TokenCipher::new(&key_from_keyring(&config.db_path)?),
+        TokenKeySource::Passphrase => {
+            let salt = match db.token_key_salt()? {
+                Option::Some(salt) => salt,
+                Option::None => {
+                    let salt = random_bytes(SALT_LEN).map_err(key_error)?;
+                    db.set_token_key_salt(&salt)?;
+                    salt
+                }
+            };
+            TokenCipher::from_passphrase(&passphrase()?, &salt)
+        }
+    };
+    cipher.map_err(key_error)
+}
+
+fn key_error<E: std::fmt::Display>(error: E) -> PhotoOxideError {
+    PhotoOxideError::ConfigError(format!("Token key: {}", error))
+}
+
+// Created on first use with a random key, an existing file must not be readable by others
+fn key_from_file(path: &Path) -> Result<Vec<u8>, PhotoOxideError> {
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            if metadata.permissions().mode() & 0o077 != 0 {
+                return Result::Err(PhotoOxideError::ConfigError(format!(
+                    "Token key file {} is accessible by other users, chmod 600 it",
+                    path.display()
+                )));
+            }
+            let key = fs::read(path)?;
+            if key.len() != KEY_LEN {
+                return Result::Err(PhotoOxideError::ConfigError(format!(
+                    "Token key file {} is not a {} byte key",
+                    path.display(),
+                    KEY_LEN
+                )));
+            }
+            Result::Ok(key)
+        }
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => {
+            let key = random_bytes(KEY_LEN).map_err(key_error)?;
+            if let Option::Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(path)?
+                .write_all(&key)?;
+            info!("Created token key file {}", path.display());
+            Result::Ok(key)
+        }
+        Err(error) => Result::Err(PhotoOxideError::from(error)),
+    }
+}
+
+// One key per DB, kept base64 encoded under the path of the DB
+#[cfg(feature = "keyring")]
+fn key_from_keyring(db_path: &Path) -> Result<Vec<u8>, PhotoOxideError> {
+    let user = format!("{}", db_path.display());
+    let keyring = keyring::Keyring::new(KEYRING_SERVICE, &user);
+    match keyring.get_password() {
+        Ok(key) => base64::decode(&key).map_err(key_error),
+        Err(keyring::KeyringError::NoPasswordFound) => {
+            let key = random_bytes(KEY_LEN).map_err(key_error)?;
+            keyring
+                .set_password(&base64::encode(&key))
+                .map_err(key_error)?;
+            info!("Stored a new token key in the keyring");
+            Result::Ok(key)
+        }
+        Err(error) => Result::Err(key_error(error)),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn key_from_keyring(_db_path: &Path) -> Result<Vec<u8>, PhotoOxideError> {
+    Result::Err(PhotoOxideError::ConfigError(String::from(
+        "Built without keyring support, rebuild with the keyring feature or use another token key source",
+    )))
+}
+
+fn passphrase() -> Result<String, PhotoOxideError> {
+    let passphrase = match env::var("PHOTOOXIDE_TOKEN_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) => rpassword::read_password_from_tty(Option::Some("Token passphrase: "))?,
+    };
+    if passphrase.is_empty() {
+        return Result::Err(PhotoOxideError::ConfigError(String::from(
+            "Empty token passphrase",
+        )));
+    }
+    Result::Ok(passphrase)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::PathBuf;
+    use std::process;
+
+    fn temp_key_file(name: &str) -> PathBuf {
+        let path = env::temp_dir()
+            .join(format!("photooxide-test-{}", process::id()))
+            .join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn token_key_from_file() {
+        let path = temp_key_file("token.key");
+
+        // Created on first use, then read back
+        let key = key_from_file(&path).unwrap();
+        assert_eq!(key.len(), KEY_LEN);
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(key_from_file(&path).unwrap(), key);
+
+        // Files readable by others are refused
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(key_from_file(&path).is_err());
+
+        // As are keys of the wrong size
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        fs::write(&path, b"short").unwrap();
+        assert!(key_from_file(&path).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}